@@ -0,0 +1,166 @@
+use super::color::{Color, WHITE};
+use super::comps::Comps;
+use super::integrator::Integrator;
+use super::ray::Ray;
+use super::sampling::{self, Rng};
+use super::world::World;
+
+/// Estimates how much of the hemisphere above a surface is blocked by nearby objects.
+///
+/// Rays are cast in random directions around the normal. A ray is occluded when it
+/// hits an object closer than `max_distance`. The same hit point always gets the
+/// same rays, so tiled renders match full renders.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AmbientOcclusion {
+    pub samples: usize,
+    pub max_distance: f64,
+    pub seed: u64,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: usize, max_distance: f64) -> AmbientOcclusion {
+        AmbientOcclusion {
+            samples,
+            max_distance,
+            seed: 0,
+        }
+    }
+
+    /// The fraction of the hemisphere that is not occluded,
+    /// from 0 (fully occluded) to 1 (fully open).
+    pub fn accessibility(&self, w: &World, comps: &Comps) -> f64 {
+        if self.samples == 0 {
+            return 1.;
+        }
+
+        let mut rng = Rng::for_point(self.seed, &comps.point);
+        let mut occluded = 0;
+        for _ in 0..self.samples {
+            let direction = sampling::cosine_weighted_hemisphere(&comps.normalv, &mut rng);
+            let r = Ray::new(&comps.over_point, &direction);
+            if let Some(h) = w.intersect(&r).hit()
+                && h.t < self.max_distance
+            {
+                occluded += 1;
+            }
+        }
+        1. - occluded as f64 / self.samples as f64
+    }
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        AmbientOcclusion::new(16, 1.)
+    }
+}
+
+/// Standalone ambient occlusion pass: white where the surface is open,
+/// darker in creases and under objects. Rays that miss are white.
+impl Integrator for AmbientOcclusion {
+    fn color_at(&self, w: &World, r: &Ray) -> Color {
+        let i = w.intersect(r);
+        match i.hit() {
+            None => WHITE,
+            Some(h) => {
+                let comps = Comps::prepare_computations(h, r, Some(i.clone()));
+                WHITE * self.accessibility(w, &comps)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::BLACK;
+    use crate::integrator::Whitted;
+    use crate::intersection::Intersection;
+    use crate::light::PointLight;
+    use crate::material::Material;
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+    use crate::transform;
+    use crate::tuple::Tuple;
+
+    fn floor_with_ball(height: f64) -> World {
+        let ball = Sphere::new_boxed(Some(transform::translation(0., height, 0.)), None);
+        World::new(None, vec![Plane::default_boxed(), ball])
+    }
+
+    fn floor_comps(w: &World) -> Comps {
+        let r = Ray::new(&Tuple::point(0., 0.5, -0.5), &Tuple::vector(0., -1., 0.));
+        let i = Intersection::new(0.5, w.shapes[0].clone());
+        Comps::prepare_computations(&i, &r, None)
+    }
+
+    #[test]
+    fn an_open_surface_is_not_occluded() {
+        let w = World::new(None, vec![Plane::default_boxed()]);
+        let ao = AmbientOcclusion::new(32, 10.);
+
+        assert_eq!(1., ao.accessibility(&w, &floor_comps(&w)));
+    }
+
+    #[test]
+    fn a_surface_next_to_an_object_is_occluded() {
+        let w = floor_with_ball(1.);
+        let ao = AmbientOcclusion::new(64, 10.);
+
+        let a = ao.accessibility(&w, &floor_comps(&w));
+        assert!(a < 0.8);
+        assert!(a > 0.);
+    }
+
+    #[test]
+    fn objects_beyond_the_maximum_distance_do_not_occlude() {
+        let w = floor_with_ball(5.);
+        let ao = AmbientOcclusion::new(64, 2.);
+
+        assert_eq!(1., ao.accessibility(&w, &floor_comps(&w)));
+    }
+
+    #[test]
+    fn the_same_point_is_always_sampled_the_same_way() {
+        let w = floor_with_ball(1.);
+        let ao = AmbientOcclusion::new(16, 10.);
+        let comps = floor_comps(&w);
+
+        assert_eq!(ao.accessibility(&w, &comps), ao.accessibility(&w, &comps));
+    }
+
+    #[test]
+    fn the_ambient_occlusion_pass() {
+        let w = floor_with_ball(1.);
+        let ao = AmbientOcclusion::new(64, 10.);
+
+        let miss = Ray::new(&Tuple::point(0., 0.5, -3.), &Tuple::vector(0., 1., -1.));
+        assert_eq!(WHITE, ao.color_at(&w, &miss));
+
+        let under = Ray::new(&Tuple::point(0., 0.5, -0.5), &Tuple::vector(0., -1., 0.));
+        let c = ao.color_at(&w, &under);
+        assert!(c.r < 0.8);
+        assert_eq!(c.r, c.g);
+    }
+
+    #[test]
+    fn ambient_occlusion_darkens_the_ambient_term() {
+        // The light is below the floor, so only the ambient term remains
+        let light = PointLight::new(&Tuple::point(0., -10., 0.), &WHITE);
+        let mut w = floor_with_ball(1.);
+        w.light = Some(light);
+        let comps = floor_comps(&w);
+        let ao = AmbientOcclusion::new(64, 10.);
+
+        let flat = Whitted::default().shade_hit(&w, &comps, 1);
+        assert_eq!(Color::new(0.1, 0.1, 0.1), flat);
+
+        let whitted = Whitted {
+            ambient_occlusion: Some(ao),
+            ..Default::default()
+        };
+        let occluded = whitted.shade_hit(&w, &comps, 1);
+        let expected = Material::default().color * 0.1 * ao.accessibility(&w, &comps);
+        assert_eq!(expected, occluded);
+        assert_ne!(BLACK, occluded);
+    }
+}
@@ -0,0 +1,303 @@
+use super::camera::Camera;
+use super::canvas::Canvas;
+use super::color::{BLACK, Color, WHITE};
+use super::comps::Comps;
+use super::intersection::Intersection;
+use super::material::Material;
+use super::ray::Ray;
+use super::tuple::Tuple;
+use super::world::World;
+
+/// What the camera ray of one pixel hit first.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SurfaceSample {
+    /// Distance from the camera to the hit
+    pub distance: f64,
+    /// World space normal, facing the camera
+    pub normal: Tuple,
+    /// Surface color before lighting
+    pub albedo: Color,
+    /// Index of the object in `World::shapes`
+    pub object_id: usize,
+    /// Objects with equal materials share the same id
+    pub material_id: usize,
+}
+
+/// Per-pixel information about the visible surfaces, used for compositing,
+/// denoising and debugging. Pixels where the ray misses have no sample.
+#[derive(Debug)]
+pub struct AuxiliaryBuffers {
+    pub width: usize,
+    pub height: usize,
+    samples: Vec<Option<SurfaceSample>>,
+}
+
+impl AuxiliaryBuffers {
+    /// Casts one ray through the center of every pixel.
+    pub fn render(camera: &Camera, w: &World) -> AuxiliaryBuffers {
+        let material_ids = material_ids(w);
+
+        let mut samples = Vec::with_capacity(camera.hsize * camera.vsize);
+        for y in 0..camera.vsize {
+            for x in 0..camera.hsize {
+                let r = camera.ray_for_pixel(x, y);
+                samples.push(surface_sample(w, &r, &material_ids));
+            }
+        }
+
+        AuxiliaryBuffers {
+            width: camera.hsize,
+            height: camera.vsize,
+            samples,
+        }
+    }
+
+    pub fn sample_at(&self, x: usize, y: usize) -> Option<&SurfaceSample> {
+        self.samples[y * self.width + x].as_ref()
+    }
+
+    /// Hit distances in world units, the same in the three channels. Misses are infinitely
+    /// far. The values are not limited to [0, 1], save them with `hdr::write_pfm`, or with
+    /// `hdr::write_rgbe` which stores the misses as its largest value.
+    pub fn depth(&self) -> Canvas {
+        let infinity = Color::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        self.to_canvas(infinity, |s| Color::new(s.distance, s.distance, s.distance))
+    }
+
+    /// Hit distance as shades of gray for display, from black at the camera to white
+    /// at the farthest hit of this render. Misses are white.
+    pub fn depth_visualization(&self) -> Canvas {
+        let farthest = self
+            .samples
+            .iter()
+            .flatten()
+            .map(|s| s.distance)
+            .fold(0., f64::max);
+
+        self.to_canvas(WHITE, |s| {
+            let d = if farthest > 0. {
+                s.distance / farthest
+            } else {
+                0.
+            };
+            Color::new(d, d, d)
+        })
+    }
+
+    /// Normals with each component mapped from [-1, 1] to [0, 1]. Misses are black.
+    pub fn normals(&self) -> Canvas {
+        self.to_canvas(BLACK, |s| {
+            let n = s.normal;
+            Color::new((n.x + 1.) / 2., (n.y + 1.) / 2., (n.z + 1.) / 2.)
+        })
+    }
+
+    /// Unlit surface colors. Misses are black.
+    pub fn albedo(&self) -> Canvas {
+        self.to_canvas(BLACK, |s| s.albedo)
+    }
+
+    /// A distinct color for each object. Misses are black.
+    pub fn object_ids(&self) -> Canvas {
+        self.to_canvas(BLACK, |s| id_color(s.object_id))
+    }
+
+    /// A distinct color for each material. Misses are black.
+    pub fn material_ids(&self) -> Canvas {
+        self.to_canvas(BLACK, |s| id_color(s.material_id))
+    }
+
+    fn to_canvas<F>(&self, miss: Color, color: F) -> Canvas
+    where
+        F: Fn(&SurfaceSample) -> Color,
+    {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let c = self.sample_at(x, y).map_or(miss, &color);
+                canvas.write_pixel(x, y, c);
+            }
+        }
+        canvas
+    }
+}
+
+fn material_ids(w: &World) -> Vec<usize> {
+    let mut materials: Vec<&Material> = vec![];
+    w.shapes
+        .iter()
+        .map(|shape| {
+            let m = shape.material();
+            match materials.iter().position(|known| *known == m) {
+                Some(id) => id,
+                None => {
+                    materials.push(m);
+                    materials.len() - 1
+                }
+            }
+        })
+        .collect()
+}
+
+fn surface_sample(w: &World, r: &Ray, material_ids: &[usize]) -> Option<SurfaceSample> {
+    // Intersect the shapes one by one to know which one was hit
+    let mut closest: Option<(usize, Intersection)> = None;
+    for (id, shape) in w.shapes.iter().enumerate() {
+        if let Some(h) = shape.intersect(*r).hit()
+            && closest.as_ref().is_none_or(|(_, c)| h.t < c.t)
+        {
+            closest = Some((id, h.clone()));
+        }
+    }
+
+    closest.map(|(object_id, hit)| {
+        let comps = Comps::prepare_computations(&hit, r, None);
+        let material = comps.object.material();
+        SurfaceSample {
+            distance: hit.t * r.direction.magnitude(),
+            normal: comps.normalv,
+            albedo: material.color_at(&comps.object, &comps.point),
+            object_id,
+            material_id: material_ids[object_id],
+        }
+    })
+}
+
+/// Spreads consecutive ids over very different hues.
+fn id_color(id: usize) -> Color {
+    let hue = (id as f64 * 0.618_033_988_749_895).fract() * 6.;
+    let x = 1. - (hue % 2. - 1.).abs();
+    match hue as u32 {
+        0 => Color::new(1., x, 0.),
+        1 => Color::new(x, 1., 0.),
+        2 => Color::new(0., 1., x),
+        3 => Color::new(0., x, 1.),
+        4 => Color::new(x, 0., 1.),
+        _ => Color::new(1., 0., x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hdr;
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+    use crate::transform;
+    use crate::transformation::view_transform;
+
+    fn camera() -> Camera {
+        let from = Tuple::point(0., 0., -5.);
+        let to = Tuple::point(0., 0., 0.);
+        let up = Tuple::vector(0., 1., 0.);
+        let t = view_transform(&from, &to, &up);
+        Camera::new(11, 11, std::f64::consts::FRAC_PI_2, Some(t))
+    }
+
+    #[test]
+    fn buffers_have_the_size_of_the_image() {
+        let buffers = AuxiliaryBuffers::render(&camera(), &World::default());
+
+        assert_eq!(11, buffers.width);
+        assert_eq!(11, buffers.height);
+        assert_eq!(11, buffers.depth().width);
+        assert_eq!(11, buffers.albedo().height);
+    }
+
+    #[test]
+    fn the_sample_at_the_center_of_the_default_world() {
+        let w = World::default();
+        let buffers = AuxiliaryBuffers::render(&camera(), &w);
+
+        let s = buffers.sample_at(5, 5).unwrap();
+        assert_eq!(4., s.distance);
+        assert_eq!(Tuple::vector(0., 0., -1.), s.normal);
+        assert_eq!(Color::new(0.8, 1., 0.6), s.albedo);
+        assert_eq!(0, s.object_id);
+        assert_eq!(0, s.material_id);
+    }
+
+    #[test]
+    fn pixels_where_the_ray_misses_have_no_sample() {
+        let buffers = AuxiliaryBuffers::render(&camera(), &World::default());
+
+        assert_eq!(None, buffers.sample_at(0, 0));
+        assert_eq!(BLACK, buffers.normals().pixel_at(0, 0));
+        assert_eq!(f64::INFINITY, buffers.depth().pixel_at(0, 0).r);
+        assert_eq!(WHITE, buffers.depth_visualization().pixel_at(0, 0));
+    }
+
+    #[test]
+    fn object_and_material_ids() {
+        let left = Sphere::new_boxed(Some(transform::translation(-2., 0., 0.)), None);
+        let right = Sphere::new_boxed(Some(transform::translation(2., 0., 0.)), None);
+        let back = Plane::new_boxed(
+            Some(transform::transforms(&[
+                transform::rotation_x(std::f64::consts::FRAC_PI_2),
+                transform::translation(0., 0., 10.),
+            ])),
+            Some(Material {
+                color: Color::new(0.5, 0.5, 0.5),
+                ..Default::default()
+            }),
+        );
+        let w = World::new(None, vec![left, right, back]);
+        let buffers = AuxiliaryBuffers::render(&camera(), &w);
+
+        let l = buffers.sample_at(2, 5).unwrap();
+        let r = buffers.sample_at(8, 5).unwrap();
+        let b = buffers.sample_at(5, 5).unwrap();
+        assert_eq!((0, 0), (l.object_id, l.material_id));
+        assert_eq!((1, 0), (r.object_id, r.material_id));
+        assert_eq!((2, 1), (b.object_id, b.material_id));
+
+        let ids = buffers.object_ids();
+        assert_ne!(ids.pixel_at(2, 5), ids.pixel_at(8, 5));
+        let materials = buffers.material_ids();
+        assert_eq!(materials.pixel_at(2, 5), materials.pixel_at(8, 5));
+    }
+
+    #[test]
+    fn the_depth_visualization_is_white_at_the_farthest_hit() {
+        let w = World::new(None, vec![Sphere::default_boxed()]);
+        let buffers = AuxiliaryBuffers::render(&camera(), &w);
+
+        let center = buffers.depth_visualization().pixel_at(5, 5);
+        assert!(center.r < 1.);
+        assert_eq!(center.r, center.g);
+    }
+
+    #[test]
+    fn the_depth_buffer_keeps_the_distances() {
+        let w = World::new(None, vec![Sphere::default_boxed()]);
+        let depth = AuxiliaryBuffers::render(&camera(), &w).depth();
+
+        let mut pfm = Vec::new();
+        hdr::write_pfm(&depth, &mut pfm).unwrap();
+        let saved = hdr::read_pfm(&mut pfm.as_slice()).unwrap();
+
+        assert_eq!(Color::new(4., 4., 4.), depth.pixel_at(5, 5));
+        assert_eq!(Color::new(4., 4., 4.), saved.pixel_at(5, 5));
+        assert_eq!(f64::INFINITY, saved.pixel_at(0, 0).g);
+    }
+
+    #[test]
+    fn the_depth_buffer_is_saved_in_every_hdr_format() {
+        let w = World::new(None, vec![Sphere::default_boxed()]);
+        let depth = AuxiliaryBuffers::render(&camera(), &w).depth();
+
+        let mut pfm = Vec::new();
+        hdr::write_pfm(&depth, &mut pfm).unwrap();
+        let mut rgbe = Vec::new();
+        hdr::write_rgbe(&depth, &mut rgbe).unwrap();
+
+        for saved in [
+            hdr::read_pfm(&mut pfm.as_slice()).unwrap(),
+            hdr::read_rgbe(&mut rgbe.as_slice()).unwrap(),
+        ] {
+            assert_eq!(Color::new(4., 4., 4.), saved.pixel_at(5, 5));
+            let miss = saved.pixel_at(0, 0).r;
+            assert!(miss > 1e30 && !miss.is_nan());
+        }
+    }
+}
@@ -4,7 +4,7 @@ use tracer::canvas::Canvas;
 use tracer::color::{Color, WHITE};
 use tracer::light::PointLight;
 use tracer::material::Material;
-use tracer::pattern::{CheckersPattern, GradientPattern, PatternType, StripePattern};
+use tracer::pattern::{CheckersPattern, GradientPattern, StripePattern};
 use tracer::plane::Plane;
 use tracer::shape::BoxShape;
 use tracer::sphere::Sphere;
@@ -18,13 +18,13 @@ use std::f64;
 fn floor() -> BoxShape {
     let pattern_transform = transform::scaling(0.5, 0.5, 0.5);
     let pattern = CheckersPattern {
-        first: Color::new(1., 0.9, 0.9),
-        second: Color::new(0.9, 0.7, 0.7),
+        first: Color::new(1., 0.9, 0.9).into(),
+        second: Color::new(0.9, 0.7, 0.7).into(),
         transform: pattern_transform,
     };
     let material = Material::new(
         Some(Color::new(1., 0.9, 0.9)),
-        Some(PatternType::Checkers(pattern)),
+        Some(Box::new(pattern)),
         None,
         None,
         Some(0.),
@@ -40,8 +40,8 @@ fn floor() -> BoxShape {
 fn wall() -> BoxShape {
     let pattern_transform = transform::scaling(2., 1., 1.);
     let pattern = GradientPattern {
-        first: Color::new(0.1, 0.1, 0.1),
-        second: Color::new(0.2, 0.2, 0.2),
+        first: Color::new(0.1, 0.1, 0.1).into(),
+        second: Color::new(0.2, 0.2, 0.2).into(),
         transform: pattern_transform,
     };
 
@@ -52,7 +52,7 @@ fn wall() -> BoxShape {
         ])),
         Some(Material::new(
             None,
-            Some(PatternType::Gradient(pattern)),
+            Some(Box::new(pattern)),
             None,
             None,
             Some(0.),
@@ -64,7 +64,7 @@ fn wall() -> BoxShape {
     )
 }
 
-pub fn ch10() -> Canvas {
+pub fn ch10(hsize: usize) -> Canvas {
     let s_pattern_tr = transform::transforms(&[
         transform::rotation_x(2.),
         transform::rotation_y(2.),
@@ -72,13 +72,13 @@ pub fn ch10() -> Canvas {
         transform::scaling(0.2, 0.2, 0.2),
     ]);
     let s_pattern = StripePattern {
-        first: Color::new(0., 1., 0.),
-        second: Color::new(0.3, 0.6, 0.),
+        first: Color::new(0., 1., 0.).into(),
+        second: Color::new(0.3, 0.6, 0.).into(),
         transform: s_pattern_tr,
     };
     let middle_m = Material::new(
         Some(Color::new(0.1, 1., 0.5)),
-        Some(PatternType::Stripe(s_pattern)),
+        Some(Box::new(s_pattern)),
         None,
         Some(0.7),
         Some(0.3),
@@ -135,7 +135,12 @@ pub fn ch10() -> Canvas {
     let c_to = Tuple::point(0., 1., 0.);
     let c_up = Tuple::vector(0., 1., 0.);
     let c_transform = view_transform(&c_from, &c_to, &c_up);
-    let camera = Camera::new(640, 480, f64::consts::FRAC_PI_3, Some(c_transform));
+    let camera = Camera::new(
+        hsize,
+        hsize * 3 / 4,
+        f64::consts::FRAC_PI_3,
+        Some(c_transform),
+    );
 
     camera.render(&world)
 }
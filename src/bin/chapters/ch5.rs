@@ -8,23 +8,21 @@ use tracer::tuple::Tuple;
 
 use std::f64;
 
-pub fn ch5() -> Canvas {
-    const CANVAS_SIZE: usize = 200;
-
+pub fn ch5(canvas_size: usize) -> Canvas {
     // The light is at z = -5
     let ray_origin = Tuple::point(0., 0., -5.);
     let wall_z = 10.;
     let wall_size = 7.;
-    let pixel_size = wall_size / CANVAS_SIZE as f64;
+    let pixel_size = wall_size / canvas_size as f64;
     let half = wall_size / 2.;
 
-    let mut canvas = Canvas::new(CANVAS_SIZE, CANVAS_SIZE);
+    let mut canvas = Canvas::new(canvas_size, canvas_size);
     let transforms = transform::shearing(1., 0., 0., 0., 0., 0.) * &transform::scaling(1., 0.5, 1.);
     let shape = Sphere::new_boxed(Some(transforms), None);
 
-    for y in 0..CANVAS_SIZE {
+    for y in 0..canvas_size {
         let world_y = half - pixel_size * y as f64;
-        for x in 0..CANVAS_SIZE {
+        for x in 0..canvas_size {
             let world_x = -half + pixel_size * x as f64;
             let position = Tuple::point(world_x, world_y, wall_z);
             let direction = position - ray_origin;
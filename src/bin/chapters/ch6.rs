@@ -9,17 +9,15 @@ use tracer::tuple::Tuple;
 
 use std::f64;
 
-pub fn ch6() -> Canvas {
-    const CANVAS_SIZE: usize = 200;
-
+pub fn ch6(canvas_size: usize) -> Canvas {
     // The light is at z = -5
     let ray_origin = Tuple::point(0., 0., -5.);
     let wall_z = 10.;
     let wall_size = 7.;
-    let pixel_size = wall_size / CANVAS_SIZE as f64;
+    let pixel_size = wall_size / canvas_size as f64;
     let half = wall_size / 2.;
 
-    let mut canvas = Canvas::new(CANVAS_SIZE, CANVAS_SIZE);
+    let mut canvas = Canvas::new(canvas_size, canvas_size);
     let material = Material::new(
         Some(Color::new(1., 0.2, 1.)),
         None,
@@ -37,9 +35,9 @@ pub fn ch6() -> Canvas {
     let light_color = WHITE;
     let light = PointLight::new(&light_position, &light_color);
 
-    for y in 0..CANVAS_SIZE {
+    for y in 0..canvas_size {
         let world_y = half - pixel_size * y as f64;
-        for x in 0..CANVAS_SIZE {
+        for x in 0..canvas_size {
             let world_x = -half + pixel_size * x as f64;
             let position = Tuple::point(world_x, world_y, wall_z);
             let direction = position - ray_origin;
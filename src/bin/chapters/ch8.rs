@@ -12,7 +12,7 @@ use tracer::world::World;
 
 use std::f64;
 
-pub fn ch8() -> Canvas {
+pub fn ch8(hsize: usize) -> Canvas {
     let floor_tr = transform::scaling(10., 0.01, 10.);
     let floor_m = Material::new(
         Some(Color::new(1., 0.9, 0.9)),
@@ -108,7 +108,7 @@ pub fn ch8() -> Canvas {
     let c_to = Tuple::point(0., 1., 0.);
     let c_up = Tuple::vector(0., 1., 0.);
     let c_transform = view_transform(&c_from, &c_to, &c_up);
-    let camera = Camera::new(300, 150, f64::consts::FRAC_PI_3, Some(c_transform));
+    let camera = Camera::new(hsize, hsize / 2, f64::consts::FRAC_PI_3, Some(c_transform));
 
     camera.render(&world)
 }
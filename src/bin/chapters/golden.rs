@@ -0,0 +1,101 @@
+// Golden image tests: each chapter is rendered at a small size and compared with a reference
+// image checked in the golden directory, so that changes to the renderer altering the book
+// images are noticed.
+//
+// After an intended change, the references can be updated with:
+//     UPDATE_GOLDEN=1 cargo test --bin chapters golden
+
+use crate::chapters;
+use tracer::canvas::Canvas;
+use tracer::compare;
+use tracer::hdr;
+use tracer::ppm::{self, PpmFormat};
+
+use std::{env, fs, io::BufWriter, path::PathBuf};
+
+const WIDTH: usize = 48;
+// Small enough to ignore floating point differences between platforms
+const TOLERANCE: f64 = 0.001;
+
+fn reference_path(chapter: i32) -> PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "src/bin/chapters/golden",
+        &format!("ch{chapter}.pfm"),
+    ]
+    .iter()
+    .collect()
+}
+
+fn check_chapter(chapter: i32) {
+    let canvas = (chapters()[&chapter].render)(WIDTH);
+    let path = reference_path(chapter);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        let mut out = BufWriter::new(fs::File::create(&path).unwrap());
+        hdr::write_pfm(&canvas, &mut out).unwrap();
+        return;
+    }
+
+    let reference = fs::File::open(&path)
+        .map_err(|err| err.into())
+        .and_then(|mut file| hdr::read_pfm(&mut file))
+        .unwrap_or_else(|err| panic!("Cannot read {}: {err}", path.display()));
+    assert_eq!(
+        (reference.width, reference.height),
+        (canvas.width, canvas.height),
+        "Chapter {chapter} size changed"
+    );
+
+    let comparison = compare::compare(&canvas, &reference, TOLERANCE);
+    if !comparison.is_match() {
+        let diff_path = write_diff(chapter, &canvas, &reference);
+        panic!(
+            "Chapter {chapter} differs from its reference: {comparison}\nDifferences written to {}",
+            diff_path.display()
+        );
+    }
+}
+
+fn write_diff(chapter: i32, canvas: &Canvas, reference: &Canvas) -> PathBuf {
+    let path = env::temp_dir().join(format!("ch{chapter}-diff.ppm"));
+    let diff = compare::diff_canvas(canvas, reference, TOLERANCE);
+    let mut out = BufWriter::new(fs::File::create(&path).unwrap());
+    ppm::write_ppm(&diff, PpmFormat::Binary, &mut out).unwrap();
+    path
+}
+
+#[test]
+fn chapter_4_matches_its_reference() {
+    check_chapter(4);
+}
+
+#[test]
+fn chapter_5_matches_its_reference() {
+    check_chapter(5);
+}
+
+#[test]
+fn chapter_6_matches_its_reference() {
+    check_chapter(6);
+}
+
+#[test]
+fn chapter_8_matches_its_reference() {
+    check_chapter(8);
+}
+
+#[test]
+fn chapter_9_matches_its_reference() {
+    check_chapter(9);
+}
+
+#[test]
+fn chapter_10_matches_its_reference() {
+    check_chapter(10);
+}
+
+#[test]
+fn chapter_11_matches_its_reference() {
+    check_chapter(11);
+}
@@ -1,5 +1,13 @@
 extern crate the_ray_tracer_challenge_rust as tracer;
-use tracer::{canvas::Canvas, ppm};
+use tracer::{
+    canvas::Canvas,
+    display::{DisplayPipeline, ToneCurve},
+    hdr,
+    png::{self, PngOptions},
+    postprocess::PostProcess,
+    ppm::{self, PpmFormat},
+    terminal,
+};
 
 pub mod ch10;
 pub mod ch11;
@@ -8,6 +16,8 @@ pub mod ch5;
 pub mod ch6;
 pub mod ch8;
 pub mod ch9;
+#[cfg(test)]
+mod golden;
 use crate::ch4::ch4;
 use crate::ch5::ch5;
 use crate::ch6::ch6;
@@ -16,11 +26,76 @@ use crate::ch9::ch9;
 use crate::ch10::ch10;
 use crate::ch11::ch11;
 
-use std::{collections::HashMap, env, fs, process};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, BufWriter, Write},
+    process,
+};
+
+#[derive(Copy, Clone)]
+enum OutputFormat {
+    Ppm(PpmFormat),
+    Png,
+    Pfm,
+    Hdr,
+    Ansi,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(OutputFormat::Png),
+            "pfm" => Ok(OutputFormat::Pfm),
+            "hdr" => Ok(OutputFormat::Hdr),
+            "ansi" => Ok(OutputFormat::Ansi),
+            _ => s.parse::<PpmFormat>().map(OutputFormat::Ppm),
+        }
+    }
+}
+
+fn write_image<W: Write>(canvas: &Canvas, format: OutputFormat, out: &mut W) -> io::Result<()> {
+    match format {
+        OutputFormat::Ppm(ppm_format) => ppm::write_ppm(canvas, ppm_format, out),
+        OutputFormat::Png => {
+            let options = PngOptions::default().with_text("Software", "The Ray Tracer Challenge");
+            png::write_png(canvas, &options, out)
+        }
+        OutputFormat::Pfm => hdr::write_pfm(canvas, out),
+        OutputFormat::Hdr => hdr::write_rgbe(canvas, out),
+        OutputFormat::Ansi => terminal::write_ansi(canvas, terminal::terminal_width(), out),
+    }
+}
+
+/// A chapter scene, rendered at a given width with the aspect ratio of the chapter.
+pub struct Chapter {
+    pub render: fn(usize) -> Canvas,
+    pub default_width: usize,
+}
+
+pub fn chapters() -> HashMap<i32, Chapter> {
+    let chapter = |render, default_width| Chapter {
+        render,
+        default_width,
+    };
+    HashMap::from([
+        (4, chapter(ch4, 200)),
+        (5, chapter(ch5, 200)),
+        (6, chapter(ch6, 200)),
+        (8, chapter(ch8, 300)),
+        (9, chapter(ch9, 300)),
+        (10, chapter(ch10, 640)),
+        (11, chapter(ch11, 640)),
+    ])
+}
 
 fn print_usage(chapters: &[i32]) {
     eprintln!("Output a PPM image from chapters of the Ray Tracer Challenge\n");
-    eprintln!("Usage: main [--output <file>] [--ch <chapter>]");
+    eprintln!(
+        "Usage: main [--output <file>] [--ch <chapter>] [--width <n>] [--post <filters>] [--exposure <n>] [--tonemap <curve>] [--srgb] [--format <p3|p6|png|pfm|hdr|ansi>]"
+    );
     eprintln!("  --output <file>   Write output to file instead of stdout");
     eprintln!(
         "  --ch <chapter>    Run a specific chapter ({})",
@@ -31,22 +106,35 @@ fn print_usage(chapters: &[i32]) {
             .join(", ")
     );
     eprintln!("                    Default: 10");
+    eprintln!("  --width <n>       Width of the image in pixels, the height follows the chapter");
+    eprintln!("                    Default: the size of the book image");
+    eprintln!("  --post <filters>  Post-processing filters applied in order, separated by commas");
+    eprintln!(
+        "                    (blur, box, sharpen, edges, emboss, bloom, vignette, aberration, grain)"
+    );
+    eprintln!("                    Parameters follow the name, e.g. bloom:1:0.5:4,vignette:0.3");
+    eprintln!("  --exposure <n>    Scale the brightness by 2^n before writing");
+    eprintln!("  --tonemap <curve> Tone curve (linear, reinhard, filmic, aces)");
+    eprintln!("                    Default: linear");
+    eprintln!("  --srgb            Encode the output with the sRGB transfer function");
+    eprintln!(
+        "  --format <format> ASCII PPM (p3), binary PPM (p6), PNG (png), PFM (pfm), Radiance HDR (hdr)"
+    );
+    eprintln!("                    or a terminal preview (ansi) fitting in $COLUMNS characters");
+    eprintln!("                    Default: p3");
 }
 
 fn main() {
-    let mut chapters: HashMap<_, fn() -> Canvas> = HashMap::new();
-    chapters.insert(4, ch4);
-    chapters.insert(5, ch5);
-    chapters.insert(6, ch6);
-    chapters.insert(8, ch8);
-    chapters.insert(9, ch9);
-    chapters.insert(10, ch10);
-    chapters.insert(11, ch11);
+    let chapters = chapters();
     let mut valid_chapters = chapters.keys().cloned().collect::<Vec<i32>>();
     valid_chapters.sort();
 
     let mut output_file: Option<String> = None;
     let mut chapter = 11;
+    let mut width: Option<usize> = None;
+    let mut post_process = PostProcess::default();
+    let mut display = DisplayPipeline::default();
+    let mut format = OutputFormat::Ppm(PpmFormat::Ascii);
 
     let args: Vec<String> = env::args().collect();
     let mut i = 1;
@@ -82,6 +170,83 @@ fn main() {
                     process::exit(1);
                 }
             }
+            "--width" => {
+                if i + 1 < args.len() {
+                    width = match args[i + 1].parse::<usize>() {
+                        Ok(w) if w > 0 => Some(w),
+                        _ => {
+                            eprintln!("Error: Invalid width");
+                            print_usage(&valid_chapters);
+                            process::exit(1);
+                        }
+                    };
+                    i += 2;
+                } else {
+                    eprintln!("Error: Missing width");
+                    print_usage(&valid_chapters);
+                    process::exit(1);
+                }
+            }
+            "--post" => {
+                if i + 1 < args.len() {
+                    post_process = args[i + 1].parse::<PostProcess>().unwrap_or_else(|err| {
+                        eprintln!("Error: {err}");
+                        print_usage(&valid_chapters);
+                        process::exit(1);
+                    });
+                    i += 2;
+                } else {
+                    eprintln!("Error: Missing filters");
+                    print_usage(&valid_chapters);
+                    process::exit(1);
+                }
+            }
+            "--exposure" => {
+                if i + 1 < args.len() {
+                    display.exposure = args[i + 1].parse::<f64>().unwrap_or_else(|_| {
+                        eprintln!("Error: Invalid exposure");
+                        print_usage(&valid_chapters);
+                        process::exit(1);
+                    });
+                    i += 2;
+                } else {
+                    eprintln!("Error: Missing exposure");
+                    print_usage(&valid_chapters);
+                    process::exit(1);
+                }
+            }
+            "--tonemap" => {
+                if i + 1 < args.len() {
+                    display.tone_curve = args[i + 1].parse::<ToneCurve>().unwrap_or_else(|err| {
+                        eprintln!("Error: {err}");
+                        print_usage(&valid_chapters);
+                        process::exit(1);
+                    });
+                    i += 2;
+                } else {
+                    eprintln!("Error: Missing tone curve");
+                    print_usage(&valid_chapters);
+                    process::exit(1);
+                }
+            }
+            "--format" => {
+                if i + 1 < args.len() {
+                    format = args[i + 1].parse::<OutputFormat>().unwrap_or_else(|err| {
+                        eprintln!("Error: {err}");
+                        print_usage(&valid_chapters);
+                        process::exit(1);
+                    });
+                    i += 2;
+                } else {
+                    eprintln!("Error: Missing output format");
+                    print_usage(&valid_chapters);
+                    process::exit(1);
+                }
+            }
+            "--srgb" => {
+                display.srgb = true;
+                i += 1;
+            }
             "-h" | "--help" => {
                 print_usage(&valid_chapters);
                 process::exit(0);
@@ -94,17 +259,32 @@ fn main() {
         }
     }
 
-    let ch_func = chapters.get(&chapter).unwrap();
-    let canvas = ch_func();
-    let ppm = ppm::canvas_to_ppm(canvas);
+    let chapter = chapters.get(&chapter).unwrap();
+    let mut canvas = (chapter.render)(width.unwrap_or(chapter.default_width));
+    if !post_process.is_empty() {
+        canvas = post_process.apply(&canvas);
+    }
+    if display != DisplayPipeline::default() {
+        canvas = display.apply(&canvas);
+    }
 
-    if output_file.is_some() {
-        let file_name = output_file.unwrap();
-        fs::write(&file_name, ppm).unwrap_or_else(|err| {
+    if let Some(file_name) = output_file {
+        let result = fs::File::create(&file_name).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            write_image(&canvas, format, &mut out)?;
+            out.flush()
+        });
+        result.unwrap_or_else(|err| {
             eprintln!("Error writing to file {file_name}: {err}");
             process::exit(1);
         });
     } else {
-        println!("{ppm}");
+        let mut out = BufWriter::new(io::stdout().lock());
+        write_image(&canvas, format, &mut out)
+            .and_then(|_| out.flush())
+            .unwrap_or_else(|err| {
+                eprintln!("Error writing to stdout: {err}");
+                process::exit(1);
+            });
     }
 }
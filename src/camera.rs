@@ -1,6 +1,10 @@
+use super::auxiliary::AuxiliaryBuffers;
 use super::canvas::Canvas;
+pub use super::canvas::Region;
+use super::color::Color;
+use super::integrator::{Integrator, Whitted};
 use super::matrix::Matrix;
-use super::ray::Ray;
+use super::ray::{Ray, RayDifferentials};
 use super::tuple::Tuple;
 use super::world::World;
 
@@ -13,6 +17,9 @@ pub struct Camera {
     pub half_width: f64,
     pub half_height: f64,
     pub transform: Matrix,
+    /// Renders the background as transparent instead of black, with the
+    /// alpha of each pixel set to the coverage of the objects.
+    pub transparent_background: bool,
 }
 
 impl Camera {
@@ -35,13 +42,37 @@ impl Camera {
             half_width,
             half_height,
             transform: transform.unwrap_or_default(),
+            transparent_background: false,
         }
     }
 
+    /// Ray through the center of a pixel, with the differentials to the next pixels.
     pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
-        // the offset from the edge of the canvas to the pixel's center
-        let xoffset = (px as f64 + 0.5) * self.pixel_size;
-        let yoffset = (py as f64 + 0.5) * self.pixel_size;
+        // the ray goes through the pixel's center
+        let (x, y) = (px as f64 + 0.5, py as f64 + 0.5);
+        let t_inverse = self.transform.inverse();
+        let right = self.ray_through(&t_inverse, x + 1., y);
+        let below = self.ray_through(&t_inverse, x, y + 1.);
+
+        self.ray_through(&t_inverse, x, y)
+            .with_differentials(RayDifferentials {
+                x_origin: right.origin,
+                x_direction: right.direction,
+                y_origin: below.origin,
+                y_direction: below.direction,
+            })
+    }
+
+    /// Ray through any point of the canvas, in pixels from its top left corner.
+    pub fn ray_for_point(&self, x: f64, y: f64) -> Ray {
+        self.ray_through(&self.transform.inverse(), x, y)
+    }
+
+    /// Ray through a point of the canvas, given the inverse of the camera transform.
+    fn ray_through(&self, t_inverse: &Matrix, x: f64, y: f64) -> Ray {
+        // the offset from the edge of the canvas to the point
+        let xoffset = x * self.pixel_size;
+        let yoffset = y * self.pixel_size;
 
         // the untransformed coordinates of the pixel in world space
         let worldx = self.half_width - xoffset;
@@ -50,31 +81,132 @@ impl Camera {
         // using a camera matrix, transform the canvas point and the origin
         // then compute the ray's direction vector
         // (remember the canvas is at z = -1)
-        let t_inverse = self.transform.inverse();
-        let pixel = t_inverse * Tuple::point(worldx, worldy, -1.);
-        let origin = t_inverse * Tuple::point(0., 0., 0.);
+        let pixel = *t_inverse * Tuple::point(worldx, worldy, -1.);
+        let origin = *t_inverse * Tuple::point(0., 0., 0.);
         let direction = (pixel - origin).normalize();
 
         Ray::new(&origin, &direction)
     }
 
     pub fn render(&self, w: &World) -> Canvas {
-        let mut canvas = Canvas::new(self.hsize, self.vsize);
-        for y in 0..self.vsize {
-            for x in 0..self.hsize {
-                let r = self.ray_for_pixel(x, y);
-                let c = w.color_at(&r, 5);
-                canvas.write_pixel(x, y, c)
+        self.render_with(w, &Whitted::default())
+    }
+
+    /// Renders the world with the given integrator, along with the
+    /// auxiliary buffers (depth, normal, albedo, object and material ids).
+    pub fn render_with_auxiliary(
+        &self,
+        w: &World,
+        integrator: &dyn Integrator,
+    ) -> (Canvas, AuxiliaryBuffers) {
+        (
+            self.render_with(w, integrator),
+            AuxiliaryBuffers::render(self, w),
+        )
+    }
+
+    /// Renders the world with the given integrator.
+    pub fn render_with(&self, w: &World, integrator: &dyn Integrator) -> Canvas {
+        self.render_region_with(w, integrator, &self.region())
+    }
+
+    /// The region covering the whole image.
+    pub fn region(&self) -> Region {
+        Region::new(0, 0, self.hsize, self.vsize)
+    }
+
+    /// Renders only a part of the image into a canvas of the region's size.
+    pub fn render_region(&self, w: &World, region: &Region) -> Canvas {
+        self.render_region_with(w, &Whitted::default(), region)
+    }
+
+    pub fn render_region_with(
+        &self,
+        w: &World,
+        integrator: &dyn Integrator,
+        region: &Region,
+    ) -> Canvas {
+        let mut canvas = Canvas::new(region.width, region.height);
+        self.render_pixels(w, integrator, region, |x, y, c, alpha| {
+            canvas.write_pixel(x - region.x, y - region.y, c);
+            canvas.write_alpha(x - region.x, y - region.y, alpha);
+        });
+        canvas
+    }
+
+    /// Renders a part of the image at its own location in a full size canvas.
+    /// The pixels outside of the region are left untouched.
+    pub fn render_into(&self, w: &World, canvas: &mut Canvas, region: &Region) {
+        self.render_into_with(w, &Whitted::default(), canvas, region)
+    }
+
+    pub fn render_into_with(
+        &self,
+        w: &World,
+        integrator: &dyn Integrator,
+        canvas: &mut Canvas,
+        region: &Region,
+    ) {
+        assert!(
+            canvas.width >= region.x + region.width && canvas.height >= region.y + region.height,
+            "Region does not fit in the canvas"
+        );
+        self.render_pixels(w, integrator, region, |x, y, c, alpha| {
+            canvas.write_pixel(x, y, c);
+            canvas.write_alpha(x, y, alpha);
+        });
+    }
+
+    /// Splits the image in tiles of at most `width` by `height` pixels, row by row.
+    /// Tiles on the right and bottom edges are cropped to the image.
+    pub fn tiles(&self, width: usize, height: usize) -> Vec<Region> {
+        assert!(width > 0 && height > 0, "Tiles must not be empty");
+
+        let mut tiles = vec![];
+        for y in (0..self.vsize).step_by(height) {
+            for x in (0..self.hsize).step_by(width) {
+                tiles.push(Region::new(
+                    x,
+                    y,
+                    width.min(self.hsize - x),
+                    height.min(self.vsize - y),
+                ));
+            }
+        }
+        tiles
+    }
+
+    fn render_pixels<F>(
+        &self,
+        w: &World,
+        integrator: &dyn Integrator,
+        region: &Region,
+        mut write: F,
+    ) where
+        F: FnMut(usize, usize, Color, f64),
+    {
+        assert!(
+            region.x + region.width <= self.hsize && region.y + region.height <= self.vsize,
+            "Region is outside of the camera's image"
+        );
+
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let (color, alpha) = if self.transparent_background {
+                    integrator.pixel_color_and_coverage(self, w, x, y)
+                } else {
+                    (integrator.pixel_color(self, w, x, y), 1.)
+                };
+                write(x, y, color, alpha)
             }
         }
-        canvas
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::color::Color;
+    use crate::color::BLACK;
     use crate::matrix::MATRIX_IDENTITY;
     use crate::transform;
     use crate::transformation::view_transform;
@@ -111,6 +243,22 @@ mod tests {
         assert_eq!(Tuple::vector(0., 0., -1.), r.direction);
     }
 
+    #[test]
+    fn a_ray_for_a_pixel_has_differentials_to_the_next_pixels() {
+        let c = Camera::new(201, 101, f64::consts::FRAC_PI_2, None);
+        let r = c.ray_for_pixel(100, 50);
+
+        let d = r.differentials.unwrap();
+        assert_eq!(c.ray_for_pixel(101, 50).direction, d.x_direction);
+        assert_eq!(c.ray_for_pixel(100, 51).direction, d.y_direction);
+        assert_eq!(r.origin, d.x_origin);
+
+        // One pixel on a wall at distance 1 from the camera
+        let footprint = r.footprint(&Tuple::point(0., 0., -1.), &Tuple::vector(0., 0., 1.));
+        assert_eq!(Tuple::vector(-c.pixel_size, 0., 0.), footprint.dpdx);
+        assert_eq!(Tuple::vector(0., -c.pixel_size, 0.), footprint.dpdy);
+    }
+
     #[test]
     fn constructing_a_ray_through_a_corner_of_the_canvas() {
         let c = Camera::new(201, 101, f64::consts::FRAC_PI_2, None);
@@ -151,4 +299,107 @@ mod tests {
 
         assert_eq!(Color::new(0.38066, 0.47583, 0.2855), image.pixel_at(5, 5));
     }
+
+    fn default_world_camera(hsize: usize, vsize: usize) -> Camera {
+        let from = Tuple::point(0., 0., -5.);
+        let to = Tuple::point(0., 0., 0.);
+        let up = Tuple::vector(0., 1., 0.);
+        let t = view_transform(&from, &to, &up);
+        Camera::new(hsize, vsize, f64::consts::FRAC_PI_2, Some(t))
+    }
+
+    #[test]
+    fn rendering_a_region_of_the_image() {
+        let w = World::default();
+        let c = default_world_camera(11, 11);
+
+        let image = c.render_region(&w, &Region::new(4, 3, 3, 5));
+
+        assert_eq!(3, image.width);
+        assert_eq!(5, image.height);
+        assert_eq!(Color::new(0.38066, 0.47583, 0.2855), image.pixel_at(1, 2));
+    }
+
+    #[test]
+    fn rendering_a_region_into_a_larger_canvas() {
+        let w = World::default();
+        let c = default_world_camera(11, 11);
+        let mut image = Canvas::new_filled(11, 11, MAGENTA);
+
+        c.render_into(&w, &mut image, &Region::new(5, 5, 1, 1));
+
+        assert_eq!(Color::new(0.38066, 0.47583, 0.2855), image.pixel_at(5, 5));
+        assert_eq!(MAGENTA, image.pixel_at(4, 5));
+        assert_eq!(MAGENTA, image.pixel_at(6, 6));
+    }
+
+    #[test]
+    fn splitting_the_image_in_tiles() {
+        let c = default_world_camera(10, 7);
+        let tiles = c.tiles(4, 4);
+
+        assert_eq!(6, tiles.len());
+        assert_eq!(Region::new(0, 0, 4, 4), tiles[0]);
+        assert_eq!(Region::new(8, 0, 2, 4), tiles[2]);
+        assert_eq!(Region::new(8, 4, 2, 3), tiles[5]);
+    }
+
+    #[test]
+    fn stitched_tiles_match_a_full_render() {
+        let w = World::default();
+        let c = default_world_camera(13, 9);
+        let full = c.render(&w);
+
+        let mut stitched = Canvas::new_filled(13, 9, MAGENTA);
+        for tile in c.tiles(5, 4) {
+            let part = c.render_region(&w, &tile);
+            for y in 0..tile.height {
+                for x in 0..tile.width {
+                    stitched.write_pixel(tile.x + x, tile.y + y, part.pixel_at(x, y));
+                }
+            }
+        }
+
+        for y in 0..full.height {
+            for x in 0..full.width {
+                let (a, b) = (full.pixel_at(x, y), stitched.pixel_at(x, y));
+                assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+            }
+        }
+        assert_ne!(BLACK, full.pixel_at(6, 4));
+    }
+
+    #[test]
+    fn rendering_with_a_transparent_background() {
+        let w = World::default();
+        let mut c = default_world_camera(11, 11);
+
+        assert!(c.render(&w).is_opaque());
+
+        c.transparent_background = true;
+        let image = c.render(&w);
+        assert_eq!(1., image.alpha_at(5, 5));
+        assert_eq!(0., image.alpha_at(0, 0));
+        assert_eq!(BLACK, image.pixel_at(0, 0));
+
+        let mut tile = Canvas::new(11, 11);
+        c.render_into(&w, &mut tile, &Region::new(0, 0, 2, 2));
+        assert_eq!(0., tile.alpha_at(1, 1));
+        assert_eq!(1., tile.alpha_at(5, 5));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rendering_a_region_outside_of_the_image() {
+        let w = World::default();
+        let c = default_world_camera(11, 11);
+
+        c.render_region(&w, &Region::new(8, 0, 4, 1));
+    }
+
+    const MAGENTA: Color = Color {
+        r: 1.,
+        g: 0.,
+        b: 1.,
+    };
 }
@@ -1,14 +1,133 @@
 use super::color::*;
 
+use std::fmt;
+use std::ops::{Add, Mul};
+use std::slice::{ChunksExact, ChunksExactMut};
+
 // TODO: Document the public API
 
-#[derive(Debug)]
+/// An image with a coverage (alpha) value per pixel, from 0 (transparent) to 1 (opaque).
+/// Colors are premultiplied by their alpha, so a transparent pixel is black.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
-    pub pixels: Vec<Vec<Color>>, // A vector for each row of pixel
+    pixels: Vec<Color>, // Row-major, one row after the other
+    alpha: Vec<f64>,
+}
+
+/// A rectangular area of the image, in pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// How to compute a color between the pixels of a canvas.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// The color of the closest pixel
+    #[default]
+    Nearest,
+    /// A weighted average of the four closest pixels
+    Bilinear,
+}
+
+impl std::str::FromStr for Interpolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Interpolation::Nearest),
+            "bilinear" => Ok(Interpolation::Bilinear),
+            _ => Err(format!("Unknown interpolation '{s}'")),
+        }
+    }
+}
+
+/// Which pixel to use for a position outside of the canvas.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Addressing {
+    /// The canvas repeats, like tiles
+    #[default]
+    Wrap,
+    /// The closest pixel on the edge
+    Clamp,
+    /// The canvas repeats, flipped every other time
+    Mirror,
+}
+
+impl Addressing {
+    /// The pixel to use for the coordinate `i` along a side of `size` pixels.
+    /// An empty side has no pixel to use, 0 is returned.
+    pub fn index(&self, i: i64, size: usize) -> usize {
+        if size == 0 {
+            return 0;
+        }
+        let size = size as i64;
+        let index = match self {
+            Addressing::Wrap => i.rem_euclid(size),
+            Addressing::Clamp => i.clamp(0, size - 1),
+            Addressing::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size { i } else { 2 * size - 1 - i }
+            }
+        };
+        index as usize
+    }
 }
 
+impl std::str::FromStr for Addressing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" | "repeat" => Ok(Addressing::Wrap),
+            "clamp" => Ok(Addressing::Clamp),
+            "mirror" => Ok(Addressing::Mirror),
+            _ => Err(format!("Unknown addressing '{s}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CanvasError {
+    /// A pixel outside of the canvas
+    OutOfBounds { x: usize, y: usize },
+    /// A region not entirely inside the canvas
+    RegionOutOfBounds(Region),
+}
+
+impl fmt::Display for CanvasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CanvasError::OutOfBounds { x, y } => {
+                write!(f, "Pixel ({x}, {y}) is outside of the canvas")
+            }
+            CanvasError::RegionOutOfBounds(r) => write!(
+                f,
+                "Region {}x{} at ({}, {}) is outside of the canvas",
+                r.width, r.height, r.x, r.y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CanvasError {}
+
 impl Canvas {
     /// Creates a new black canvas.
     pub fn new(width: usize, height: usize) -> Canvas {
@@ -20,18 +139,278 @@ impl Canvas {
         Canvas {
             width,
             height,
-            pixels: vec![vec![color; width]; height],
+            pixels: vec![color; width * height],
+            alpha: vec![1.; width * height],
+        }
+    }
+
+    /// Creates a new canvas where every pixel is transparent.
+    pub fn new_transparent(width: usize, height: usize) -> Canvas {
+        Canvas {
+            alpha: vec![0.; width * height],
+            ..Canvas::new(width, height)
         }
     }
 
+    /// Panics if the pixel is outside of the canvas.
     pub fn write_pixel(&mut self, x: usize, y: usize, color: Color) {
-        // Note: No check for out-of-bounds
-        self.pixels[y][x] = color;
+        let index = self.index(x, y);
+        self.pixels[index] = color;
     }
 
+    /// Panics if the pixel is outside of the canvas.
     pub fn pixel_at(&self, x: usize, y: usize) -> Color {
-        // Note: No check for out-of-bounds
-        self.pixels[y][x]
+        self.pixels[self.index(x, y)]
+    }
+
+    /// Panics if the pixel is outside of the canvas.
+    pub fn write_alpha(&mut self, x: usize, y: usize, alpha: f64) {
+        let index = self.index(x, y);
+        self.alpha[index] = alpha;
+    }
+
+    /// Panics if the pixel is outside of the canvas.
+    pub fn alpha_at(&self, x: usize, y: usize) -> f64 {
+        self.alpha[self.index(x, y)]
+    }
+
+    /// The alpha of all the pixels, row by row.
+    pub fn alphas(&self) -> &[f64] {
+        &self.alpha
+    }
+
+    /// Returns true if no pixel is even partially transparent.
+    pub fn is_opaque(&self) -> bool {
+        self.alpha.iter().all(|&a| a >= 1.)
+    }
+
+    /// Whether the canvas has no pixel.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Returns the color of the pixel, or None if it is outside of the canvas.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Color> {
+        self.contains(x, y).then(|| self.pixel_at(x, y))
+    }
+
+    /// Writes the pixel, or returns an error if it is outside of the canvas.
+    pub fn try_write_pixel(&mut self, x: usize, y: usize, color: Color) -> Result<(), CanvasError> {
+        if !self.contains(x, y) {
+            return Err(CanvasError::OutOfBounds { x, y });
+        }
+        self.write_pixel(x, y, color);
+        Ok(())
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// The region covering the whole canvas.
+    pub fn region(&self) -> Region {
+        Region::new(0, 0, self.width, self.height)
+    }
+
+    /// All the pixels, row by row.
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    /// Iterates over the rows, from top to bottom.
+    pub fn rows(&self) -> ChunksExact<'_, Color> {
+        self.pixels.chunks_exact(self.width.max(1))
+    }
+
+    pub fn rows_mut(&mut self) -> ChunksExactMut<'_, Color> {
+        self.pixels.chunks_exact_mut(self.width.max(1))
+    }
+
+    /// Iterates over the pixels row by row, with their coordinates.
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (usize, usize, Color)> + '_ {
+        let width = self.width;
+        self.pixels
+            .iter()
+            .enumerate()
+            .map(move |(i, &c)| (i % width, i / width, c))
+    }
+
+    /// Fills the part of the region inside the canvas with an opaque color.
+    pub fn fill_rect(&mut self, region: &Region, color: Color) {
+        let Some(r) = self.clip(region) else {
+            return;
+        };
+        for y in r.y..r.y + r.height {
+            let start = y * self.width + r.x;
+            self.pixels[start..start + r.width].fill(color);
+            self.alpha[start..start + r.width].fill(1.);
+        }
+    }
+
+    /// Copies a region of the canvas into a new canvas.
+    pub fn crop(&self, region: &Region) -> Result<Canvas, CanvasError> {
+        if region.x + region.width > self.width || region.y + region.height > self.height {
+            return Err(CanvasError::RegionOutOfBounds(*region));
+        }
+
+        let mut pixels = Vec::with_capacity(region.width * region.height);
+        let mut alpha = Vec::with_capacity(region.width * region.height);
+        for y in region.y..region.y + region.height {
+            let start = y * self.width + region.x;
+            pixels.extend_from_slice(&self.pixels[start..start + region.width]);
+            alpha.extend_from_slice(&self.alpha[start..start + region.width]);
+        }
+        Ok(Canvas {
+            width: region.width,
+            height: region.height,
+            pixels,
+            alpha,
+        })
+    }
+
+    /// Copies another canvas, colors and alpha, with its top left corner at (x, y).
+    /// The parts falling outside of this canvas are ignored.
+    pub fn blit(&mut self, source: &Canvas, x: usize, y: usize) {
+        let Some(r) = self.clip(&Region::new(x, y, source.width, source.height)) else {
+            return;
+        };
+        for sy in 0..r.height {
+            let start = (r.y + sy) * self.width + r.x;
+            let source_start = sy * source.width;
+            self.pixels[start..start + r.width]
+                .copy_from_slice(&source.pixels[source_start..source_start + r.width]);
+            self.alpha[start..start + r.width]
+                .copy_from_slice(&source.alpha[source_start..source_start + r.width]);
+        }
+    }
+
+    /// Mirrors the canvas left to right.
+    pub fn flip_horizontal(&mut self) {
+        let width = self.width.max(1);
+        self.pixels
+            .chunks_exact_mut(width)
+            .for_each(|row| row.reverse());
+        self.alpha
+            .chunks_exact_mut(width)
+            .for_each(|row| row.reverse());
+    }
+
+    /// Mirrors the canvas top to bottom.
+    pub fn flip_vertical(&mut self) {
+        fn flip<T>(values: &mut [T], width: usize, height: usize) {
+            for y in 0..height / 2 {
+                let (top, bottom) = values.split_at_mut((height - 1 - y) * width);
+                top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+            }
+        }
+        flip(&mut self.pixels, self.width, self.height);
+        flip(&mut self.alpha, self.width, self.height);
+    }
+
+    /// Returns the color at a position in pixels, where pixel (x, y) covers [x, x + 1) x [y, y + 1).
+    /// Positions outside of the canvas get the color of the closest edge.
+    pub fn sample(&self, x: f64, y: f64, interpolation: Interpolation) -> Color {
+        self.sample_addressed(x, y, interpolation, Addressing::Clamp)
+    }
+
+    /// Returns the color at a position in pixels, like `sample` does, with a choice of
+    /// the pixels used outside of the canvas.
+    pub fn sample_addressed(
+        &self,
+        x: f64,
+        y: f64,
+        interpolation: Interpolation,
+        addressing: Addressing,
+    ) -> Color {
+        if self.is_empty() {
+            return BLACK;
+        }
+        self.interpolate(x, y, interpolation, addressing, |x, y| self.pixel_at(x, y))
+    }
+
+    /// Returns the alpha at a position in pixels, like `sample` does for the color.
+    pub fn sample_alpha(&self, x: f64, y: f64, interpolation: Interpolation) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        self.interpolate(x, y, interpolation, Addressing::Clamp, |x, y| {
+            self.alpha_at(x, y)
+        })
+    }
+
+    /// Scales the canvas to a new size.
+    pub fn resize(&self, width: usize, height: usize, interpolation: Interpolation) -> Canvas {
+        let mut resized = Canvas::new(width, height);
+        if self.width == 0 || self.height == 0 {
+            return resized;
+        }
+
+        let scale_x = self.width as f64 / width as f64;
+        let scale_y = self.height as f64 / height as f64;
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = ((x as f64 + 0.5) * scale_x, (y as f64 + 0.5) * scale_y);
+                resized.write_pixel(x, y, self.sample(sx, sy, interpolation));
+                resized.write_alpha(x, y, self.sample_alpha(sx, sy, interpolation));
+            }
+        }
+        resized
+    }
+
+    fn interpolate<T, F>(
+        &self,
+        x: f64,
+        y: f64,
+        interpolation: Interpolation,
+        addressing: Addressing,
+        value: F,
+    ) -> T
+    where
+        T: Add<Output = T> + Mul<f64, Output = T>,
+        F: Fn(usize, usize) -> T,
+    {
+        let address_x = |x: f64| addressing.index(x.floor() as i64, self.width);
+        let address_y = |y: f64| addressing.index(y.floor() as i64, self.height);
+
+        match interpolation {
+            Interpolation::Nearest => value(address_x(x), address_y(y)),
+            Interpolation::Bilinear => {
+                // Pixel centers are at half coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (left, right) = (address_x(x0), address_x(x0 + 1.));
+                let (top, bottom) = (address_y(y0), address_y(y0 + 1.));
+
+                let upper = value(left, top) * (1. - tx) + value(right, top) * tx;
+                let lower = value(left, bottom) * (1. - tx) + value(right, bottom) * tx;
+                upper * (1. - ty) + lower * ty
+            }
+        }
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(
+            self.contains(x, y),
+            "Pixel ({x}, {y}) is outside of the {}x{} canvas",
+            self.width,
+            self.height
+        );
+        y * self.width + x
+    }
+
+    /// The part of the region inside the canvas, if any.
+    fn clip(&self, region: &Region) -> Option<Region> {
+        if region.x >= self.width || region.y >= self.height {
+            return None;
+        }
+        let width = region.width.min(self.width - region.x);
+        let height = region.height.min(self.height - region.y);
+        (width > 0 && height > 0).then(|| Region::new(region.x, region.y, width, height))
     }
 }
 
@@ -39,6 +418,17 @@ impl Canvas {
 mod tests {
     use super::*;
 
+    /// A canvas where each pixel has a different color.
+    fn numbered(width: usize, height: usize) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                c.write_pixel(x, y, Color::new(x as f64, y as f64, 0.));
+            }
+        }
+        c
+    }
+
     #[test]
     fn creating_a_canvas() {
         let c = Canvas::new(10, 20);
@@ -64,4 +454,248 @@ mod tests {
 
         assert_eq!(c.pixel_at(2, 4), BLACK); // Neighbor is still black
     }
+
+    #[test]
+    fn pixels_are_stored_row_by_row() {
+        let c = numbered(3, 2);
+
+        assert_eq!(6, c.pixels().len());
+        assert_eq!(Color::new(0., 1., 0.), c.pixels()[3]);
+        let rows: Vec<&[Color]> = c.rows().collect();
+        assert_eq!(2, rows.len());
+        assert_eq!(Color::new(2., 1., 0.), rows[1][2]);
+    }
+
+    #[test]
+    fn enumerating_the_pixels_of_a_canvas() {
+        let c = numbered(3, 2);
+
+        let pixels: Vec<_> = c.enumerate_pixels().collect();
+        assert_eq!(6, pixels.len());
+        assert!(
+            pixels
+                .iter()
+                .all(|&(x, y, color)| color == c.pixel_at(x, y))
+        );
+        assert_eq!((2, 1), (pixels[5].0, pixels[5].1));
+    }
+
+    #[test]
+    fn checked_access_outside_of_the_canvas() {
+        let mut c = Canvas::new(10, 20);
+
+        assert_eq!(Some(BLACK), c.get_pixel(9, 19));
+        assert_eq!(None, c.get_pixel(10, 0));
+        assert_eq!(None, c.get_pixel(0, 20));
+
+        assert_eq!(Ok(()), c.try_write_pixel(9, 19, RED));
+        assert_eq!(
+            Err(CanvasError::OutOfBounds { x: 10, y: 3 }),
+            c.try_write_pixel(10, 3, RED)
+        );
+        assert_eq!(RED, c.pixel_at(9, 19));
+    }
+
+    #[test]
+    #[should_panic(expected = "Pixel (10, 0) is outside of the 10x20 canvas")]
+    fn writing_a_pixel_outside_of_the_canvas() {
+        // Would otherwise write to the first pixel of the next row
+        Canvas::new(10, 20).write_pixel(10, 0, RED);
+    }
+
+    #[test]
+    fn canvases_are_opaque_by_default() {
+        let mut c = Canvas::new(3, 2);
+
+        assert_eq!(1., c.alpha_at(2, 1));
+        assert!(c.is_opaque());
+        c.write_alpha(2, 1, 0.5);
+        assert_eq!(0.5, c.alpha_at(2, 1));
+        assert!(!c.is_opaque());
+
+        let c = Canvas::new_transparent(3, 2);
+        assert_eq!(0., c.alpha_at(0, 0));
+        assert_eq!(BLACK, c.pixel_at(0, 0));
+    }
+
+    #[test]
+    fn image_operations_keep_the_alpha() {
+        let mut c = Canvas::new_transparent(4, 4);
+        c.fill_rect(&Region::new(0, 0, 2, 1), RED);
+        assert_eq!(1., c.alpha_at(1, 0));
+        assert_eq!(0., c.alpha_at(2, 0));
+
+        assert_eq!(1., c.crop(&Region::new(1, 0, 2, 2)).unwrap().alpha_at(0, 0));
+
+        c.flip_vertical();
+        c.flip_horizontal();
+        assert_eq!(1., c.alpha_at(3, 3));
+        assert_eq!(0., c.alpha_at(3, 0));
+
+        let mut target = Canvas::new(4, 4);
+        target.blit(&c, 0, 0);
+        assert_eq!(0., target.alpha_at(0, 0));
+
+        let resized = c.resize(2, 2, Interpolation::Bilinear);
+        assert_eq!(0.5, resized.alpha_at(1, 1));
+    }
+
+    #[test]
+    fn cropping_a_canvas() {
+        let c = numbered(5, 4);
+
+        let cropped = c.crop(&Region::new(1, 2, 3, 2)).unwrap();
+        assert_eq!((3, 2), (cropped.width, cropped.height));
+        assert_eq!(Color::new(1., 2., 0.), cropped.pixel_at(0, 0));
+        assert_eq!(Color::new(3., 3., 0.), cropped.pixel_at(2, 1));
+
+        let outside = Region::new(3, 0, 3, 1);
+        assert_eq!(
+            Some(CanvasError::RegionOutOfBounds(outside)),
+            c.crop(&outside).err()
+        );
+    }
+
+    #[test]
+    fn blitting_a_canvas_into_another() {
+        let mut c = Canvas::new(4, 3);
+
+        c.blit(&Canvas::new_filled(2, 2, RED), 1, 1);
+        assert_eq!(BLACK, c.pixel_at(0, 0));
+        assert_eq!(RED, c.pixel_at(1, 1));
+        assert_eq!(RED, c.pixel_at(2, 2));
+        assert_eq!(BLACK, c.pixel_at(3, 2));
+
+        // Clipped by the edges of the canvas
+        c.blit(&Canvas::new_filled(5, 5, BLUE), 3, 2);
+        assert_eq!(BLUE, c.pixel_at(3, 2));
+        assert_eq!(BLACK, c.pixel_at(3, 1));
+        c.blit(&Canvas::new_filled(5, 5, GREEN), 4, 0);
+    }
+
+    #[test]
+    fn filling_a_rectangle() {
+        let mut c = Canvas::new(4, 3);
+
+        c.fill_rect(&Region::new(2, 1, 10, 1), GREEN);
+        assert_eq!(BLACK, c.pixel_at(1, 1));
+        assert_eq!(GREEN, c.pixel_at(2, 1));
+        assert_eq!(GREEN, c.pixel_at(3, 1));
+        assert_eq!(BLACK, c.pixel_at(3, 2));
+    }
+
+    #[test]
+    fn flipping_a_canvas() {
+        let mut c = numbered(3, 3);
+        c.flip_horizontal();
+        assert_eq!(Color::new(2., 0., 0.), c.pixel_at(0, 0));
+        assert_eq!(Color::new(1., 1., 0.), c.pixel_at(1, 1));
+
+        let mut c = numbered(2, 3);
+        c.flip_vertical();
+        assert_eq!(Color::new(1., 2., 0.), c.pixel_at(1, 0));
+        assert_eq!(Color::new(0., 1., 0.), c.pixel_at(0, 1));
+        assert_eq!(Color::new(0., 0., 0.), c.pixel_at(0, 2));
+    }
+
+    #[test]
+    fn resizing_with_the_nearest_pixel() {
+        let c = numbered(2, 2);
+
+        let bigger = c.resize(4, 4, Interpolation::Nearest);
+        assert_eq!(Color::new(0., 0., 0.), bigger.pixel_at(1, 1));
+        assert_eq!(Color::new(1., 0., 0.), bigger.pixel_at(2, 1));
+        assert_eq!(Color::new(1., 1., 0.), bigger.pixel_at(3, 3));
+
+        let smaller = numbered(4, 4).resize(2, 2, Interpolation::Nearest);
+        assert_eq!(Color::new(3., 3., 0.), smaller.pixel_at(1, 1));
+    }
+
+    #[test]
+    fn addressing_pixels_outside_of_the_canvas() {
+        let cases = [
+            (Addressing::Wrap, [2, 0, 1, 2, 0, 1]),
+            (Addressing::Clamp, [0, 0, 1, 2, 2, 2]),
+            (Addressing::Mirror, [0, 0, 1, 2, 2, 1]),
+        ];
+
+        for (addressing, expected) in cases {
+            let indices: Vec<usize> = [-1, 0, 1, 2, 3, 4]
+                .iter()
+                .map(|&i| addressing.index(i, 3))
+                .collect();
+            assert_eq!(expected.to_vec(), indices, "{addressing:?}");
+        }
+    }
+
+    #[test]
+    fn sampling_across_the_edges_of_the_canvas() {
+        let c = numbered(2, 1);
+
+        // Halfway between the last pixel and the first one
+        let at_edge = |addressing| c.sample_addressed(2., 0.5, Interpolation::Bilinear, addressing);
+        assert_eq!(Color::new(0.5, 0., 0.), at_edge(Addressing::Wrap));
+        assert_eq!(Color::new(1., 0., 0.), at_edge(Addressing::Clamp));
+        assert_eq!(Color::new(1., 0., 0.), at_edge(Addressing::Mirror));
+        assert_eq!(
+            Color::new(1., 0., 0.),
+            c.sample_addressed(-0.5, 0.5, Interpolation::Nearest, Addressing::Wrap)
+        );
+        assert_eq!(
+            Color::new(0., 0., 0.),
+            c.sample_addressed(-0.5, 0.5, Interpolation::Nearest, Addressing::Mirror)
+        );
+    }
+
+    #[test]
+    fn sampling_an_empty_canvas() {
+        for addressing in [Addressing::Wrap, Addressing::Clamp, Addressing::Mirror] {
+            assert_eq!(0, addressing.index(5, 0));
+            for c in [Canvas::new(0, 3), Canvas::new(3, 0)] {
+                let color = c.sample_addressed(1.5, 0.5, Interpolation::Bilinear, addressing);
+                assert_eq!(BLACK, color);
+                assert_eq!(0., c.sample_alpha(1.5, 0.5, Interpolation::Nearest));
+            }
+        }
+    }
+
+    #[test]
+    fn parsing_addressing_modes() {
+        assert_eq!(Ok(Addressing::Mirror), "mirror".parse());
+        assert!("border".parse::<Addressing>().is_err());
+    }
+
+    #[test]
+    fn resizing_with_bilinear_interpolation() {
+        let c = numbered(2, 1);
+
+        let bigger = c.resize(4, 1, Interpolation::Bilinear);
+        assert_eq!(Color::new(0., 0., 0.), bigger.pixel_at(0, 0));
+        assert_eq!(Color::new(0.25, 0., 0.), bigger.pixel_at(1, 0));
+        assert_eq!(Color::new(0.75, 0., 0.), bigger.pixel_at(2, 0));
+        assert_eq!(Color::new(1., 0., 0.), bigger.pixel_at(3, 0));
+    }
+
+    #[test]
+    fn sampling_between_pixels() {
+        let c = numbered(3, 3);
+
+        assert_eq!(
+            Color::new(1., 1., 0.),
+            c.sample(1.5, 1.5, Interpolation::Bilinear)
+        );
+        assert_eq!(
+            Color::new(1.5, 0.5, 0.),
+            c.sample(2., 1., Interpolation::Bilinear)
+        );
+        assert_eq!(
+            Color::new(1., 2., 0.),
+            c.sample(1.9, 2.1, Interpolation::Nearest)
+        );
+        // Outside of the canvas
+        assert_eq!(
+            Color::new(2., 0., 0.),
+            c.sample(7., -3., Interpolation::Bilinear)
+        );
+    }
 }
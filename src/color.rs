@@ -39,6 +39,17 @@ impl Color {
     pub fn new(r: f64, g: f64, b: f64) -> Color {
         Color { r, g, b }
     }
+
+    /// The 8 bits components written by the image formats and the terminal preview.
+    pub(crate) fn as_rgb255(&self) -> (u8, u8, u8) {
+        let convert = |v: f64| quantize(v, 255) as u8;
+        (convert(self.r), convert(self.g), convert(self.b))
+    }
+}
+
+/// Scales a component from [0, 1] to an integer in [0, max], clamping the values out of range.
+pub(crate) fn quantize(value: f64, max: u32) -> u32 {
+    (value * max as f64).clamp(0., max as f64) as u32
 }
 
 impl PartialEq for Color {
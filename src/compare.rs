@@ -0,0 +1,296 @@
+use super::canvas::Canvas;
+use super::color::{Color, RED};
+
+use std::fmt;
+
+// Image comparison metrics, used to catch regressions in rendered images.
+// RMSE, PSNR and SSIM are computed on colors clamped to [0, 1], as they would be displayed,
+// while the per-pixel tolerance applies to the rendered colors.
+
+/// A summary of the differences between two canvases of the same size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    /// Number of pixels with a component differing by more than the tolerance
+    pub differing_pixels: usize,
+    /// Largest difference found on a single component
+    pub max_difference: f64,
+    pub rmse: f64,
+    pub psnr: f64,
+    pub ssim: f64,
+}
+
+impl Comparison {
+    /// Returns true if no pixel differs by more than the tolerance.
+    pub fn is_match(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} differing pixels (max difference {:.6}), RMSE {:.6}, PSNR {:.2} dB, SSIM {:.6}",
+            self.differing_pixels, self.max_difference, self.rmse, self.psnr, self.ssim
+        )
+    }
+}
+
+/// Compares two canvases of the same size, a pixel differs if any of its components
+/// differ by more than the tolerance.
+pub fn compare(a: &Canvas, b: &Canvas, tolerance: f64) -> Comparison {
+    Comparison {
+        differing_pixels: differing_pixels(a, b, tolerance),
+        max_difference: max_difference(a, b),
+        rmse: rmse(a, b),
+        psnr: psnr(a, b),
+        ssim: ssim(a, b),
+    }
+}
+
+/// Counts the pixels with a component differing by more than the tolerance.
+pub fn differing_pixels(a: &Canvas, b: &Canvas, tolerance: f64) -> usize {
+    pixel_pairs(a, b)
+        .filter(|(ca, cb)| component_difference(ca, cb) > tolerance)
+        .count()
+}
+
+/// Returns the largest difference on a single component.
+pub fn max_difference(a: &Canvas, b: &Canvas) -> f64 {
+    pixel_pairs(a, b)
+        .map(|(ca, cb)| component_difference(&ca, &cb))
+        .fold(0., f64::max)
+}
+
+/// Root mean square error over all the components.
+pub fn rmse(a: &Canvas, b: &Canvas) -> f64 {
+    let count = (a.width * a.height * 3).max(1);
+    let sum: f64 = pixel_pairs(a, b)
+        .map(|(ca, cb)| {
+            let (ca, cb) = (clamp(&ca), clamp(&cb));
+            (ca.r - cb.r).powi(2) + (ca.g - cb.g).powi(2) + (ca.b - cb.b).powi(2)
+        })
+        .sum();
+    (sum / count as f64).sqrt()
+}
+
+/// Peak signal to noise ratio in decibels, for a peak value of 1.
+/// Identical images have an infinite ratio.
+pub fn psnr(a: &Canvas, b: &Canvas) -> f64 {
+    let rmse = rmse(a, b);
+    if rmse == 0. {
+        f64::INFINITY
+    } else {
+        -20. * rmse.log10()
+    }
+}
+
+/// Mean structural similarity of the luminance, over 8x8 windows overlapping by half.
+/// Identical images have a similarity of 1.
+pub fn ssim(a: &Canvas, b: &Canvas) -> f64 {
+    const WINDOW: usize = 8;
+    const STRIDE: usize = 4;
+    const C1: f64 = 0.01 * 0.01;
+    const C2: f64 = 0.03 * 0.03;
+
+    check_sizes(a, b);
+    let luminance = |canvas: &Canvas, x: usize, y: usize| {
+        let c = clamp(&canvas.pixel_at(x, y));
+        0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+    };
+
+    // Images smaller than a window are compared as a whole
+    let window_width = WINDOW.min(a.width);
+    let window_height = WINDOW.min(a.height);
+    let starts = |size: usize, window: usize| (0..=size - window).step_by(STRIDE);
+
+    let mut total = 0.;
+    let mut windows = 0;
+    for y0 in starts(a.height, window_height) {
+        for x0 in starts(a.width, window_width) {
+            let n = (window_width * window_height) as f64;
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0., 0., 0., 0., 0.);
+            for y in y0..y0 + window_height {
+                for x in x0..x0 + window_width {
+                    let (la, lb) = (luminance(a, x, y), luminance(b, x, y));
+                    sum_a += la;
+                    sum_b += lb;
+                    sum_aa += la * la;
+                    sum_bb += lb * lb;
+                    sum_ab += la * lb;
+                }
+            }
+
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let variance_a = sum_aa / n - mean_a * mean_a;
+            let variance_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+
+            total += ((2. * mean_a * mean_b + C1) * (2. * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2));
+            windows += 1;
+        }
+    }
+
+    if windows == 0 {
+        1.
+    } else {
+        total / windows as f64
+    }
+}
+
+/// Creates a canvas showing the differences: matching pixels are a dimmed gray version
+/// of the first image, differing pixels are red, brighter for larger differences.
+pub fn diff_canvas(a: &Canvas, b: &Canvas, tolerance: f64) -> Canvas {
+    check_sizes(a, b);
+    let mut diff = Canvas::new(a.width, a.height);
+    for y in 0..a.height {
+        for x in 0..a.width {
+            let (ca, cb) = (a.pixel_at(x, y), b.pixel_at(x, y));
+            let difference = component_difference(&ca, &cb);
+            let color = if difference > tolerance {
+                RED * (0.5 + 0.5 * difference.min(1.))
+            } else {
+                let c = clamp(&ca);
+                let gray = 0.25 * (0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b);
+                Color::new(gray, gray, gray)
+            };
+            diff.write_pixel(x, y, color);
+        }
+    }
+    diff
+}
+
+fn check_sizes(a: &Canvas, b: &Canvas) {
+    assert!(
+        a.width == b.width && a.height == b.height,
+        "Cannot compare a {}x{} canvas with a {}x{} canvas",
+        a.width,
+        a.height,
+        b.width,
+        b.height
+    );
+}
+
+fn pixel_pairs<'a>(a: &'a Canvas, b: &'a Canvas) -> impl Iterator<Item = (Color, Color)> + 'a {
+    check_sizes(a, b);
+    a.pixels().iter().copied().zip(b.pixels().iter().copied())
+}
+
+fn component_difference(a: &Color, b: &Color) -> f64 {
+    (a.r - b.r)
+        .abs()
+        .max((a.g - b.g).abs())
+        .max((a.b - b.b).abs())
+}
+
+fn clamp(c: &Color) -> Color {
+    Color::new(c.r.clamp(0., 1.), c.g.clamp(0., 1.), c.b.clamp(0., 1.))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, BLUE, WHITE};
+    use crate::utils::approx_eq;
+
+    fn gradient(width: usize, height: usize) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let v = (x + y) as f64 / (width + height) as f64;
+                c.write_pixel(x, y, Color::new(v, 1. - v, 0.5));
+            }
+        }
+        c
+    }
+
+    #[test]
+    fn comparing_identical_canvases() {
+        let c = gradient(16, 12);
+        let comparison = compare(&c, &gradient(16, 12), 0.);
+
+        assert!(comparison.is_match());
+        assert_eq!(0., comparison.max_difference);
+        assert_eq!(0., comparison.rmse);
+        assert_eq!(f64::INFINITY, comparison.psnr);
+        assert!(approx_eq(1., comparison.ssim));
+    }
+
+    #[test]
+    fn differences_within_the_tolerance_are_ignored() {
+        let a = Canvas::new_filled(4, 4, WHITE);
+        let mut b = Canvas::new_filled(4, 4, WHITE);
+        b.write_pixel(1, 2, Color::new(1., 0.99, 1.));
+        b.write_pixel(3, 3, Color::new(0.5, 1., 1.));
+
+        assert_eq!(1, differing_pixels(&a, &b, 0.02));
+        assert_eq!(2, differing_pixels(&a, &b, 0.));
+        assert!(approx_eq(0.5, max_difference(&a, &b)));
+        assert!(!compare(&a, &b, 0.02).is_match());
+    }
+
+    #[test]
+    fn the_rmse_and_psnr_of_a_uniform_difference() {
+        let a = Canvas::new_filled(3, 3, Color::new(0.5, 0.5, 0.5));
+        let b = Canvas::new_filled(3, 3, Color::new(0.6, 0.4, 0.6));
+
+        assert!(approx_eq(0.1, rmse(&a, &b)));
+        assert!(approx_eq(20., psnr(&a, &b)));
+    }
+
+    #[test]
+    fn colors_are_clamped_before_measuring_the_error() {
+        let a = Canvas::new_filled(2, 2, WHITE);
+        let b = Canvas::new_filled(2, 2, WHITE * 3.);
+
+        assert_eq!(0., rmse(&a, &b));
+        // The tolerance applies to the colors as they were rendered
+        assert_eq!(4, differing_pixels(&a, &b, 0.5));
+    }
+
+    #[test]
+    fn structural_similarity_decreases_with_the_differences() {
+        let a = gradient(32, 32);
+        let mut slightly = gradient(32, 32);
+        slightly.write_pixel(10, 10, WHITE);
+        let mut b = gradient(32, 32);
+        for y in 0..32 {
+            for x in 0..32 {
+                if (x / 4 + y / 4) % 2 == 0 {
+                    b.write_pixel(x, y, BLUE);
+                }
+            }
+        }
+
+        let similar = ssim(&a, &slightly);
+        let different = ssim(&a, &b);
+        assert!(similar < 1.);
+        assert!(different < similar);
+        assert!(different < 0.5);
+    }
+
+    #[test]
+    fn the_structural_similarity_of_tiny_canvases() {
+        let a = Canvas::new_filled(2, 3, WHITE);
+        assert!(approx_eq(1., ssim(&a, &Canvas::new_filled(2, 3, WHITE))));
+        assert!(ssim(&a, &Canvas::new(2, 3)) < 0.01);
+    }
+
+    #[test]
+    fn the_diff_canvas_shows_differing_pixels_in_red() {
+        let a = Canvas::new_filled(2, 1, WHITE);
+        let mut b = Canvas::new_filled(2, 1, WHITE);
+        b.write_pixel(1, 0, BLACK);
+
+        let diff = diff_canvas(&a, &b, 0.01);
+        assert_eq!(Color::new(0.25, 0.25, 0.25), diff.pixel_at(0, 0));
+        assert_eq!(RED, diff.pixel_at(1, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot compare a 2x2 canvas with a 2x3 canvas")]
+    fn comparing_canvases_of_different_sizes() {
+        rmse(&Canvas::new(2, 2), &Canvas::new(2, 3));
+    }
+}
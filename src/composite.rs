@@ -0,0 +1,224 @@
+use super::canvas::Canvas;
+use super::color::Color;
+
+// Porter-Duff compositing of images with premultiplied alpha.
+// The result of each operator is Fs * source + Fd * destination, for both the color and
+// the alpha, where the factors Fs and Fd depend on the alpha of the source and destination.
+
+/// The Porter-Duff operators, combining a source image with a destination image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PorterDuff {
+    /// Nothing is kept
+    Clear,
+    /// Only the source is kept
+    Source,
+    /// Only the destination is kept
+    Destination,
+    /// The source is placed over the destination
+    #[default]
+    SourceOver,
+    /// The destination is placed over the source
+    DestinationOver,
+    /// The part of the source inside the destination
+    SourceIn,
+    /// The part of the destination inside the source
+    DestinationIn,
+    /// The part of the source outside of the destination
+    SourceOut,
+    /// The part of the destination outside of the source
+    DestinationOut,
+    /// The part of the source inside the destination, over the destination
+    SourceAtop,
+    /// The part of the destination inside the source, over the source
+    DestinationAtop,
+    /// The parts of the source and destination that do not overlap
+    Xor,
+}
+
+impl PorterDuff {
+    /// The factors applied to the source and to the destination.
+    fn factors(&self, source_alpha: f64, destination_alpha: f64) -> (f64, f64) {
+        let (a, b) = (source_alpha, destination_alpha);
+        match self {
+            PorterDuff::Clear => (0., 0.),
+            PorterDuff::Source => (1., 0.),
+            PorterDuff::Destination => (0., 1.),
+            PorterDuff::SourceOver => (1., 1. - a),
+            PorterDuff::DestinationOver => (1. - b, 1.),
+            PorterDuff::SourceIn => (b, 0.),
+            PorterDuff::DestinationIn => (0., a),
+            PorterDuff::SourceOut => (1. - b, 0.),
+            PorterDuff::DestinationOut => (0., 1. - a),
+            PorterDuff::SourceAtop => (b, 1. - a),
+            PorterDuff::DestinationAtop => (1. - b, a),
+            PorterDuff::Xor => (1. - b, 1. - a),
+        }
+    }
+
+    /// Combines a premultiplied source color and alpha with a destination color and alpha.
+    pub fn apply(
+        &self,
+        source: Color,
+        source_alpha: f64,
+        destination: Color,
+        destination_alpha: f64,
+    ) -> (Color, f64) {
+        let (fs, fd) = self.factors(source_alpha, destination_alpha);
+        (
+            source * fs + destination * fd,
+            source_alpha * fs + destination_alpha * fd,
+        )
+    }
+}
+
+impl std::str::FromStr for PorterDuff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clear" => Ok(PorterDuff::Clear),
+            "src" => Ok(PorterDuff::Source),
+            "dst" => Ok(PorterDuff::Destination),
+            "src-over" | "over" => Ok(PorterDuff::SourceOver),
+            "dst-over" => Ok(PorterDuff::DestinationOver),
+            "src-in" | "in" => Ok(PorterDuff::SourceIn),
+            "dst-in" => Ok(PorterDuff::DestinationIn),
+            "src-out" | "out" => Ok(PorterDuff::SourceOut),
+            "dst-out" => Ok(PorterDuff::DestinationOut),
+            "src-atop" | "atop" => Ok(PorterDuff::SourceAtop),
+            "dst-atop" => Ok(PorterDuff::DestinationAtop),
+            "xor" => Ok(PorterDuff::Xor),
+            _ => Err(format!("Unknown compositing operator '{s}'")),
+        }
+    }
+}
+
+/// Combines two canvases of the same size with a Porter-Duff operator.
+pub fn composite(source: &Canvas, destination: &Canvas, operator: PorterDuff) -> Canvas {
+    assert!(
+        source.width == destination.width && source.height == destination.height,
+        "Cannot composite a {}x{} canvas with a {}x{} canvas",
+        source.width,
+        source.height,
+        destination.width,
+        destination.height
+    );
+
+    let mut result = Canvas::new(source.width, source.height);
+    for y in 0..source.height {
+        for x in 0..source.width {
+            let (color, alpha) = operator.apply(
+                source.pixel_at(x, y),
+                source.alpha_at(x, y),
+                destination.pixel_at(x, y),
+                destination.alpha_at(x, y),
+            );
+            result.write_pixel(x, y, color);
+            result.write_alpha(x, y, alpha);
+        }
+    }
+    result
+}
+
+/// Places the foreground over a background image of the same size.
+pub fn over(foreground: &Canvas, background: &Canvas) -> Canvas {
+    composite(foreground, background, PorterDuff::SourceOver)
+}
+
+/// Places the foreground over a solid, opaque color.
+pub fn over_color(foreground: &Canvas, background: Color) -> Canvas {
+    over(
+        foreground,
+        &Canvas::new_filled(foreground.width, foreground.height, background),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, BLUE, RED, WHITE};
+
+    /// A 2x1 canvas with an opaque left pixel and a transparent right pixel.
+    fn half_covered(color: Color) -> Canvas {
+        let mut c = Canvas::new_transparent(2, 1);
+        c.write_pixel(0, 0, color);
+        c.write_alpha(0, 0, 1.);
+        c
+    }
+
+    #[test]
+    fn placing_a_render_over_a_background() {
+        let background = Canvas::new_filled(2, 1, BLUE);
+
+        let result = over(&half_covered(RED), &background);
+        assert_eq!(RED, result.pixel_at(0, 0));
+        assert_eq!(BLUE, result.pixel_at(1, 0));
+        assert!(result.is_opaque());
+    }
+
+    #[test]
+    fn partially_covered_pixels_are_blended() {
+        let mut foreground = Canvas::new_transparent(1, 1);
+        // Premultiplied: half covered by white
+        foreground.write_pixel(0, 0, WHITE * 0.5);
+        foreground.write_alpha(0, 0, 0.5);
+
+        let result = over_color(&foreground, RED);
+        assert_eq!(Color::new(1., 0.5, 0.5), result.pixel_at(0, 0));
+        assert_eq!(1., result.alpha_at(0, 0));
+    }
+
+    #[test]
+    fn placing_a_transparent_canvas_over_another() {
+        let background = half_covered(BLUE);
+        let mut foreground = Canvas::new_transparent(2, 1);
+        foreground.write_pixel(1, 0, RED * 0.25);
+        foreground.write_alpha(1, 0, 0.25);
+
+        let result = over(&foreground, &background);
+        assert_eq!(BLUE, result.pixel_at(0, 0));
+        assert_eq!(1., result.alpha_at(0, 0));
+        assert_eq!(RED * 0.25, result.pixel_at(1, 0));
+        assert_eq!(0.25, result.alpha_at(1, 0));
+    }
+
+    #[test]
+    fn the_porter_duff_operators() {
+        let source = (RED, 1.);
+        let destination = (BLUE * 0.5, 0.5);
+        let apply =
+            |operator: PorterDuff| operator.apply(source.0, source.1, destination.0, destination.1);
+
+        assert_eq!((BLACK, 0.), apply(PorterDuff::Clear));
+        assert_eq!(source, apply(PorterDuff::Source));
+        assert_eq!(destination, apply(PorterDuff::Destination));
+        assert_eq!((RED, 1.), apply(PorterDuff::SourceOver));
+        assert_eq!(
+            (RED * 0.5 + BLUE * 0.5, 1.),
+            apply(PorterDuff::DestinationOver)
+        );
+        assert_eq!((RED * 0.5, 0.5), apply(PorterDuff::SourceIn));
+        assert_eq!(destination, apply(PorterDuff::DestinationIn));
+        assert_eq!((RED * 0.5, 0.5), apply(PorterDuff::SourceOut));
+        assert_eq!((BLACK, 0.), apply(PorterDuff::DestinationOut));
+        assert_eq!((RED * 0.5, 0.5), apply(PorterDuff::SourceAtop));
+        assert_eq!(
+            (RED * 0.5 + BLUE * 0.5, 1.),
+            apply(PorterDuff::DestinationAtop)
+        );
+        assert_eq!((RED * 0.5, 0.5), apply(PorterDuff::Xor));
+    }
+
+    #[test]
+    fn parsing_porter_duff_operators() {
+        assert_eq!(Ok(PorterDuff::SourceOver), "over".parse());
+        assert_eq!(Ok(PorterDuff::DestinationAtop), "dst-atop".parse());
+        assert!("under".parse::<PorterDuff>().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot composite a 2x1 canvas with a 1x1 canvas")]
+    fn compositing_canvases_of_different_sizes() {
+        over(&Canvas::new(2, 1), &Canvas::new(1, 1));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::intersection::Intersections;
 
 use super::intersection::Intersection;
-use super::ray::Ray;
+use super::ray::{Footprint, Ray};
 use super::shape::BoxShape;
 use super::tuple::Tuple;
 use super::utils;
@@ -20,6 +20,8 @@ pub struct Comps {
     pub inside: bool,
 
     pub reflectv: Tuple,
+    /// The area seen by the pixel around the point, null for rays without differentials
+    pub footprint: Footprint,
 
     pub n1: f64,
     pub n2: f64,
@@ -29,10 +31,17 @@ impl Comps {
     pub fn prepare_computations(i: &Intersection, r: &Ray, xs: Option<Intersections>) -> Comps {
         let point = r.position(i.t);
         let eyev = -r.direction;
-        let mut normalv = i.object.normal_at(&point);
+        let mut geometric_normalv = i.object.normal_at(&point);
+        let mut normalv = i
+            .object
+            .material()
+            .normal_at(&i.object, &point, &geometric_normalv);
         let xs = xs.unwrap_or_else(|| Intersections::new(vec![i.clone()]));
 
-        let inside = if Tuple::dot_product(&normalv, &eyev) < 0. {
+        // Bumps may tilt the shading normal away from the eye, only the geometry tells
+        // which side of the surface is hit
+        let inside = if Tuple::dot_product(&geometric_normalv, &eyev) < 0. {
+            geometric_normalv = -geometric_normalv;
             normalv = -normalv;
             true
         } else {
@@ -69,8 +78,16 @@ impl Comps {
             }
         }
 
-        let over_point = point + (normalv * utils::EPSILON);
-        let under_point = point - (normalv * utils::EPSILON);
+        // Offset along the geometric normal, so that bumps cannot push the points through
+        let over_point = point + (geometric_normalv * utils::EPSILON);
+        let under_point = point - (geometric_normalv * utils::EPSILON);
+
+        // Strong bumps can reflect the ray into the surface, mirror it back out
+        let mut reflectv = Tuple::reflect(&r.direction, &normalv);
+        let below = Tuple::dot_product(&reflectv, &geometric_normalv);
+        if below < 0. {
+            reflectv = reflectv - geometric_normalv * (2. * below);
+        }
 
         Comps {
             t: i.t,
@@ -81,11 +98,35 @@ impl Comps {
             eyev,
             normalv,
             inside,
-            reflectv: Tuple::reflect(&r.direction, &normalv),
+            reflectv,
+            footprint: r.footprint(&point, &geometric_normalv),
             n1,
             n2,
         }
     }
+
+    /// Direction of the ray refracted through the surface,
+    /// or None under total internal reflection.
+    pub fn refracted_direction(&self) -> Option<Tuple> {
+        // Find the ratio of first index of refraction to the second.
+        // This is inverted from the definition of Snell's Law.
+        let n_ratio = self.n1 / self.n2;
+
+        // cos(theta_i) is the same as the dot product of the two vectors
+        let cos_i = Tuple::dot_product(&self.eyev, &self.normalv);
+
+        // Find sin(theta_t)^2 via trigonometric identity
+        let sin2_t = n_ratio * n_ratio * (1. - (cos_i * cos_i));
+        if sin2_t > 1. {
+            return None; // Total internal reflection
+        }
+
+        // Find cos(theta_t) via trigonometric identity
+        let cos_t = (1.0 - sin2_t).sqrt();
+
+        // Compute the direction of the refracted ray
+        Some(self.normalv * (n_ratio * cos_i - cos_t) - self.eyev * n_ratio)
+    }
 }
 
 #[cfg(test)]
@@ -93,6 +134,9 @@ mod tests {
     use std::f64::consts::SQRT_2;
 
     use super::*;
+    use crate::color::{BLACK, WHITE};
+    use crate::material::{BumpMap, Height, Material};
+    use crate::pattern::GradientPattern;
     use crate::plane::Plane;
     use crate::sphere::Sphere;
     use crate::transform;
@@ -120,7 +164,7 @@ mod tests {
         let i = Intersection::new(4., s);
 
         let comps = Comps::prepare_computations(&i, &r, None);
-        assert_eq!(false, comps.inside);
+        assert!(!comps.inside);
     }
 
     #[test]
@@ -131,7 +175,7 @@ mod tests {
         let i = Intersection::new(1., s);
 
         let comps = Comps::prepare_computations(&i, &r, None);
-        assert_eq!(true, comps.inside);
+        assert!(comps.inside);
     }
 
     #[test]
@@ -146,6 +190,54 @@ mod tests {
         assert!(comps.point.z > comps.over_point.z);
     }
 
+    #[test]
+    fn bumps_change_the_shading_normal_but_not_the_offset_points() {
+        let bump = BumpMap::new(
+            Height::Pattern(GradientPattern::new_boxed(BLACK, WHITE, None)),
+            1.,
+        );
+        let floor = Plane::new_boxed(None, Some(Material::default().with_bump(bump)));
+        let r = Ray::new(&Tuple::point(0.5, 1., 0.3), &Tuple::vector(0., -1., 0.));
+        let i = Intersection::new(1., floor);
+
+        let comps = Comps::prepare_computations(&i, &r, None);
+        assert!(!comps.inside);
+        assert_eq!(Tuple::vector(-SQRT_2 / 2., SQRT_2 / 2., 0.), comps.normalv);
+        assert_eq!(Tuple::vector(-1., 0., 0.), comps.reflectv);
+        assert_eq!(Tuple::point(0.5, utils::EPSILON, 0.3), comps.over_point);
+        assert_eq!(Tuple::point(0.5, -utils::EPSILON, 0.3), comps.under_point);
+    }
+
+    #[test]
+    fn strong_bumps_reflect_above_the_surface() {
+        let bump = BumpMap::new(
+            Height::Pattern(GradientPattern::new_boxed(BLACK, WHITE, None)),
+            3.,
+        );
+        let floor = Plane::new_boxed(None, Some(Material::default().with_bump(bump)));
+        let r = Ray::new(&Tuple::point(0.5, 1., 0.3), &Tuple::vector(0., -1., 0.));
+        let i = Intersection::new(1., floor);
+
+        let comps = Comps::prepare_computations(&i, &r, None);
+        assert_eq!(Tuple::vector(-0.6, 0.8, 0.), comps.reflectv);
+    }
+
+    #[test]
+    fn bumps_seen_from_inside_a_shape() {
+        let bump = BumpMap::new(
+            Height::Pattern(GradientPattern::new_boxed(BLACK, WHITE, None)),
+            1.,
+        );
+        let floor = Plane::new_boxed(None, Some(Material::default().with_bump(bump)));
+        let r = Ray::new(&Tuple::point(0.5, -1., 0.3), &Tuple::vector(0., 1., 0.));
+        let i = Intersection::new(1., floor);
+
+        let comps = Comps::prepare_computations(&i, &r, None);
+        assert!(comps.inside);
+        assert_eq!(Tuple::vector(SQRT_2 / 2., -SQRT_2 / 2., 0.), comps.normalv);
+        assert!(comps.over_point.y < 0.);
+    }
+
     #[test]
     fn precomputing_the_reflection_vector() {
         let r = Ray::new(
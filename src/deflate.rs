@@ -0,0 +1,321 @@
+// Minimal DEFLATE (RFC 1951) and zlib (RFC 1950) compressor, used by the PNG writer.
+//
+// Repeated byte sequences are found with LZ77 (hash chains over a 32 KB window)
+// and written with the fixed Huffman codes of the format.
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Compresses data in the zlib format (a DEFLATE stream with a header and a checksum).
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32 KB window, default compression level
+    let mut out = vec![0x78, 0x9C];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// Compresses data as a single DEFLATE block using the fixed Huffman codes.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    bits.write(1, 1); // Last block
+    bits.write(1, 2); // Fixed Huffman codes
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let (length, distance) = longest_match(data, pos, &head, &prev);
+
+        let step = if length >= MIN_MATCH {
+            write_match(&mut bits, length, distance);
+            length
+        } else {
+            write_literal(&mut bits, data[pos] as u16);
+            1
+        };
+
+        for p in pos..(pos + step) {
+            if p + MIN_MATCH <= data.len() {
+                let h = hash(&data[p..]);
+                prev[p % WINDOW_SIZE] = head[h];
+                head[h] = p;
+            }
+        }
+        pos += step;
+    }
+
+    write_literal(&mut bits, 256); // End of block
+    bits.finish()
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums can be delayed for 5552 bytes before overflowing
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let v = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    (v.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> (usize, usize) {
+    if pos + MIN_MATCH > data.len() {
+        return (0, 0);
+    }
+
+    let max_length = MAX_MATCH.min(data.len() - pos);
+    let (mut best_length, mut best_distance) = (0, 0);
+    let mut candidate = head[hash(&data[pos..])];
+
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || pos - candidate > WINDOW_SIZE - 1 {
+            break;
+        }
+
+        let length = data[candidate..]
+            .iter()
+            .zip(&data[pos..pos + max_length])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if length > best_length {
+            best_length = length;
+            best_distance = pos - candidate;
+            if length == max_length {
+                break;
+            }
+        }
+
+        let next = prev[candidate % WINDOW_SIZE];
+        // Older entries of the ring buffer were overwritten by newer positions
+        if next == usize::MAX || next >= candidate {
+            break;
+        }
+        candidate = next;
+    }
+
+    (best_length, best_distance)
+}
+
+fn write_literal(bits: &mut BitWriter, value: u16) {
+    // Fixed Huffman codes for literals and lengths (RFC 1951, section 3.2.6)
+    let (code, length) = match value {
+        0..=143 => (0x30 + value, 8),
+        144..=255 => (0x190 + value - 144, 9),
+        256..=279 => (value - 256, 7),
+        _ => (0xC0 + value - 280, 8),
+    };
+    bits.write_huffman(code as u32, length);
+}
+
+fn write_match(bits: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE
+        .iter()
+        .rposition(|&b| b as usize <= length)
+        .unwrap();
+    write_literal(bits, 257 + code as u16);
+    bits.write(
+        (length - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code] as u32,
+    );
+
+    let code = DISTANCE_BASE
+        .iter()
+        .rposition(|&b| b as usize <= distance)
+        .unwrap();
+    bits.write_huffman(code as u32, 5);
+    bits.write(
+        (distance - DISTANCE_BASE[code] as usize) as u32,
+        DISTANCE_EXTRA[code] as u32,
+    );
+}
+
+/// Packs bits starting from the least significant bit of each byte.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are stored starting from their most significant bit.
+    fn write_huffman(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Decompresses a zlib stream made of fixed Huffman or stored blocks.
+/// Only used to check the compressor.
+#[cfg(test)]
+pub(crate) fn zlib_decompress(data: &[u8]) -> Vec<u8> {
+    assert_eq!(0, ((data[0] as u16) << 8 | data[1] as u16) % 31);
+
+    let mut input = BitReader {
+        data: &data[2..data.len() - 4],
+        pos: 0,
+    };
+    let mut out: Vec<u8> = vec![];
+    loop {
+        let last = input.read(1);
+        match input.read(2) {
+            0 => {
+                input.pos = input.pos.div_ceil(8) * 8;
+                let length = input.read(16) as usize;
+                input.read(16);
+                for _ in 0..length {
+                    out.push(input.read(8) as u8);
+                }
+            }
+            1 => loop {
+                let symbol = input.read_fixed_literal();
+                if symbol < 256 {
+                    out.push(symbol as u8);
+                } else if symbol == 256 {
+                    break;
+                } else {
+                    let i = (symbol - 257) as usize;
+                    let length =
+                        LENGTH_BASE[i] as usize + input.read(LENGTH_EXTRA[i] as u32) as usize;
+                    let i = input.read_huffman(5) as usize;
+                    let distance =
+                        DISTANCE_BASE[i] as usize + input.read(DISTANCE_EXTRA[i] as u32) as usize;
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            },
+            t => panic!("Unsupported block type {t}"),
+        }
+        if last == 1 {
+            break;
+        }
+    }
+
+    assert_eq!(
+        adler32(&out).to_be_bytes(),
+        data[data.len() - 4..],
+        "Bad checksum"
+    );
+    out
+}
+
+#[cfg(test)]
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+#[cfg(test)]
+impl BitReader<'_> {
+    fn read(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let bit = (self.data[self.pos / 8] >> (self.pos % 8)) & 1;
+            value |= (bit as u32) << i;
+            self.pos += 1;
+        }
+        value
+    }
+
+    fn read_huffman(&mut self, length: u32) -> u32 {
+        (0..length).fold(0, |code, _| (code << 1) | self.read(1))
+    }
+
+    fn read_fixed_literal(&mut self) -> u16 {
+        let code = self.read_huffman(7);
+        if code <= 0x17 {
+            return 256 + code as u16;
+        }
+        let code = (code << 1) | self.read(1);
+        match code {
+            0x30..=0xBF => (code - 0x30) as u16,
+            0xC0..=0xC7 => (280 + code - 0xC0) as u16,
+            _ => (144 + ((code << 1) | self.read(1)) - 0x190) as u16,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_adler32_checksum() {
+        assert_eq!(1, adler32(b""));
+        assert_eq!(0x11E60398, adler32(b"Wikipedia"));
+    }
+
+    #[test]
+    fn compressing_an_empty_input() {
+        let compressed = zlib_compress(b"");
+        assert_eq!(vec![0x78, 0x9C, 0x03, 0x00, 0, 0, 0, 1], compressed);
+        assert!(zlib_decompress(&compressed).is_empty());
+    }
+
+    #[test]
+    fn compressed_data_can_be_decompressed() {
+        let data = b"The quick brown fox jumps over the lazy dog. The quick brown fox!".to_vec();
+        assert_eq!(data, zlib_decompress(&zlib_compress(&data)));
+
+        let bytes: Vec<u8> = (0..70000u32).map(|i| (i * 7 % 251) as u8).collect();
+        assert_eq!(bytes, zlib_decompress(&zlib_compress(&bytes)));
+    }
+
+    #[test]
+    fn repeated_data_is_smaller_once_compressed() {
+        let data = vec![42u8; 100000];
+        let compressed = zlib_compress(&data);
+
+        assert!(compressed.len() < 1000);
+        assert_eq!(data, zlib_decompress(&compressed));
+    }
+}
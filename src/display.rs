@@ -0,0 +1,229 @@
+use super::canvas::Canvas;
+use super::color::Color;
+
+/// Curve compressing high dynamic range values into the displayable [0, 1] range.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ToneCurve {
+    /// Values are kept as is, and clipped by the image writer.
+    Linear,
+    /// x / (1 + x)
+    Reinhard,
+    /// John Hable's filmic curve, from Uncharted 2.
+    Filmic,
+    /// Krzysztof Narkowicz's fit of the ACES reference curve.
+    Aces,
+}
+
+impl ToneCurve {
+    /// The curves are only defined for positive values, negative values (from
+    /// sharpening or edge filters) are mapped to 0. Linear keeps them as is.
+    pub fn apply(&self, x: f64) -> f64 {
+        let x = if *self == ToneCurve::Linear {
+            x
+        } else {
+            x.max(0.)
+        };
+        match self {
+            ToneCurve::Linear => x,
+            ToneCurve::Reinhard => x / (1. + x),
+            ToneCurve::Filmic => {
+                const EXPOSURE_BIAS: f64 = 2.;
+                const WHITE_POINT: f64 = 11.2;
+                hable(x * EXPOSURE_BIAS) / hable(WHITE_POINT)
+            }
+            ToneCurve::Aces => {
+                let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+                mapped.clamp(0., 1.)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for ToneCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(ToneCurve::Linear),
+            "reinhard" => Ok(ToneCurve::Reinhard),
+            "filmic" => Ok(ToneCurve::Filmic),
+            "aces" => Ok(ToneCurve::Aces),
+            _ => Err(format!("Unknown tone curve: {s}")),
+        }
+    }
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15; // Shoulder strength
+    const B: f64 = 0.50; // Linear strength
+    const C: f64 = 0.10; // Linear angle
+    const D: f64 = 0.20; // Toe strength
+    const E: f64 = 0.02; // Toe numerator
+    const F: f64 = 0.30; // Toe denominator
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+/// Encodes a linear value with the sRGB transfer function (gamma).
+pub fn srgb_encode(x: f64) -> f64 {
+    let x = x.clamp(0., 1.);
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Converts the linear radiance of a render into display values,
+/// before the image writers quantize them.
+///
+/// The default pipeline does nothing, so images are written as before.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DisplayPipeline {
+    /// In stops: every +1 doubles the brightness.
+    pub exposure: f64,
+    pub tone_curve: ToneCurve,
+    /// Encode the result with the sRGB transfer function.
+    pub srgb: bool,
+}
+
+impl DisplayPipeline {
+    pub fn new(exposure: f64, tone_curve: ToneCurve, srgb: bool) -> DisplayPipeline {
+        DisplayPipeline {
+            exposure,
+            tone_curve,
+            srgb,
+        }
+    }
+
+    pub fn apply_color(&self, color: Color) -> Color {
+        let scale = 2f64.powf(self.exposure);
+        let map = |x: f64| {
+            let x = self.tone_curve.apply(x * scale);
+            if self.srgb { srgb_encode(x) } else { x }
+        };
+        Color::new(map(color.r), map(color.g), map(color.b))
+    }
+
+    /// Applies the pipeline to every pixel, keeping the alpha.
+    /// Partially transparent colors are mapped without their premultiplication.
+    pub fn apply(&self, canvas: &Canvas) -> Canvas {
+        let mut result = canvas.clone();
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                let alpha = canvas.alpha_at(x, y);
+                let color = if alpha > 0. && alpha < 1. {
+                    self.apply_color(canvas.pixel_at(x, y) * (1. / alpha)) * alpha
+                } else {
+                    self.apply_color(canvas.pixel_at(x, y))
+                };
+                result.write_pixel(x, y, color);
+            }
+        }
+        result
+    }
+}
+
+impl Default for DisplayPipeline {
+    fn default() -> Self {
+        DisplayPipeline::new(0., ToneCurve::Linear, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, RED};
+    use crate::utils::approx_eq;
+
+    #[test]
+    fn the_default_pipeline_keeps_the_colors() {
+        let p = DisplayPipeline::default();
+        let c = Color::new(1.5, 0.505, -0.5);
+
+        let mapped = p.apply_color(c);
+        assert_eq!((c.r, c.g, c.b), (mapped.r, mapped.g, mapped.b));
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let p = DisplayPipeline::new(1., ToneCurve::Linear, false);
+        assert_eq!(
+            Color::new(0.5, 1., 0.),
+            p.apply_color(Color::new(0.25, 0.5, 0.))
+        );
+
+        let p = DisplayPipeline::new(-2., ToneCurve::Linear, false);
+        assert_eq!(Color::new(0.25, 0., 0.), p.apply_color(RED));
+    }
+
+    #[test]
+    fn the_reinhard_curve() {
+        assert_eq!(0., ToneCurve::Reinhard.apply(0.));
+        assert_eq!(0.5, ToneCurve::Reinhard.apply(1.));
+        assert!(ToneCurve::Reinhard.apply(1000.) < 1.);
+    }
+
+    #[test]
+    fn the_filmic_curve_maps_the_white_point_to_white() {
+        assert!(approx_eq(0., ToneCurve::Filmic.apply(0.)));
+        assert!(approx_eq(1., ToneCurve::Filmic.apply(5.6)));
+        assert!(ToneCurve::Filmic.apply(0.5) < ToneCurve::Filmic.apply(0.6));
+    }
+
+    #[test]
+    fn the_aces_curve_is_clamped() {
+        assert_eq!(0., ToneCurve::Aces.apply(0.));
+        assert_eq!(1., ToneCurve::Aces.apply(100.));
+        assert!(approx_eq(0.803797, ToneCurve::Aces.apply(1.)));
+    }
+
+    #[test]
+    fn negative_values_are_black_with_every_curve() {
+        for curve in [ToneCurve::Reinhard, ToneCurve::Filmic, ToneCurve::Aces] {
+            let p = DisplayPipeline::new(0., curve, true);
+            assert_eq!(BLACK, p.apply_color(Color::new(-2., -1., -0.5)));
+            assert!(approx_eq(0., curve.apply(-1.)));
+        }
+    }
+
+    #[test]
+    fn parsing_tone_curves() {
+        assert_eq!(Ok(ToneCurve::Aces), "aces".parse());
+        assert_eq!(Ok(ToneCurve::Filmic), "filmic".parse());
+        assert!("gamma".parse::<ToneCurve>().is_err());
+    }
+
+    #[test]
+    fn srgb_encoding() {
+        assert_eq!(0., srgb_encode(0.));
+        assert!(approx_eq(0.02584, srgb_encode(0.002)));
+        assert!(approx_eq(0.73536, srgb_encode(0.5)));
+        assert!(approx_eq(1., srgb_encode(1.)));
+        assert!(approx_eq(1., srgb_encode(3.)));
+    }
+
+    #[test]
+    fn applying_a_pipeline_to_a_canvas() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(0, 0, Color::new(1., 3., 0.));
+        let p = DisplayPipeline::new(0., ToneCurve::Reinhard, false);
+
+        let mapped = p.apply(&c);
+        assert_eq!(Color::new(0.5, 0.75, 0.), mapped.pixel_at(0, 0));
+        assert_eq!(BLACK, mapped.pixel_at(1, 0));
+    }
+
+    #[test]
+    fn a_pipeline_keeps_the_alpha() {
+        let mut c = Canvas::new_transparent(2, 1);
+        // Half covered by a color of 3
+        c.write_pixel(1, 0, Color::new(1.5, 1.5, 1.5));
+        c.write_alpha(1, 0, 0.5);
+        let p = DisplayPipeline::new(0., ToneCurve::Reinhard, false);
+
+        let mapped = p.apply(&c);
+        assert_eq!(0., mapped.alpha_at(0, 0));
+        assert_eq!(0.5, mapped.alpha_at(1, 0));
+        assert_eq!(Color::new(0.375, 0.375, 0.375), mapped.pixel_at(1, 0));
+    }
+}
@@ -0,0 +1,582 @@
+use super::canvas::Canvas;
+use super::color::Color;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+// High dynamic range image formats, keeping colors above 1.0:
+// - Portable Float Map (PFM): 32 bits floating point per component
+// - Radiance HDR: 8 bits per component with a shared exponent (RGBE)
+
+/// Errors found while reading a PFM or Radiance HDR image.
+#[derive(Debug)]
+pub enum HdrError {
+    Io(io::Error),
+    /// The file does not start with a known magic number
+    UnknownFormat(String),
+    /// Something in the header is missing, invalid or not supported
+    InvalidHeader(String),
+    /// The file ends before all the pixels were read
+    MissingPixels {
+        expected: usize,
+        found: usize,
+    },
+    /// The run-length encoding of a scanline is invalid
+    InvalidScanline(usize),
+}
+
+impl fmt::Display for HdrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HdrError::Io(err) => write!(f, "{err}"),
+            HdrError::UnknownFormat(magic) => write!(f, "Unknown image format '{magic}'"),
+            HdrError::InvalidHeader(reason) => write!(f, "Invalid header: {reason}"),
+            HdrError::MissingPixels { expected, found } => write!(
+                f,
+                "Truncated image data: expected {expected} pixels, found {found}"
+            ),
+            HdrError::InvalidScanline(y) => write!(f, "Invalid run-length encoding on row {y}"),
+        }
+    }
+}
+
+impl std::error::Error for HdrError {}
+
+impl From<io::Error> for HdrError {
+    fn from(err: io::Error) -> Self {
+        HdrError::Io(err)
+    }
+}
+
+/// Writes the canvas as a little-endian color PFM image.
+pub fn write_pfm<W: Write>(canvas: &Canvas, out: &mut W) -> io::Result<()> {
+    // A negative scale means little-endian
+    write!(out, "PF\n{} {}\n-1.0\n", canvas.width, canvas.height)?;
+
+    // Rows are stored from the bottom to the top of the image
+    let mut row = Vec::with_capacity(canvas.width * 12);
+    for y in (0..canvas.height).rev() {
+        row.clear();
+        for x in 0..canvas.width {
+            let c = canvas.pixel_at(x, y);
+            for value in [c.r, c.g, c.b] {
+                row.extend((value as f32).to_le_bytes());
+            }
+        }
+        out.write_all(&row)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a color (PF) or grayscale (Pf) PFM image of either endianness.
+/// The magnitude of the scale factor is ignored.
+pub fn read_pfm<R: Read>(input: &mut R) -> Result<Canvas, HdrError> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+
+    let mut pos = 0;
+    let mut next_token = || -> Option<String> {
+        while data.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        let start = pos;
+        while data.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        (start < pos).then(|| String::from_utf8_lossy(&data[start..pos]).into_owned())
+    };
+
+    let magic = next_token().unwrap_or_default();
+    let channels = match magic.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(HdrError::UnknownFormat(magic)),
+    };
+    let width = parse_header(next_token(), "width")?;
+    let height = parse_header(next_token(), "height")?;
+    let scale: f64 = parse_header(next_token(), "scale")?;
+    // A single whitespace separates the header from the pixels
+    let start = pos + 1;
+
+    let values: Vec<f64> = data
+        .get(start..)
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .map(|b| {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            if scale < 0. {
+                f32::from_le_bytes(bytes) as f64
+            } else {
+                f32::from_be_bytes(bytes) as f64
+            }
+        })
+        .collect();
+
+    let expected = pixel_count(width, height)?;
+    if values.len() / channels < expected {
+        return Err(HdrError::MissingPixels {
+            expected,
+            found: values.len() / channels,
+        });
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    for (i, v) in values.chunks_exact(channels).take(expected).enumerate() {
+        let color = if channels == 3 {
+            Color::new(v[0], v[1], v[2])
+        } else {
+            Color::new(v[0], v[0], v[0])
+        };
+        canvas.write_pixel(i % width, height - 1 - i / width, color);
+    }
+
+    Ok(canvas)
+}
+
+/// Writes the canvas as a run-length encoded Radiance HDR (RGBE) image.
+pub fn write_rgbe<W: Write>(canvas: &Canvas, out: &mut W) -> io::Result<()> {
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        canvas.height, canvas.width
+    )?;
+
+    let width = canvas.width;
+    let mut pixels = Vec::with_capacity(width);
+    let mut line = Vec::with_capacity(width * 4 + 4);
+    for y in 0..canvas.height {
+        pixels.clear();
+        pixels.extend((0..width).map(|x| to_rgbe(canvas.pixel_at(x, y))));
+
+        line.clear();
+        if (8..0x8000).contains(&width) {
+            line.extend([2, 2, (width >> 8) as u8, (width & 0xFF) as u8]);
+            for component in 0..4 {
+                let bytes: Vec<u8> = pixels.iter().map(|p| p[component]).collect();
+                encode_runs(&bytes, &mut line);
+            }
+        } else {
+            // Too narrow or too wide for run-length encoding
+            pixels.iter().for_each(|p| line.extend(p));
+        }
+        out.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a Radiance HDR image, flat or run-length encoded.
+/// Only the standard orientation (-Y height +X width) is supported.
+pub fn read_rgbe<R: Read>(input: &mut R) -> Result<Canvas, HdrError> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+
+    let mut pos = 0;
+    let mut next_line = || -> Option<String> {
+        if pos >= data.len() {
+            return None;
+        }
+        let start = pos;
+        while data.get(pos).is_some_and(|&b| b != b'\n') {
+            pos += 1;
+        }
+        let line = String::from_utf8_lossy(&data[start..pos]).into_owned();
+        pos += 1;
+        Some(line)
+    };
+
+    let magic = next_line().unwrap_or_default();
+    if !magic.starts_with("#?") {
+        return Err(HdrError::UnknownFormat(magic));
+    }
+
+    // Header variables end with an empty line
+    loop {
+        match next_line() {
+            None => return Err(HdrError::InvalidHeader("missing resolution".to_string())),
+            Some(line) if line.is_empty() => break,
+            Some(line) => {
+                if let Some(format) = line.strip_prefix("FORMAT=")
+                    && format != "32-bit_rle_rgbe"
+                {
+                    return Err(HdrError::InvalidHeader(format!(
+                        "unsupported format {format}"
+                    )));
+                }
+            }
+        }
+    }
+
+    let resolution = next_line().unwrap_or_default();
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => (
+            parse_header(Some(h.to_string()), "height")?,
+            parse_header(Some(w.to_string()), "width")?,
+        ),
+        _ => {
+            return Err(HdrError::InvalidHeader(format!(
+                "unsupported resolution '{resolution}'"
+            )));
+        }
+    };
+
+    let expected = pixel_count(width, height)?;
+    if expected == 0 {
+        return Ok(Canvas::new(width, height));
+    }
+
+    // The header is not trusted with the allocations: rows are only stored once decoded,
+    // and the canvas is created at the end
+    let mut rest = &data[pos.min(data.len())..];
+    let mut pixels = Vec::new();
+    let mut scanline = Vec::new();
+    for y in 0..height {
+        let missing = || HdrError::MissingPixels {
+            expected,
+            found: y * width,
+        };
+        // At least the 4 bytes of a flat pixel, or 2 bytes for every 127 values in each of
+        // the 4 components of a run-length encoded scanline
+        let shortest = if width >= 8 {
+            4 + 8 * width.div_ceil(127)
+        } else {
+            4 * width
+        };
+        if rest.len() < shortest {
+            return Err(missing());
+        }
+        scanline.resize(width, [0u8; 4]);
+
+        let rle = width >= 8 && rest.len() >= 4 && rest[0] == 2 && rest[1] == 2 && rest[2] < 128;
+        if rle {
+            if ((rest[2] as usize) << 8 | rest[3] as usize) != width {
+                return Err(HdrError::InvalidScanline(y));
+            }
+            rest = &rest[4..];
+            for component in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let (&count, tail) = rest.split_first().ok_or_else(missing)?;
+                    rest = tail;
+                    if count > 128 {
+                        let count = count as usize - 128;
+                        let (&value, tail) = rest.split_first().ok_or_else(missing)?;
+                        rest = tail;
+                        if count > width - x {
+                            return Err(HdrError::InvalidScanline(y));
+                        }
+                        scanline[x..x + count]
+                            .iter_mut()
+                            .for_each(|p| p[component] = value);
+                        x += count;
+                    } else {
+                        let count = count as usize;
+                        if count == 0 || count > width - x {
+                            return Err(HdrError::InvalidScanline(y));
+                        }
+                        if rest.len() < count {
+                            return Err(missing());
+                        }
+                        for (p, &value) in scanline[x..x + count].iter_mut().zip(rest) {
+                            p[component] = value;
+                        }
+                        rest = &rest[count..];
+                        x += count;
+                    }
+                }
+            }
+        } else {
+            if rest.len() < width * 4 {
+                return Err(missing());
+            }
+            for (p, bytes) in scanline.iter_mut().zip(rest.chunks_exact(4)) {
+                p.copy_from_slice(bytes);
+            }
+            rest = &rest[width * 4..];
+        }
+
+        pixels.extend(scanline.iter().map(from_rgbe));
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    for (i, color) in pixels.into_iter().enumerate() {
+        canvas.write_pixel(i % width, i / width, color);
+    }
+    Ok(canvas)
+}
+
+/// The number of pixels of an image, if it fits in memory addresses.
+fn pixel_count(width: usize, height: usize) -> Result<usize, HdrError> {
+    width
+        .checked_mul(height)
+        .ok_or_else(|| HdrError::InvalidHeader(format!("{width}x{height} is too large")))
+}
+
+fn parse_header<T: std::str::FromStr>(token: Option<String>, name: &str) -> Result<T, HdrError> {
+    match token {
+        None => Err(HdrError::InvalidHeader(format!("missing {name}"))),
+        Some(token) => token
+            .parse::<T>()
+            .map_err(|_| HdrError::InvalidHeader(format!("invalid {name} '{token}'"))),
+    }
+}
+
+/// Stores a color as three 8 bits mantissas sharing the exponent of the brightest component.
+/// Infinite components are clamped to the largest value of the format, NaN and negative
+/// components are written as zero.
+fn to_rgbe(color: Color) -> [u8; 4] {
+    // The largest mantissa with the largest exponent
+    let largest = 255. * 2f64.powi(127 - 8);
+    let channel = |c: f64| if c.is_nan() { 0. } else { c.clamp(0., largest) };
+    let (r, g, b) = (channel(color.r), channel(color.g), channel(color.b));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // v = mantissa * 2^exponent, with the mantissa in [0.5, 1)
+    let mut exponent = v.log2().floor() as i32 + 1;
+    let mut mantissa = v / 2f64.powi(exponent);
+    if mantissa >= 1. {
+        mantissa /= 2.;
+        exponent += 1;
+    } else if mantissa < 0.5 {
+        mantissa *= 2.;
+        exponent -= 1;
+    }
+
+    let scale = mantissa * 256. / v;
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+fn from_rgbe(rgbe: &[u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0., 0., 0.);
+    }
+    let f = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    Color::new(rgbe[0] as f64 * f, rgbe[1] as f64 * f, rgbe[2] as f64 * f)
+}
+
+/// Run-length encodes one component of a scanline: runs of 4 or more identical bytes
+/// are written as (128 + count, value), the others as (count, values...).
+fn encode_runs(bytes: &[u8], out: &mut Vec<u8>) {
+    const MIN_RUN: usize = 4;
+
+    let mut pos = 0;
+    while pos < bytes.len() {
+        // Find the next run long enough to be worth encoding
+        let mut run_start = pos;
+        let mut run_length = 0;
+        while run_start < bytes.len() {
+            run_length = bytes[run_start..]
+                .iter()
+                .take(127)
+                .take_while(|&&b| b == bytes[run_start])
+                .count();
+            if run_length >= MIN_RUN {
+                break;
+            }
+            run_start += run_length;
+        }
+        if run_length < MIN_RUN {
+            run_start = bytes.len();
+        }
+
+        // Write the bytes before the run as they are
+        while pos < run_start {
+            let count = (run_start - pos).min(128);
+            out.push(count as u8);
+            out.extend(&bytes[pos..pos + count]);
+            pos += count;
+        }
+
+        if run_start < bytes.len() {
+            out.push(128 + run_length as u8);
+            out.push(bytes[run_start]);
+            pos = run_start + run_length;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLACK, RED, WHITE};
+
+    fn canvas() -> Canvas {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(12.5, 0.25, 0.));
+        c.write_pixel(2, 0, WHITE);
+        c.write_pixel(1, 1, Color::new(0.1, 1000., 3.));
+        c
+    }
+
+    #[test]
+    fn writing_a_pfm_image() {
+        let mut out = Vec::new();
+        write_pfm(&canvas(), &mut out).unwrap();
+
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(header.to_vec(), out[..header.len()].to_vec());
+        assert_eq!(header.len() + 3 * 2 * 12, out.len());
+
+        // The first stored row is the bottom one
+        let data = &out[header.len()..];
+        assert_eq!(1000f32.to_le_bytes(), data[16..20]);
+        assert_eq!(12.5f32.to_le_bytes(), data[36..40]);
+    }
+
+    #[test]
+    fn a_pfm_image_keeps_the_colors_unclamped() {
+        let mut out = Vec::new();
+        write_pfm(&canvas(), &mut out).unwrap();
+        let c = read_pfm(&mut out.as_slice()).unwrap();
+
+        assert_eq!(3, c.width);
+        assert_eq!(2, c.height);
+        assert_eq!(Color::new(12.5, 0.25, 0.), c.pixel_at(0, 0));
+        assert_eq!(Color::new(0.1, 1000., 3.), c.pixel_at(1, 1));
+        assert_eq!(BLACK, c.pixel_at(0, 1));
+    }
+
+    #[test]
+    fn reading_a_big_endian_grayscale_pfm() {
+        let mut pfm = b"Pf\n2 1\n1.0\n".to_vec();
+        pfm.extend(0.5f32.to_be_bytes());
+        pfm.extend(4f32.to_be_bytes());
+
+        let c = read_pfm(&mut pfm.as_slice()).unwrap();
+        assert_eq!(Color::new(0.5, 0.5, 0.5), c.pixel_at(0, 0));
+        assert_eq!(Color::new(4., 4., 4.), c.pixel_at(1, 0));
+    }
+
+    #[test]
+    fn reading_invalid_pfm_images() {
+        let err = read_pfm(&mut b"P6\n1 1\n255\n".as_slice()).unwrap_err();
+        assert!(matches!(err, HdrError::UnknownFormat(_)));
+
+        let err = read_pfm(&mut b"PF\n2 1\n-1.0\n\0\0\0\0".as_slice()).unwrap_err();
+        assert!(matches!(
+            err,
+            HdrError::MissingPixels {
+                expected: 2,
+                found: 0
+            }
+        ));
+    }
+
+    #[test]
+    fn converting_colors_to_rgbe() {
+        assert_eq!([128, 128, 128, 129], to_rgbe(WHITE));
+        assert_eq!([0, 0, 0, 0], to_rgbe(BLACK));
+        assert_eq!([200, 4, 0, 132], to_rgbe(Color::new(12.5, 0.25, 0.)));
+
+        assert_eq!(WHITE, from_rgbe(&[128, 128, 128, 129]));
+        assert_eq!(Color::new(12.5, 0.25, 0.), from_rgbe(&[200, 4, 0, 132]));
+    }
+
+    #[test]
+    fn a_radiance_image_clamps_the_colors_it_cannot_hold() {
+        let mut c = Canvas::new(3, 1);
+        c.write_pixel(0, 0, Color::new(f64::INFINITY, 1., 0.));
+        c.write_pixel(1, 0, Color::new(f64::NAN, -1., 0.5));
+        c.write_pixel(2, 0, Color::new(1e300, 0., 0.));
+
+        let mut out = Vec::new();
+        write_rgbe(&c, &mut out).unwrap();
+        let read = read_rgbe(&mut out.as_slice()).unwrap();
+
+        let largest = 255. * 2f64.powi(119);
+        assert_eq!(largest, read.pixel_at(0, 0).r);
+        assert_eq!(0., read.pixel_at(0, 0).b);
+        assert_eq!(Color::new(0., 0., 0.5), read.pixel_at(1, 0));
+        assert_eq!(largest, read.pixel_at(2, 0).r);
+    }
+
+    #[test]
+    fn run_length_encoding() {
+        let mut out = vec![];
+        encode_runs(&[1, 2, 3, 3, 3, 3, 3, 4], &mut out);
+        assert_eq!(vec![2, 1, 2, 128 + 5, 3, 1, 4], out);
+
+        let mut out = vec![];
+        encode_runs(&[7; 300], &mut out);
+        assert_eq!(vec![255, 7, 255, 7, 128 + 46, 7], out);
+    }
+
+    #[test]
+    fn a_radiance_image_keeps_colors_above_one() {
+        let mut c = Canvas::new_filled(10, 3, RED * 5.);
+        c.write_pixel(4, 1, Color::new(0.5, 64., 2.));
+
+        let mut out = Vec::new();
+        write_rgbe(&c, &mut out).unwrap();
+        assert!(out.starts_with(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 3 +X 10\n"));
+
+        let read = read_rgbe(&mut out.as_slice()).unwrap();
+        assert_eq!(10, read.width);
+        assert_eq!(3, read.height);
+        assert_eq!(RED * 5., read.pixel_at(0, 0));
+        assert_eq!(Color::new(0.5, 64., 2.), read.pixel_at(4, 1));
+    }
+
+    #[test]
+    fn narrow_radiance_images_are_not_run_length_encoded() {
+        let mut out = Vec::new();
+        write_rgbe(&canvas(), &mut out).unwrap();
+
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 3\n";
+        assert_eq!(header.len() + 3 * 2 * 4, out.len());
+
+        let read = read_rgbe(&mut out.as_slice()).unwrap();
+        assert_eq!(WHITE, read.pixel_at(2, 0));
+    }
+
+    #[test]
+    fn reading_invalid_radiance_images() {
+        let err = read_rgbe(&mut b"PF\n".as_slice()).unwrap_err();
+        assert!(matches!(err, HdrError::UnknownFormat(_)));
+
+        let err = read_rgbe(&mut b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n".as_slice()).unwrap_err();
+        assert!(matches!(err, HdrError::InvalidHeader(_)));
+
+        let err = read_rgbe(&mut b"#?RADIANCE\n\n+Y 1 +X 1\n".as_slice()).unwrap_err();
+        assert!(matches!(err, HdrError::InvalidHeader(_)));
+
+        let err =
+            read_rgbe(&mut b"#?RADIANCE\n\n-Y 2 +X 1\n\x80\x80\x80\x81".as_slice()).unwrap_err();
+        assert_eq!(
+            "Truncated image data: expected 2 pixels, found 1",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn reading_a_header_larger_than_the_data() {
+        let err = read_pfm(&mut b"PF\n4294967296 4294967296\n-1.0\n".as_slice()).unwrap_err();
+        assert!(matches!(err, HdrError::InvalidHeader(_)));
+
+        let err = read_pfm(&mut b"PF\n100000 100000\n-1.0\n\0\0\0\0".as_slice()).unwrap_err();
+        assert!(matches!(err, HdrError::MissingPixels { found: 0, .. }));
+
+        let err = read_rgbe(&mut b"#?RADIANCE\n\n-Y 100000 +X 100000\n\x80\x80\x80\x81".as_slice())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            HdrError::MissingPixels {
+                expected: 10_000_000_000,
+                found: 0
+            }
+        ));
+
+        let err =
+            read_rgbe(&mut b"#?RADIANCE\n\n-Y 4294967296 +X 4294967296\n".as_slice()).unwrap_err();
+        assert!(matches!(err, HdrError::InvalidHeader(_)));
+    }
+}
@@ -0,0 +1,254 @@
+use super::ambient_occlusion::AmbientOcclusion;
+use super::camera::Camera;
+use super::color::{BLACK, Color};
+use super::comps::Comps;
+use super::material::Channel;
+use super::ray::Ray;
+use super::world::{self, World};
+
+/// Computes the color seen along camera rays.
+///
+/// Implement this trait to plug a new shading algorithm into `Camera::render_with`.
+pub trait Integrator {
+    /// The color seen along a ray.
+    fn color_at(&self, w: &World, r: &Ray) -> Color;
+
+    /// The color of one pixel. By default, a single ray through its center.
+    fn pixel_color(&self, camera: &Camera, w: &World, px: usize, py: usize) -> Color {
+        self.color_at(w, &camera.ray_for_pixel(px, py))
+    }
+
+    /// The color of one pixel and the fraction of it covered by objects, used as its alpha
+    /// when the background is transparent. Both must come from the same camera rays, so
+    /// that integrators sampling several rays per pixel have to override this method.
+    /// By default, the color and whether the ray through the center hits something.
+    fn pixel_color_and_coverage(
+        &self,
+        camera: &Camera,
+        w: &World,
+        px: usize,
+        py: usize,
+    ) -> (Color, f64) {
+        let r = camera.ray_for_pixel(px, py);
+        let coverage = if w.intersect(&r).hit().is_some() {
+            1.
+        } else {
+            0.
+        };
+        (self.color_at(w, &r), coverage)
+    }
+}
+
+/// The recursive ray tracer from the book: Phong lighting with shadows,
+/// plus perfect reflection and refraction.
+/// The ambient term is optionally scaled by ambient occlusion.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Whitted {
+    pub max_depth: u8,
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+}
+
+impl Whitted {
+    pub fn new(max_depth: u8) -> Whitted {
+        Whitted {
+            max_depth,
+            ambient_occlusion: None,
+        }
+    }
+
+    /// The color seen along a ray, following at most `remaining` reflections or refractions.
+    pub fn trace(&self, w: &World, r: &Ray, remaining: u8) -> Color {
+        let i = w.intersect(r);
+        match i.hit() {
+            None => BLACK,
+            Some(h) => {
+                let comps = Comps::prepare_computations(h, r, Some(i.clone()));
+                self.shade_hit(w, &comps, remaining)
+            }
+        }
+    }
+
+    pub fn shade_hit(&self, w: &World, comps: &Comps, remaining: u8) -> Color {
+        if let Some(light) = w.light
+            && remaining > 0
+        {
+            let material =
+                comps
+                    .object
+                    .material()
+                    .at(&comps.object, &comps.point, &comps.footprint);
+            let in_shadow = w.is_shadowed(&comps.over_point);
+            let color = material.filtered_color_at(&comps.object, &comps.point, &comps.footprint);
+            let surface = match &self.ambient_occlusion {
+                None => material.lighting_with_color(
+                    color,
+                    &light,
+                    &comps.point,
+                    &comps.eyev,
+                    &comps.normalv,
+                    in_shadow,
+                ),
+                Some(ao) => {
+                    let ambient =
+                        color * light.intensity * material.ambient * ao.accessibility(w, comps);
+                    ambient
+                        + material.direct_lighting_with_color(
+                            color,
+                            &light,
+                            &comps.point,
+                            &comps.eyev,
+                            &comps.normalv,
+                            in_shadow,
+                        )
+                }
+            };
+            let reflected = self.reflected_color(w, comps, remaining - 1);
+            let refracted = self.refracted_color(w, comps, remaining - 1);
+
+            if material.reflective > 0. && material.transparency > 0. {
+                let reflectance = world::schlick(comps);
+                return surface + reflected * reflectance + refracted * (1. - reflectance);
+            }
+            surface + reflected + refracted
+        } else {
+            BLACK
+        }
+    }
+
+    pub fn reflected_color(&self, w: &World, comps: &Comps, remaining: u8) -> Color {
+        let reflective = channel_at(comps, Channel::Reflective);
+        if reflective == 0. || remaining == 0 {
+            return BLACK;
+        }
+        let reflect_ray = Ray::new(&comps.over_point, &comps.reflectv);
+        let color = self.trace(w, &reflect_ray, remaining);
+        color * reflective
+    }
+
+    pub fn refracted_color(&self, w: &World, comps: &Comps, remaining: u8) -> Color {
+        let transparency = channel_at(comps, Channel::Transparency);
+        if transparency == 0. || remaining == 0 {
+            return BLACK;
+        }
+
+        // Total internal reflection gives no refracted ray
+        let direction = match comps.refracted_direction() {
+            Some(direction) => direction,
+            None => return BLACK,
+        };
+
+        // Create the refracted ray
+        let refracted_ray = Ray::new(&comps.under_point, &direction);
+
+        // Find the color at the refracted ray
+        // making sure to multiply by the transparency value to account for any opacity
+        self.trace(w, &refracted_ray, remaining - 1) * transparency
+    }
+}
+
+/// The value of a channel of the material at the hit point.
+fn channel_at(comps: &Comps, channel: Channel) -> f64 {
+    comps
+        .object
+        .material()
+        .channel_at(channel, &comps.object, &comps.point, &comps.footprint)
+}
+
+impl Default for Whitted {
+    fn default() -> Self {
+        Whitted::new(5)
+    }
+}
+
+impl Integrator for Whitted {
+    fn color_at(&self, w: &World, r: &Ray) -> Color {
+        self.trace(w, r, self.max_depth)
+    }
+}
+
+/// Debug integrator showing the world space normal of the surfaces,
+/// with each component mapped from [-1, 1] to [0, 1].
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn color_at(&self, w: &World, r: &Ray) -> Color {
+        let i = w.intersect(r);
+        match i.hit() {
+            None => BLACK,
+            Some(h) => {
+                let comps = Comps::prepare_computations(h, r, Some(i.clone()));
+                let n = comps.normalv;
+                Color::new((n.x + 1.) / 2., (n.y + 1.) / 2., (n.z + 1.) / 2.)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transformation::view_transform;
+    use crate::tuple::Tuple;
+
+    struct Constant(Color);
+
+    impl Integrator for Constant {
+        fn color_at(&self, _w: &World, _r: &Ray) -> Color {
+            self.0
+        }
+    }
+
+    fn camera() -> Camera {
+        let from = Tuple::point(0., 0., -5.);
+        let to = Tuple::point(0., 0., 0.);
+        let up = Tuple::vector(0., 1., 0.);
+        let t = view_transform(&from, &to, &up);
+        Camera::new(11, 11, std::f64::consts::FRAC_PI_2, Some(t))
+    }
+
+    #[test]
+    fn the_whitted_integrator_matches_the_world() {
+        let w = World::default();
+        let r = Ray::new(&Tuple::point(0., 0., -5.), &Tuple::vector(0., 0., 1.));
+
+        let c = Whitted::default().color_at(&w, &r);
+        assert_eq!(Color::new(0.38066, 0.47583, 0.2855), c);
+    }
+
+    #[test]
+    fn the_default_integrator_reproduces_the_render() {
+        let w = World::default();
+        let c = camera();
+
+        let image = c.render(&w);
+        let whitted = c.render_with(&w, &Whitted::default());
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let (a, b) = (image.pixel_at(x, y), whitted.pixel_at(x, y));
+                assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+            }
+        }
+    }
+
+    #[test]
+    fn the_normals_integrator_shows_the_surface_normal() {
+        let w = World::default();
+        let r = Ray::new(&Tuple::point(0., 0., -5.), &Tuple::vector(0., 0., 1.));
+
+        assert_eq!(Color::new(0.5, 0.5, 0.), Normals.color_at(&w, &r));
+
+        let r = Ray::new(&Tuple::point(0., 0., -5.), &Tuple::vector(0., 1., 0.));
+        assert_eq!(BLACK, Normals.color_at(&w, &r));
+    }
+
+    #[test]
+    fn rendering_with_a_custom_integrator() {
+        let w = World::default();
+        let color = Color::new(0.1, 0.2, 0.3);
+
+        let image = camera().render_with(&w, &Constant(color));
+        assert_eq!(color, image.pixel_at(0, 0));
+        assert_eq!(color, image.pixel_at(10, 10));
+    }
+}
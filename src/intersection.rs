@@ -17,7 +17,7 @@ impl Intersection {
 
 impl PartialEq for Intersection {
     fn eq(&self, other: &Self) -> bool {
-        self.t == other.t && &self.object == &other.object
+        self.t == other.t && self.object.eq(&other.object)
     }
 }
 
@@ -99,7 +99,7 @@ mod tests {
         let i1 = Intersection::new(t1, s.clone());
         let i2 = Intersection::new(t2, s);
 
-        let xs = vec![i1, i2];
+        let xs = [i1, i2];
         assert_eq!(2, xs.len());
         assert_eq!(t1, xs[0].t);
         assert_eq!(t2, xs[1].t);
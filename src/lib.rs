@@ -1,17 +1,32 @@
+pub mod ambient_occlusion;
+pub mod auxiliary;
 pub mod camera;
 pub mod canvas;
 pub mod color;
+pub mod compare;
+pub mod composite;
 pub mod comps;
+pub mod deflate;
+pub mod display;
+pub mod hdr;
+pub mod integrator;
 pub mod intersection;
 pub mod light;
 pub mod material;
 pub mod matrix;
+pub mod noise;
+pub mod path_tracer;
 pub mod pattern;
 pub mod plane;
+pub mod png;
+pub mod postprocess;
 pub mod ppm;
 pub mod ray;
+pub mod sampling;
 pub mod shape;
 pub mod sphere;
+pub mod terminal;
+pub mod texture;
 pub mod transform;
 pub mod transformation;
 pub mod tuple;
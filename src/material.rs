@@ -1,13 +1,19 @@
 use super::color::{BLACK, Color, WHITE};
 use super::light::PointLight;
-use super::pattern::PatternType;
+use super::matrix::Matrix;
+use super::noise::Noise;
+use super::pattern::BoxPattern;
+use super::ray::Footprint;
 use super::shape::BoxShape;
+use super::texture::NormalMap;
 use super::tuple::Tuple;
 
+use std::borrow::Cow;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Material {
     pub color: Color,
-    pub pattern: Option<PatternType>,
+    pub pattern: Option<BoxPattern>,
     pub ambient: f64,
     pub diffuse: f64,
     pub specular: f64,
@@ -15,12 +21,148 @@ pub struct Material {
     pub reflective: f64,
     pub transparency: f64,
     pub refractive_index: f64,
+    /// Patterns replacing the constant value of some channels, none by default
+    pub channel_maps: Vec<ChannelMap>,
+    /// Normals read from a texture instead of the geometric ones, none by default
+    pub normal_map: Option<NormalMap>,
+    /// Bumps tilting the shading normal, none by default
+    pub bump: Option<BumpMap>,
+}
+
+/// The scalar properties of a material that can vary over the surface.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    Ambient,
+    Diffuse,
+    Specular,
+    Shininess,
+    Reflective,
+    Transparency,
+}
+
+/// A pattern driving a channel of a material: the brightness of the pattern, from
+/// black to white, maps linearly to a value from `min` to `max`.
+#[derive(Debug, Clone)]
+pub struct ChannelMap {
+    pub channel: Channel,
+    pub pattern: BoxPattern,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ChannelMap {
+    pub fn new(channel: Channel, pattern: BoxPattern, min: f64, max: f64) -> ChannelMap {
+        ChannelMap {
+            channel,
+            pattern,
+            min,
+            max,
+        }
+    }
+
+    /// The value of the channel at a world point, on the given shape.
+    pub fn value_at(&self, object: &BoxShape, point: &Tuple, footprint: &Footprint) -> f64 {
+        let color = if footprint.is_null() {
+            self.pattern.pattern_at_shape(object, point)
+        } else {
+            self.pattern
+                .filtered_pattern_at_shape(object, point, footprint)
+        };
+        let brightness = (color.r + color.g + color.b) / 3.;
+        self.min + (self.max - self.min) * brightness
+    }
 }
 
+impl PartialEq for ChannelMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.channel == other.channel
+            && self.pattern.eq(&other.pattern)
+            && self.min == other.min
+            && self.max == other.max
+    }
+}
+
+/// Where a bump map takes its heights from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Height {
+    /// The brightness of a pattern, from 0 for black to 1 for white
+    Pattern(BoxPattern),
+    /// Fractal noise in object space, with its own transformation
+    Noise {
+        noise: Noise,
+        octaves: u32,
+        transform: Matrix,
+    },
+}
+
+/// Bumps on a surface, faked by tilting the shading normal against the slope of a
+/// height field. The geometry, and so the silhouette and the shadows, do not change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BumpMap {
+    pub height: Height,
+    /// How much the normal tilts for a given slope: negative values give dents
+    pub scale: f64,
+}
+
+impl BumpMap {
+    pub fn new(height: Height, scale: f64) -> BumpMap {
+        BumpMap { height, scale }
+    }
+
+    /// The height at a world point, on the given shape.
+    pub fn height_at(&self, object: &BoxShape, point: &Tuple) -> f64 {
+        self.local_height_at(&(self.to_local(object) * *point))
+    }
+
+    /// The transformation from world space to the space of the pattern or of the noise.
+    fn to_local(&self, object: &BoxShape) -> Matrix {
+        let height_transform = match &self.height {
+            Height::Pattern(pattern) => pattern.transformation(),
+            Height::Noise { transform, .. } => *transform,
+        };
+        height_transform.inverse() * &object.transformation().inverse()
+    }
+
+    fn local_height_at(&self, local_point: &Tuple) -> f64 {
+        match &self.height {
+            Height::Pattern(pattern) => {
+                let color = pattern.pattern_at(local_point);
+                (color.r + color.g + color.b) / 3.
+            }
+            Height::Noise { noise, octaves, .. } => noise.fbm(local_point, *octaves),
+        }
+    }
+
+    /// The normal at a world point, tilted by the slope of the heights along the surface.
+    pub fn perturb(&self, object: &BoxShape, point: &Tuple, normal: &Tuple) -> Tuple {
+        let to_local = self.to_local(object);
+        let height_at = |p: Tuple| self.local_height_at(&(to_local * p));
+
+        // Central differences along the world axes
+        let slope = |axis: Tuple| {
+            (height_at(*point + axis * BUMP_DELTA) - height_at(*point - axis * BUMP_DELTA))
+                / (2. * BUMP_DELTA)
+        };
+        let gradient = Tuple::vector(
+            slope(Tuple::vector(1., 0., 0.)),
+            slope(Tuple::vector(0., 1., 0.)),
+            slope(Tuple::vector(0., 0., 1.)),
+        );
+
+        // Only the change of height along the surface tilts the normal
+        let tangential = gradient - *normal * Tuple::dot_product(&gradient, normal);
+        (*normal - tangential * self.scale).normalize()
+    }
+}
+
+/// The distance between the samples of the heights of a bump map.
+const BUMP_DELTA: f64 = 1e-4;
+
 impl Material {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         color: Option<Color>,
-        pattern: Option<PatternType>,
+        pattern: Option<BoxPattern>,
         ambient: Option<f64>,
         diffuse: Option<f64>,
         specular: Option<f64>,
@@ -39,54 +181,237 @@ impl Material {
             reflective: reflective.unwrap_or(0.),
             transparency: transparency.unwrap_or(0.),
             refractive_index: refractive_index.unwrap_or(1.0),
+            channel_maps: Vec::new(),
+            normal_map: None,
+            bump: None,
+        }
+    }
+
+    /// Drives a channel with a pattern, replacing its constant value.
+    pub fn with_channel_map(mut self, map: ChannelMap) -> Material {
+        self.channel_maps.retain(|m| m.channel != map.channel);
+        self.channel_maps.push(map);
+        self
+    }
+
+    /// Tilts the shading normal with a bump map.
+    pub fn with_bump(mut self, bump: BumpMap) -> Material {
+        self.bump = Some(bump);
+        self
+    }
+
+    /// Replaces the geometric normals with the normals of a texture.
+    pub fn with_normal_map(mut self, normal_map: NormalMap) -> Material {
+        self.normal_map = Some(normal_map);
+        self
+    }
+
+    /// The shading normal at a world point: the geometric normal, replaced by the normal
+    /// map and then perturbed by the bump map when there are ones.
+    pub fn normal_at(&self, object: &BoxShape, point: &Tuple, normal: &Tuple) -> Tuple {
+        let normal = match &self.normal_map {
+            Some(normal_map) => normal_map.normal_at(object, point, normal),
+            None => *normal,
+        };
+        match &self.bump {
+            Some(bump) => bump.perturb(object, point, &normal),
+            None => normal,
+        }
+    }
+
+    /// The constant value of a channel.
+    pub fn channel(&self, channel: Channel) -> f64 {
+        match channel {
+            Channel::Ambient => self.ambient,
+            Channel::Diffuse => self.diffuse,
+            Channel::Specular => self.specular,
+            Channel::Shininess => self.shininess,
+            Channel::Reflective => self.reflective,
+            Channel::Transparency => self.transparency,
+        }
+    }
+
+    fn channel_mut(&mut self, channel: Channel) -> &mut f64 {
+        match channel {
+            Channel::Ambient => &mut self.ambient,
+            Channel::Diffuse => &mut self.diffuse,
+            Channel::Specular => &mut self.specular,
+            Channel::Shininess => &mut self.shininess,
+            Channel::Reflective => &mut self.reflective,
+            Channel::Transparency => &mut self.transparency,
+        }
+    }
+
+    /// The value of a channel at a world point, from its map or its constant value.
+    pub fn channel_at(
+        &self,
+        channel: Channel,
+        object: &BoxShape,
+        point: &Tuple,
+        footprint: &Footprint,
+    ) -> f64 {
+        match self.channel_maps.iter().find(|m| m.channel == channel) {
+            Some(map) => map.value_at(object, point, footprint),
+            None => self.channel(channel),
+        }
+    }
+
+    /// The material at a world point, with constant channels and color instead of patterns.
+    /// Without channel maps, the material itself is returned without any copy.
+    pub fn at(&self, object: &BoxShape, point: &Tuple, footprint: &Footprint) -> Cow<'_, Material> {
+        if self.channel_maps.is_empty() {
+            return Cow::Borrowed(self);
+        }
+
+        let mut material = Material {
+            color: self.filtered_color_at(object, point, footprint),
+            pattern: None,
+            channel_maps: Vec::new(),
+            normal_map: self.normal_map.clone(),
+            bump: self.bump.clone(),
+            ..*self
+        };
+        for map in &self.channel_maps {
+            *material.channel_mut(map.channel) = map.value_at(object, point, footprint);
+        }
+        Cow::Owned(material)
+    }
+
+    /// The surface color at a world point, taken from the pattern when there is one.
+    pub fn color_at(&self, object: &BoxShape, point: &Tuple) -> Color {
+        match &self.pattern {
+            Some(p) => p.pattern_at_shape(object, point),
+            _ => self.color,
+        }
+    }
+
+    /// The surface color averaged over the footprint of a pixel, to avoid aliasing textures.
+    pub fn filtered_color_at(
+        &self,
+        object: &BoxShape,
+        point: &Tuple,
+        footprint: &Footprint,
+    ) -> Color {
+        match &self.pattern {
+            Some(p) if !footprint.is_null() => {
+                p.filtered_pattern_at_shape(object, point, footprint)
+            }
+            _ => self.color_at(object, point),
         }
     }
 
     pub fn lighting(
         &self,
         object: &BoxShape,
+        light: &PointLight,
+        point: &Tuple,
+        eyev: &Tuple,
+        normalv: &Tuple,
+        in_shadow: bool,
+    ) -> Color {
+        let material = self.at(object, point, &Footprint::default());
+        let color = material.color_at(object, point);
+        material.lighting_with_color(color, light, point, eyev, normalv, in_shadow)
+    }
+
+    /// Like `lighting`, with the surface color already known.
+    /// The channel maps are ignored, call it on the material returned by `at`.
+    pub fn lighting_with_color(
+        &self,
+        color: Color,
         &light: &PointLight,
         &point: &Tuple,
         &eyev: &Tuple,
         &normalv: &Tuple,
         in_shadow: bool,
     ) -> Color {
-        let color = match &self.pattern {
-            Some(p) => p.pattern_at_shape(object, &point),
-            _ => self.color,
-        };
         let effective_color = color * light.intensity;
-        let lightv = (light.position - point).normalize();
 
         let ambient = effective_color * self.ambient;
+        let (diffuse, specular) = if in_shadow {
+            (BLACK, BLACK)
+        } else {
+            self.diffuse_and_specular(effective_color, &light, &point, &eyev, &normalv)
+        };
+
+        ambient + diffuse + specular
+    }
+
+    /// The light received directly from the light source, without the ambient term.
+    pub fn direct_lighting(
+        &self,
+        object: &BoxShape,
+        light: &PointLight,
+        point: &Tuple,
+        eyev: &Tuple,
+        normalv: &Tuple,
+        in_shadow: bool,
+    ) -> Color {
+        if in_shadow {
+            return BLACK;
+        }
+
+        let material = self.at(object, point, &Footprint::default());
+        let color = material.color_at(object, point);
+        material.direct_lighting_with_color(color, light, point, eyev, normalv, in_shadow)
+    }
+
+    /// Like `direct_lighting`, with the surface color already known.
+    /// The channel maps are ignored, call it on the material returned by `at`.
+    pub fn direct_lighting_with_color(
+        &self,
+        color: Color,
+        &light: &PointLight,
+        &point: &Tuple,
+        &eyev: &Tuple,
+        &normalv: &Tuple,
+        in_shadow: bool,
+    ) -> Color {
+        if in_shadow {
+            return BLACK;
+        }
+
+        let effective_color = color * light.intensity;
+        let (diffuse, specular) =
+            self.diffuse_and_specular(effective_color, &light, &point, &eyev, &normalv);
+        diffuse + specular
+    }
+
+    fn diffuse_and_specular(
+        &self,
+        effective_color: Color,
+        &light: &PointLight,
+        &point: &Tuple,
+        &eyev: &Tuple,
+        &normalv: &Tuple,
+    ) -> (Color, Color) {
+        let lightv = (light.position - point).normalize();
 
         // light_dot_normal represents the cosine of the angle between
         // the light vector and the normal vector.
         // A negative number means the light is on the other side of the surface.
         let light_dot_normal = Tuple::dot_product(&lightv, &normalv);
 
-        let (diffuse, specular) = if light_dot_normal < 0. || in_shadow {
-            (BLACK, BLACK)
+        if light_dot_normal < 0. {
+            return (BLACK, BLACK);
+        }
+
+        let diffuse = effective_color * self.diffuse * light_dot_normal;
+
+        // reflect_dot_eye represents the cosing of the angle between
+        // the reflection vector and the eye vector.
+        // A negative number means the light reflects away from the eye.
+        let reflectv = Tuple::reflect(&(-lightv), &normalv);
+        let reflect_dot_eye = Tuple::dot_product(&reflectv, &eyev);
+
+        let specular = if reflect_dot_eye < 0. {
+            BLACK
         } else {
-            let diffuse = effective_color * self.diffuse * light_dot_normal;
-
-            // reflect_dot_eye represents the cosing of the angle between
-            // the reflection vector and the eye vector.
-            // A negative number means the light reflects away from the eye.
-            let reflectv = Tuple::reflect(&(-lightv), &normalv);
-            let reflect_dot_eye = Tuple::dot_product(&reflectv, &eyev);
-
-            let specular = if reflect_dot_eye < 0. {
-                BLACK
-            } else {
-                let factor = reflect_dot_eye.powf(self.shininess);
-                light.intensity * self.specular * factor
-            };
-
-            (diffuse, specular)
+            let factor = reflect_dot_eye.powf(self.shininess);
+            light.intensity * self.specular * factor
         };
 
-        ambient + diffuse + specular
+        (diffuse, specular)
     }
 }
 
@@ -98,15 +423,21 @@ impl Default for Material {
 
 #[cfg(test)]
 mod tests {
+    use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
     use super::*;
     use crate::{
+        color::RED,
         comps::Comps,
+        integrator::Whitted,
         intersection::{Intersection, Intersections},
         matrix::{MATRIX_IDENTITY, Matrix},
         pattern,
+        plane::Plane,
         ray::Ray,
         sphere::Sphere,
-        utils::EPSILON,
+        utils::{EPSILON, approx_eq},
+        world::World,
     };
 
     fn glass_sphere(transform: Option<Matrix>, refractive_index: Option<f64>) -> BoxShape {
@@ -284,15 +615,17 @@ mod tests {
 
     #[test]
     fn lighting_with_a_pattern_applied() {
-        let mut m = Material::default();
-        m.pattern = Some(PatternType::Stripe(pattern::StripePattern {
-            first: WHITE,
-            second: BLACK,
-            transform: Matrix::default(),
-        }));
-        m.ambient = 1.;
-        m.diffuse = 0.;
-        m.specular = 0.;
+        let m = Material {
+            pattern: Some(Box::new(pattern::StripePattern {
+                first: WHITE.into(),
+                second: BLACK.into(),
+                transform: Matrix::default(),
+            })),
+            ambient: 1.,
+            diffuse: 0.,
+            specular: 0.,
+            ..Default::default()
+        };
 
         let eyev = Tuple::vector(0., 0., -1.);
         let normalv = Tuple::vector(0., 0., -1.);
@@ -324,6 +657,247 @@ mod tests {
         );
     }
 
+    /// Specular tiles alternating between 0.9 and 0, along x.
+    fn glossy_tiles() -> Material {
+        Material::default().with_channel_map(ChannelMap::new(
+            Channel::Specular,
+            pattern::StripePattern::new_boxed(WHITE, BLACK, None),
+            0.,
+            0.9,
+        ))
+    }
+
+    #[test]
+    fn constant_channels_are_used_as_they_are() {
+        let m = Material::default();
+        let s = Sphere::default_boxed();
+        let p = Tuple::point(0., 0., -1.);
+
+        assert!(matches!(
+            m.at(&s, &p, &Footprint::default()),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            0.9,
+            m.channel_at(Channel::Diffuse, &s, &p, &Footprint::default())
+        );
+    }
+
+    #[test]
+    fn a_pattern_driving_a_channel() {
+        let m = glossy_tiles();
+        let s = Sphere::default_boxed();
+        let specular_at = |x| {
+            m.channel_at(
+                Channel::Specular,
+                &s,
+                &Tuple::point(x, 0., 0.),
+                &Footprint::default(),
+            )
+        };
+
+        assert_eq!(0.9, specular_at(0.5));
+        assert_eq!(0., specular_at(1.5));
+        // Other channels keep their constant value
+        assert_eq!(
+            0.9,
+            m.channel_at(
+                Channel::Diffuse,
+                &s,
+                &Tuple::point(1.5, 0., 0.),
+                &Footprint::default()
+            )
+        );
+    }
+
+    #[test]
+    fn the_brightness_of_the_pattern_maps_to_the_range_of_the_channel() {
+        let grey = Color::new(0.2, 0.5, 0.8);
+        let m = Material::default().with_channel_map(ChannelMap::new(
+            Channel::Shininess,
+            pattern::StripePattern::new_boxed(grey, grey, None),
+            10.,
+            210.,
+        ));
+        let s = Sphere::default_boxed();
+
+        let resolved = m.at(&s, &Tuple::point(0., 0., 0.), &Footprint::default());
+        assert!(approx_eq(110., resolved.shininess));
+        assert!(resolved.channel_maps.is_empty());
+        assert_eq!(m.diffuse, resolved.diffuse);
+    }
+
+    #[test]
+    fn resolving_a_material_keeps_its_pattern_color() {
+        let m = Material {
+            pattern: Some(pattern::StripePattern::new_boxed(RED, BLACK, None)),
+            ..glossy_tiles()
+        };
+        let s = Sphere::default_boxed();
+        let p = Tuple::point(0.5, 0., 0.);
+
+        let resolved = m.at(&s, &p, &Footprint::default());
+        assert_eq!(RED, resolved.color_at(&s, &p));
+    }
+
+    #[test]
+    fn a_channel_can_only_have_one_map() {
+        let m = glossy_tiles().with_channel_map(ChannelMap::new(
+            Channel::Specular,
+            pattern::StripePattern::new_boxed(BLACK, BLACK, None),
+            0.,
+            1.,
+        ));
+        let s = Sphere::default_boxed();
+
+        assert_eq!(1, m.channel_maps.len());
+        assert_eq!(
+            0.,
+            m.channel_at(
+                Channel::Specular,
+                &s,
+                &Tuple::point(0.5, 0., 0.),
+                &Footprint::default()
+            )
+        );
+    }
+
+    #[test]
+    fn lighting_with_a_specular_map() {
+        let m = Material {
+            ambient: 0.,
+            diffuse: 0.,
+            ..glossy_tiles()
+        };
+        let s = Sphere::default_boxed();
+        let eyev = Tuple::vector(0., 0., -1.);
+        let normalv = Tuple::vector(0., 0., -1.);
+        // The light is right behind the eye, for the brightest highlight
+        let lighting = |x| {
+            let light = PointLight::new(&Tuple::point(x, 0., -10.), &WHITE);
+            m.lighting(&s, &light, &Tuple::point(x, 0., 0.), &eyev, &normalv, false)
+        };
+
+        assert_eq!(Color::new(0.9, 0.9, 0.9), lighting(0.5));
+        assert_eq!(BLACK, lighting(1.5));
+    }
+
+    #[test]
+    fn a_material_without_bumps_keeps_the_normal() {
+        let s = Sphere::default_boxed();
+        let n = Tuple::vector(0., 1., 0.);
+
+        assert_eq!(n, s.material().normal_at(&s, &Tuple::point(0., 1., 0.), &n));
+    }
+
+    #[test]
+    fn a_bump_map_tilts_the_normal_against_the_slope() {
+        let bump = BumpMap::new(
+            Height::Pattern(pattern::GradientPattern::new_boxed(BLACK, WHITE, None)),
+            1.,
+        );
+        let floor = Plane::new_boxed(None, Some(Material::default().with_bump(bump)));
+        let n = Tuple::vector(0., 1., 0.);
+
+        // The height rises along x with a slope of 1
+        assert_eq!(
+            Tuple::vector(-FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.),
+            floor
+                .material()
+                .normal_at(&floor, &Tuple::point(0.5, 0., 0.3), &n)
+        );
+    }
+
+    #[test]
+    fn only_the_slope_along_the_surface_tilts_the_normal() {
+        // The height rises along y, across the floor
+        let bump = BumpMap::new(
+            Height::Pattern(pattern::GradientPattern::new_boxed(
+                BLACK,
+                WHITE,
+                Some(crate::transform::rotation_z(PI / 2.)),
+            )),
+            1.,
+        );
+        let n = Tuple::vector(0., 1., 0.);
+
+        assert_eq!(
+            n,
+            bump.perturb(
+                &Plane::new_boxed(None, None),
+                &Tuple::point(0.3, 0.5, 0.2),
+                &n
+            )
+        );
+    }
+
+    #[test]
+    fn bumps_from_noise() {
+        let bump = BumpMap::new(
+            Height::Noise {
+                noise: Noise::perlin(1),
+                octaves: 3,
+                transform: crate::transform::scaling(0.2, 0.2, 0.2),
+            },
+            0.2,
+        );
+        let s = Sphere::default_boxed();
+        let normals: Vec<(Tuple, Tuple)> = (0..50)
+            .map(|i| {
+                let angle = i as f64 * 0.125;
+                let point = Tuple::point(angle.cos(), 0., angle.sin());
+                let n = s.normal_at(&point);
+                (n, bump.perturb(&s, &point, &n))
+            })
+            .collect();
+
+        for (n, bumped) in &normals {
+            assert!(approx_eq(1., bumped.magnitude()));
+            assert!(Tuple::dot_product(n, bumped) > 0.);
+        }
+        assert!(normals.iter().any(|(n, bumped)| n != bumped));
+    }
+
+    #[test]
+    fn a_reflective_map_on_a_floor() {
+        let floor = Plane::new_boxed(
+            None,
+            Some(Material::default().with_channel_map(ChannelMap::new(
+                Channel::Reflective,
+                pattern::StripePattern::new_boxed(BLACK, WHITE, None),
+                0.,
+                0.5,
+            ))),
+        );
+        let w = World::new(
+            Some(PointLight::new(&Tuple::point(-10., 10., -10.), &WHITE)),
+            vec![
+                floor,
+                Sphere::new_boxed(
+                    Some(crate::transform::transforms(&[
+                        crate::transform::scaling(3., 3., 3.),
+                        crate::transform::translation(0., 5., 5.),
+                    ])),
+                    None,
+                ),
+            ],
+        );
+        let whitted = Whitted::default();
+        let reflected_at = |x: f64| {
+            let r = Ray::new(
+                &Tuple::point(x, 1., -0.5),
+                &Tuple::vector(0., -1., 1.).normalize(),
+            );
+            let xs = w.intersect(&r);
+            let comps = Comps::prepare_computations(xs.hit().unwrap(), &r, Some(xs.clone()));
+            whitted.reflected_color(&w, &comps, 5)
+        };
+
+        // The stripe at x in [0, 1) is matte, the next one is a mirror
+        assert_eq!(BLACK, reflected_at(0.5));
+        assert_ne!(BLACK, reflected_at(1.5));
+    }
+
     #[test]
     fn finding_n1_and_n2_at_various_intersections() {
         let s1 = glass_sphere(Some(crate::transform::scaling(2., 2., 2.)), Some(1.5));
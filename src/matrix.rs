@@ -105,7 +105,7 @@ impl Matrix {
 
     pub fn cofactor(&self, row: usize, column: usize) -> f64 {
         let minor = self.minor(row, column);
-        if (row + column) % 2 == 0 {
+        if (row + column).is_multiple_of(2) {
             return minor;
         }
         -minor
@@ -430,7 +430,7 @@ mod tests {
         let m = Matrix::new_with_values(4, 4, &values);
 
         assert_eq!(-2120., m.determinant());
-        assert_eq!(true, m.is_invertible());
+        assert!(m.is_invertible());
     }
 
     #[test]
@@ -441,7 +441,7 @@ mod tests {
         let m = Matrix::new_with_values(4, 4, &values);
 
         assert_eq!(0., m.determinant());
-        assert_eq!(false, m.is_invertible());
+        assert!(!m.is_invertible());
     }
 
     #[test]
@@ -451,7 +451,7 @@ mod tests {
         ];
         let m = Matrix::new_with_values(4, 4, &values);
 
-        assert_eq!(true, m.is_invertible());
+        assert!(m.is_invertible());
         let inverse = m.inverse();
 
         assert_eq!(532., m.determinant());
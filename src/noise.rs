@@ -0,0 +1,328 @@
+use super::sampling::Rng;
+use super::tuple::Tuple;
+
+// Seedable gradient noise for procedural patterns.
+// The permutation table is shuffled by the deterministic Rng, so the same seed gives
+// the same noise on every run and every platform.
+
+/// The gradient noise algorithms.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NoiseKind {
+    /// Ken Perlin's improved noise, on a grid of cubes
+    #[default]
+    Perlin,
+    /// Simplex noise, on a grid of tetrahedrons: cheaper and without axis-aligned artifacts
+    Simplex,
+}
+
+impl std::str::FromStr for NoiseKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "perlin" => Ok(NoiseKind::Perlin),
+            "simplex" => Ok(NoiseKind::Simplex),
+            _ => Err(format!("Unknown noise '{s}'")),
+        }
+    }
+}
+
+/// Gradient noise with values roughly in [-1, 1], and 0 at every integer coordinate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Noise {
+    pub kind: NoiseKind,
+    pub seed: u64,
+    permutation: Vec<u8>, // 256 values repeated twice, to avoid wrapping indices
+}
+
+impl Noise {
+    pub fn new(kind: NoiseKind, seed: u64) -> Noise {
+        let mut values: Vec<u8> = (0..=255).collect();
+        // Fisher-Yates shuffle
+        let mut rng = Rng::new(seed);
+        for i in (1..values.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            values.swap(i, j);
+        }
+
+        let mut permutation = values.clone();
+        permutation.extend(values);
+        Noise {
+            kind,
+            seed,
+            permutation,
+        }
+    }
+
+    pub fn perlin(seed: u64) -> Noise {
+        Noise::new(NoiseKind::Perlin, seed)
+    }
+
+    pub fn simplex(seed: u64) -> Noise {
+        Noise::new(NoiseKind::Simplex, seed)
+    }
+
+    pub fn noise(&self, point: &Tuple) -> f64 {
+        match self.kind {
+            NoiseKind::Perlin => self.perlin_at(point.x, point.y, point.z),
+            NoiseKind::Simplex => self.simplex_at(point.x, point.y, point.z),
+        }
+    }
+
+    /// Fractal Brownian motion: the sum of `octaves` layers of noise, each with twice the
+    /// frequency and half the amplitude of the previous one. Normalized to about [-1, 1].
+    pub fn fbm(&self, point: &Tuple, octaves: u32) -> f64 {
+        self.fractal(point, octaves, |n| n)
+    }
+
+    /// Like `fbm` but summing the absolute values, for billowing shapes. In about [0, 1].
+    pub fn turbulence(&self, point: &Tuple, octaves: u32) -> f64 {
+        self.fractal(point, octaves, f64::abs)
+    }
+
+    fn fractal(&self, point: &Tuple, octaves: u32, layer: impl Fn(f64) -> f64) -> f64 {
+        let (mut sum, mut total) = (0., 0.);
+        let (mut amplitude, mut frequency) = (1., 1.);
+        for _ in 0..octaves.max(1) {
+            let p = Tuple::point(
+                point.x * frequency,
+                point.y * frequency,
+                point.z * frequency,
+            );
+            sum += amplitude * layer(self.noise(&p));
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.;
+        }
+        sum / total
+    }
+
+    fn perlin_at(&self, x: f64, y: f64, z: f64) -> f64 {
+        let p = &self.permutation;
+        let (xi, yi, zi) = (cell(x), cell(y), cell(z));
+        let (x, y, z) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        // Hash the 8 corners of the cube containing the point
+        let a = p[xi] as usize + yi;
+        let (aa, ab) = (p[a] as usize + zi, p[a + 1] as usize + zi);
+        let b = p[xi + 1] as usize + yi;
+        let (ba, bb) = (p[b] as usize + zi, p[b + 1] as usize + zi);
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1., y, z)),
+                lerp(u, grad(p[ab], x, y - 1., z), grad(p[bb], x - 1., y - 1., z)),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(p[aa + 1], x, y, z - 1.),
+                    grad(p[ba + 1], x - 1., y, z - 1.),
+                ),
+                lerp(
+                    u,
+                    grad(p[ab + 1], x, y - 1., z - 1.),
+                    grad(p[bb + 1], x - 1., y - 1., z - 1.),
+                ),
+            ),
+        )
+    }
+
+    fn simplex_at(&self, x: f64, y: f64, z: f64) -> f64 {
+        const F3: f64 = 1. / 3.;
+        const G3: f64 = 1. / 6.;
+        let p = &self.permutation;
+
+        // Skew the space to find the cube, then unskew to get the offset from its origin
+        let s = (x + y + z) * F3;
+        let (i, j, k) = ((x + s).floor(), (y + s).floor(), (z + s).floor());
+        let t = (i + j + k) * G3;
+        let (x0, y0, z0) = (x - (i - t), y - (j - t), z - (k - t));
+
+        // The tetrahedron of the cube containing the point, from the order of the offsets
+        let (i1, j1, k1, i2, j2, k2) = if x0 >= y0 {
+            if y0 >= z0 {
+                (1, 0, 0, 1, 1, 0)
+            } else if x0 >= z0 {
+                (1, 0, 0, 1, 0, 1)
+            } else {
+                (0, 0, 1, 1, 0, 1)
+            }
+        } else if y0 < z0 {
+            (0, 0, 1, 0, 1, 1)
+        } else if x0 < z0 {
+            (0, 1, 0, 0, 1, 1)
+        } else {
+            (0, 1, 0, 1, 1, 0)
+        };
+
+        let corners = [
+            (0, 0, 0, x0, y0, z0),
+            (
+                i1,
+                j1,
+                k1,
+                x0 - i1 as f64 + G3,
+                y0 - j1 as f64 + G3,
+                z0 - k1 as f64 + G3,
+            ),
+            (
+                i2,
+                j2,
+                k2,
+                x0 - i2 as f64 + 2. * G3,
+                y0 - j2 as f64 + 2. * G3,
+                z0 - k2 as f64 + 2. * G3,
+            ),
+            (
+                1,
+                1,
+                1,
+                x0 - 1. + 3. * G3,
+                y0 - 1. + 3. * G3,
+                z0 - 1. + 3. * G3,
+            ),
+        ];
+
+        let (ii, jj, kk) = (cell(i), cell(j), cell(k));
+        let sum: f64 = corners
+            .iter()
+            .map(|&(ci, cj, ck, dx, dy, dz)| {
+                let falloff = 0.6 - dx * dx - dy * dy - dz * dz;
+                if falloff < 0. {
+                    0.
+                } else {
+                    let hash = p[ii + ci + p[jj + cj + p[kk + ck] as usize] as usize];
+                    falloff.powi(4) * grad(hash, dx, dy, dz)
+                }
+            })
+            .sum();
+
+        // Scales the result to about [-1, 1]
+        32. * sum
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise::perlin(0)
+    }
+}
+
+/// The index of a lattice cell in the permutation table.
+fn cell(v: f64) -> usize {
+    (v.floor() as i64 & 255) as usize
+}
+
+/// 6t^5 - 15t^4 + 10t^3, with null first and second derivatives at 0 and 1.
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Dot product of the offset with one of 12 gradient directions picked by the hash.
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..=3 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn along_x(noise: &Noise, sample: impl Fn(&Noise, &Tuple) -> f64) -> Vec<f64> {
+        (0..400)
+            .map(|i| sample(noise, &Tuple::point(0.05 + i as f64 * 0.01, 0.3, 0.7)))
+            .collect()
+    }
+
+    #[test]
+    fn noise_is_null_on_integer_coordinates() {
+        let perlin = Noise::perlin(1);
+        let simplex = Noise::simplex(1);
+
+        assert_eq!(0., perlin.noise(&Tuple::point(0., 0., 0.)));
+        assert_eq!(0., perlin.noise(&Tuple::point(3., -2., 7.)));
+        assert_eq!(0., simplex.noise(&Tuple::point(0., 0., 0.)));
+    }
+
+    #[test]
+    fn noise_is_deterministic_for_a_seed() {
+        let p = Tuple::point(1.3, 2.7, -0.4);
+
+        for kind in [NoiseKind::Perlin, NoiseKind::Simplex] {
+            let noise = Noise::new(kind, 5);
+            assert_eq!(noise.noise(&p), Noise::new(kind, 5).noise(&p));
+            assert_ne!(noise.noise(&p), Noise::new(kind, 6).noise(&p));
+            assert_eq!(noise, Noise::new(kind, 5));
+        }
+    }
+
+    #[test]
+    fn noise_is_identical_across_runs() {
+        let p = Tuple::point(1.3, 2.7, -0.4);
+
+        // Changing these values changes every procedural texture of existing scenes
+        assert!((Noise::perlin(42).noise(&p) - -0.2071213159521792).abs() < 1e-12);
+        assert!((Noise::simplex(42).noise(&p) - -0.476135413333333).abs() < 1e-12);
+    }
+
+    #[test]
+    fn noise_is_continuous_and_bounded() {
+        for noise in [Noise::perlin(0), Noise::simplex(0)] {
+            let values = along_x(&noise, Noise::noise);
+            for pair in values.windows(2) {
+                assert!((-1.1..=1.1).contains(&pair[1]));
+                assert!((pair[1] - pair[0]).abs() < 0.1);
+            }
+        }
+    }
+
+    #[test]
+    fn fractal_noise_is_normalized() {
+        let noise = Noise::default();
+
+        for value in along_x(&noise, |n, p| n.fbm(p, 6)) {
+            assert!((-1.1..=1.1).contains(&value));
+        }
+        for value in along_x(&noise, |n, p| n.turbulence(p, 6)) {
+            assert!((0. ..=1.1).contains(&value));
+        }
+    }
+
+    #[test]
+    fn a_single_octave_is_the_noise() {
+        let noise = Noise::simplex(3);
+        let p = Tuple::point(0.4, -1.2, 2.5);
+
+        assert_eq!(noise.noise(&p), noise.fbm(&p, 1));
+        assert_eq!(noise.noise(&p).abs(), noise.turbulence(&p, 1));
+    }
+
+    #[test]
+    fn more_octaves_add_detail() {
+        let noise = Noise::default();
+        let p = Tuple::point(0.4, -1.2, 2.5);
+
+        assert_ne!(noise.fbm(&p, 1), noise.fbm(&p, 4));
+    }
+
+    #[test]
+    fn parsing_noise_kinds() {
+        assert_eq!(Ok(NoiseKind::Simplex), "simplex".parse());
+        assert!("value".parse::<NoiseKind>().is_err());
+    }
+}
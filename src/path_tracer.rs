@@ -0,0 +1,319 @@
+use super::camera::Camera;
+use super::color::{BLACK, Color, WHITE};
+use super::comps::Comps;
+use super::integrator::Integrator;
+use super::ray::Ray;
+use super::sampling::{self, Rng};
+use super::world::{self, World};
+
+/// An unbiased Monte Carlo path tracer.
+///
+/// Each camera sample follows a single path through the scene. At every hit,
+/// the light received directly from the light source is added (next-event
+/// estimation), then the path continues in one direction chosen at random
+/// among a diffuse bounce, a mirror reflection and a refraction.
+/// Paths longer than `roulette_depth` are randomly terminated (Russian roulette).
+///
+/// Like `Material::lighting`, lights have no falloff with the distance, so the
+/// direct light matches the Whitted renderer and only the ambient term is
+/// replaced by the indirect light.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PathTracer {
+    pub samples: usize,
+    pub max_depth: u8,
+    pub roulette_depth: u8,
+    pub seed: u64,
+}
+
+impl PathTracer {
+    pub fn new(samples: usize) -> PathTracer {
+        PathTracer {
+            samples,
+            max_depth: 16,
+            roulette_depth: 3,
+            seed: 0,
+        }
+    }
+
+    /// The color seen along one random path starting with the given ray.
+    pub fn color_at(&self, w: &World, r: &Ray, rng: &mut Rng) -> Color {
+        self.trace(w, r, rng).0
+    }
+
+    /// The color seen along one random path, and whether its first ray hits an object.
+    fn trace(&self, w: &World, r: &Ray, rng: &mut Rng) -> (Color, bool) {
+        let mut color = BLACK;
+        let mut throughput = WHITE;
+        let mut ray = *r;
+        let mut hit = false;
+
+        for depth in 0..self.max_depth {
+            let xs = w.intersect(&ray);
+            let comps = match xs.hit() {
+                None => break,
+                Some(h) => Comps::prepare_computations(h, &ray, Some(xs.clone())),
+            };
+            hit = true;
+            let material =
+                comps
+                    .object
+                    .material()
+                    .at(&comps.object, &comps.point, &comps.footprint);
+
+            if let Some(light) = w.light {
+                let direct = material.direct_lighting(
+                    &comps.object,
+                    &light,
+                    &comps.over_point,
+                    &comps.eyev,
+                    &comps.normalv,
+                    w.is_shadowed(&comps.over_point),
+                );
+                color = color + throughput * direct;
+            }
+
+            // Weight of each way the path can continue, as in World::shade_hit
+            let albedo = material.color_at(&comps.object, &comps.point) * material.diffuse;
+            let refracted = comps.refracted_direction();
+            let (mut reflect_w, mut refract_w) = (
+                material.reflective,
+                if refracted.is_some() {
+                    material.transparency
+                } else {
+                    0.
+                },
+            );
+            if material.reflective > 0. && material.transparency > 0. {
+                let reflectance = world::schlick(&comps);
+                reflect_w *= reflectance;
+                refract_w *= 1. - reflectance;
+            }
+            let diffuse_w = max_component(albedo).max(0.);
+
+            let total = diffuse_w + reflect_w + refract_w;
+            if total <= 0. {
+                break;
+            }
+
+            // Pick one direction with a probability proportional to its weight
+            let choice = rng.next_f64() * total;
+            ray = if choice < diffuse_w {
+                throughput = throughput * albedo * (total / diffuse_w);
+                let direction = sampling::cosine_weighted_hemisphere(&comps.normalv, rng);
+                Ray::new(&comps.over_point, &direction)
+            } else if choice < diffuse_w + reflect_w {
+                throughput = throughput * total;
+                Ray::new(&comps.over_point, &comps.reflectv)
+            } else {
+                throughput = throughput * total;
+                Ray::new(&comps.under_point, &refracted.unwrap())
+            };
+
+            if depth + 1 >= self.roulette_depth {
+                let survival = max_component(throughput).min(0.95);
+                if survival <= 0. || rng.next_f64() >= survival {
+                    break;
+                }
+                throughput = throughput * (1. / survival);
+            }
+        }
+
+        (color, hit)
+    }
+}
+
+impl Integrator for PathTracer {
+    /// One path per call, always with the same random sequence.
+    /// Use `pixel_color` to average several paths.
+    fn color_at(&self, w: &World, r: &Ray) -> Color {
+        PathTracer::color_at(self, w, r, &mut Rng::new(self.seed))
+    }
+
+    /// The average color of `samples` paths through random points of the pixel.
+    fn pixel_color(&self, camera: &Camera, w: &World, px: usize, py: usize) -> Color {
+        self.pixel_color_and_coverage(camera, w, px, py).0
+    }
+
+    /// The average color of the paths and the fraction of them that hit an object.
+    fn pixel_color_and_coverage(
+        &self,
+        camera: &Camera,
+        w: &World,
+        px: usize,
+        py: usize,
+    ) -> (Color, f64) {
+        if self.samples == 0 {
+            return (BLACK, 0.);
+        }
+
+        let mut rng = Rng::for_pixel(self.seed, px, py);
+        let mut sum = BLACK;
+        let mut hits = 0;
+        for _ in 0..self.samples {
+            let x = px as f64 + rng.next_f64();
+            let y = py as f64 + rng.next_f64();
+            let r = camera.ray_for_point(x, y);
+            let (color, hit) = self.trace(w, &r, &mut rng);
+            sum = sum + color;
+            hits += hit as usize;
+        }
+        let samples = self.samples as f64;
+        (sum * (1. / samples), hits as f64 / samples)
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        PathTracer::new(64)
+    }
+}
+
+fn max_component(c: Color) -> f64 {
+    c.r.max(c.g).max(c.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::RED;
+    use crate::light::PointLight;
+    use crate::material::Material;
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+    use crate::transform;
+    use crate::transformation::view_transform;
+    use crate::tuple::Tuple;
+
+    #[test]
+    fn a_ray_that_misses_is_black() {
+        let w = World::default();
+        let r = Ray::new(&Tuple::point(0., 0., -5.), &Tuple::vector(0., 1., 0.));
+
+        let c = PathTracer::new(1).color_at(&w, &r, &mut Rng::new(0));
+        assert_eq!(BLACK, c);
+    }
+
+    #[test]
+    fn a_lone_diffuse_object_receives_only_direct_light() {
+        let light = PointLight::new(&Tuple::point(-10., 10., -10.), &WHITE);
+        let s = Sphere::default_boxed();
+        let w = World::new(Some(light), vec![s.clone()]);
+        let r = Ray::new(&Tuple::point(0., 0., -5.), &Tuple::vector(0., 0., 1.));
+
+        let c = PathTracer::new(1).color_at(&w, &r, &mut Rng::new(0));
+
+        let m = Material::default();
+        let p = Tuple::point(0., 0., -1.);
+        let n = Tuple::vector(0., 0., -1.);
+        assert_eq!(m.direct_lighting(&s, &light, &p, &n, &n, false), c);
+    }
+
+    #[test]
+    fn a_world_without_light_is_black() {
+        let w = World::new(None, World::default().shapes);
+        let r = Ray::new(&Tuple::point(0., 0., -5.), &Tuple::vector(0., 0., 1.));
+
+        let c = PathTracer::new(1).color_at(&w, &r, &mut Rng::new(0));
+        assert_eq!(BLACK, c);
+    }
+
+    #[test]
+    fn indirect_light_bleeds_color_between_surfaces() {
+        let light = PointLight::new(&Tuple::point(0., 10., 0.), &WHITE);
+        let red = Material {
+            color: RED,
+            specular: 0.,
+            ..Default::default()
+        };
+        let floor = Plane::new_boxed(None, Some(red));
+        let ball = Sphere::new_boxed(Some(transform::translation(0., 1.5, 0.)), None);
+        let w = World::new(Some(light), vec![floor, ball]);
+
+        // The underside of the ball only sees the floor, not the light
+        let r = Ray::new(&Tuple::point(0., 0.6, -5.), &Tuple::vector(0., 0., 1.));
+        let pt = PathTracer::new(1);
+        let mut rng = Rng::new(1);
+        let c = (0..64).fold(BLACK, |sum, _| sum + pt.color_at(&w, &r, &mut rng));
+
+        assert!(c.r > 0.01);
+        assert_eq!(0., c.g);
+        assert_eq!(0., c.b);
+    }
+
+    #[test]
+    fn paths_between_mutually_reflective_surfaces_terminate() {
+        let light = PointLight::new(&Tuple::point(0., 0., 0.), &WHITE);
+        let mirror = Material {
+            diffuse: 0.,
+            reflective: 1.,
+            ..Default::default()
+        };
+        let lower = Plane::new_boxed(
+            Some(transform::translation(0., -1., 0.)),
+            Some(mirror.clone()),
+        );
+        let upper = Plane::new_boxed(Some(transform::translation(0., 1., 0.)), Some(mirror));
+        let w = World::new(Some(light), vec![lower, upper]);
+
+        let r = Ray::new(&Tuple::point(0., 0., 0.), &Tuple::vector(0., 1., 0.));
+        let pt = PathTracer::new(1);
+        let c = pt.color_at(&w, &r, &mut Rng::new(0));
+
+        // Perfect mirrors keep the throughput at 1, only divided by the survival
+        // probability of 0.95 of the Russian roulette. Each of the at most `max_depth`
+        // hits adds at most the specular highlight of the white light.
+        let bound = pt.max_depth as f64 * 0.9 / 0.95f64.powi(pt.max_depth as i32);
+        for channel in [c.r, c.g, c.b] {
+            assert!(channel.is_finite());
+            assert!((0. ..=bound).contains(&channel));
+        }
+    }
+
+    #[test]
+    fn the_same_seed_renders_the_same_pixel() {
+        let c = Camera::new(5, 5, std::f64::consts::FRAC_PI_2, None);
+        let w = World::default();
+        let pt = PathTracer::new(4);
+
+        let a = pt.pixel_color(&c, &w, 2, 2);
+        let b = pt.pixel_color(&c, &w, 2, 2);
+        assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+    }
+
+    #[test]
+    fn the_coverage_of_pixels_on_the_edge_of_an_object() {
+        let from = Tuple::point(0., 0., -5.);
+        let t = view_transform(&from, &Tuple::point(0., 0., 0.), &Tuple::vector(0., 1., 0.));
+        let c = Camera::new(11, 11, std::f64::consts::FRAC_PI_2, Some(t));
+        let w = World::default();
+        let pt = PathTracer::new(32);
+        let coverage = |x, y| pt.pixel_color_and_coverage(&c, &w, x, y).1;
+
+        assert_eq!(1., coverage(5, 5));
+        assert_eq!(0., coverage(0, 0));
+        let edge = coverage(4, 5);
+        assert!(edge > 0. && edge < 1.);
+    }
+
+    #[test]
+    fn the_color_and_the_coverage_of_a_pixel_come_from_the_same_paths() {
+        let light = PointLight::new(&Tuple::point(0., 0., -10.), &WHITE);
+        let flat = Material {
+            specular: 0.,
+            ..Default::default()
+        };
+        let w = World::new(Some(light), vec![Sphere::new_boxed(None, Some(flat))]);
+        let from = Tuple::point(0., 0., -5.);
+        let t = view_transform(&from, &Tuple::point(0., 0., 0.), &Tuple::vector(0., 1., 0.));
+        let c = Camera::new(11, 11, std::f64::consts::FRAC_PI_2, Some(t));
+        let pt = PathTracer::new(16);
+
+        // Misses are black and hits at most as bright as the white light, so a color
+        // premultiplied by the coverage can never be brighter than it
+        for x in 0..11 {
+            let (color, coverage) = pt.pixel_color_and_coverage(&c, &w, x, 5);
+            assert_eq!(color, pt.pixel_color(&c, &w, x, 5));
+            assert!(max_component(color) <= coverage);
+        }
+    }
+}
@@ -1,156 +1,1213 @@
 use super::color::Color;
 use super::matrix::Matrix;
+use super::noise::Noise;
+use super::ray::Footprint;
+use super::sampling::Rng;
 use super::shape::BoxShape;
 use super::tuple::Tuple;
 
+use std::any::Any;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// A color that varies over the surface of a shape.
+///
+/// Implement this trait to define a new pattern, in this crate or in another one.
+/// A pattern only computes its color in its own space, `pattern_at_shape` handles the
+/// transformations of the shape and of the pattern.
+pub trait Pattern: fmt::Debug {
+    fn transformation(&self) -> Matrix;
+
+    /// The color at a point in pattern space.
+    fn pattern_at(&self, point: &Tuple) -> Color;
+
+    /// The color at a point in the space of the parent: a shape, or an enclosing pattern.
+    fn transformed_pattern_at(&self, point: &Tuple) -> Color {
+        self.pattern_at(&(self.transformation().inverse() * *point))
+    }
+
+    /// The color at a point in world space, on the given shape.
+    fn pattern_at_shape(&self, shape: &BoxShape, world_point: &Tuple) -> Color {
+        let object_point = shape.transformation().inverse() * *world_point;
+        self.transformed_pattern_at(&object_point)
+    }
+
+    /// The color averaged over the footprint of a pixel around a point in pattern space.
+    /// Image textures filter, patterns with nested paints pass the footprint on to them,
+    /// and the others give the color at the point.
+    fn filtered_pattern_at(&self, point: &Tuple, _footprint: &Footprint) -> Color {
+        self.pattern_at(point)
+    }
+
+    /// The filtered color at a point in world space, on the given shape.
+    fn filtered_pattern_at_shape(
+        &self,
+        shape: &BoxShape,
+        world_point: &Tuple,
+        footprint: &Footprint,
+    ) -> Color {
+        let to_object = shape.transformation().inverse();
+        let to_pattern = self.transformation().inverse();
+        let pattern_point = to_pattern * (to_object * *world_point);
+        let footprint = footprint.transform(to_object).transform(to_pattern);
+        self.filtered_pattern_at(&pattern_point, &footprint)
+    }
+
+    fn box_clone(&self) -> BoxPattern;
+    fn box_eq(&self, other: &dyn Any) -> bool;
+    fn as_any(&self) -> &dyn Any;
+}
+
+pub type BoxPattern = Box<dyn Pattern>;
+
+impl Clone for BoxPattern {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+impl PartialEq for BoxPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.box_eq(other.as_any())
+    }
+}
+
+/// What fills a slot of a pattern: a solid color or another pattern.
+///
+/// A nested pattern has its own transformation, applied on top of the one of the
+/// enclosing pattern.
 #[derive(Debug, Clone, PartialEq)]
-pub enum PatternType {
-    Checkers(CheckersPattern),
-    Gradient(GradientPattern),
-    Ring(RingPattern),
-    Stripe(StripePattern),
-    Test(TestPattern),
+pub enum Paint {
+    Solid(Color),
+    Pattern(BoxPattern),
 }
 
-impl PatternType {
-    pub fn pattern_at(&self, point: &Tuple) -> Color {
+impl Paint {
+    /// The color at a point in the space of the enclosing pattern.
+    pub fn color_at(&self, point: &Tuple) -> Color {
         match self {
-            PatternType::Checkers(p) => p.pattern_at(point),
-            PatternType::Gradient(p) => p.pattern_at(point),
-            PatternType::Ring(p) => p.pattern_at(point),
-            PatternType::Stripe(p) => p.pattern_at(point),
-            PatternType::Test(p) => p.pattern_at(point),
+            Paint::Solid(color) => *color,
+            Paint::Pattern(pattern) => pattern.transformed_pattern_at(point),
         }
     }
-    pub fn pattern_at_shape(&self, shape: &BoxShape, world_point: &Tuple) -> Color {
-        let object_point = shape.transformation().inverse() * *world_point;
 
+    /// The color averaged over the footprint of a pixel, in the space of the enclosing pattern.
+    pub fn filtered_color_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
         match self {
-            PatternType::Checkers(p) => {
-                let pattern_point = p.transform.inverse() * object_point;
-                p.pattern_at(&pattern_point)
-            }
-            PatternType::Gradient(p) => {
-                let pattern_point = p.transform.inverse() * object_point;
-                p.pattern_at(&pattern_point)
-            }
-            PatternType::Ring(p) => {
-                let pattern_point = p.transform.inverse() * object_point;
-                p.pattern_at(&pattern_point)
-            }
-            PatternType::Stripe(p) => {
-                let pattern_point = p.transform.inverse() * object_point;
-                p.pattern_at(&pattern_point)
-            }
-            PatternType::Test(p) => {
-                let pattern_point = p.transform.inverse() * object_point;
-                p.pattern_at(&pattern_point)
+            Paint::Pattern(pattern) if !footprint.is_null() => {
+                let to_pattern = pattern.transformation().inverse();
+                pattern
+                    .filtered_pattern_at(&(to_pattern * *point), &footprint.transform(to_pattern))
             }
+            _ => self.color_at(point),
+        }
+    }
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        Paint::Solid(color)
+    }
+}
+
+impl From<BoxPattern> for Paint {
+    fn from(pattern: BoxPattern) -> Self {
+        Paint::Pattern(pattern)
+    }
+}
+
+impl PartialEq<Color> for Paint {
+    fn eq(&self, other: &Color) -> bool {
+        matches!(self, Paint::Solid(color) if color == other)
+    }
+}
+
+/// Interpolates between two paints, `t` being the share of the second one.
+fn mix(first: &Paint, second: &Paint, t: f64, point: &Tuple, footprint: &Footprint) -> Color {
+    first.filtered_color_at(point, footprint) * (1. - t)
+        + second.filtered_color_at(point, footprint) * t
+}
+
+/// Mixes two paints, with `weight` being the share of the second one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlendPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub weight: f64,
+    pub transform: Matrix,
+}
+
+impl BlendPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        weight: f64,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            weight,
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    /// The average of two paints.
+    pub fn average(first: impl Into<Paint>, second: impl Into<Paint>) -> Self {
+        BlendPattern::new(first, second, 0.5, None)
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        weight: f64,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(BlendPattern::new(first, second, weight, transform))
+    }
+}
+
+impl Pattern for BlendPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        mix(&self.first, &self.second, self.weight, point, footprint)
+    }
+}
+
+/// Bricks in the xz plane, constant in y: rows of bricks along x, stacked along z.
+/// The first paint is the bricks and the second one the mortar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrickPattern {
+    pub first: Paint,
+    pub second: Paint,
+    /// The size of a brick along x, including the mortar
+    pub width: f64,
+    /// The size of a brick along z, including the mortar
+    pub depth: f64,
+    /// The thickness of the joints
+    pub mortar: f64,
+    /// The shift of every other row, as a share of the width: 0.5 for bricks, 0 for tiles
+    pub offset: f64,
+    pub transform: Matrix,
+}
+
+impl BrickPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            width: 1.,
+            depth: 0.5,
+            mortar: 0.05,
+            offset: 0.5,
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    /// Square tiles aligned in rows and columns.
+    pub fn tiles(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            depth: 1.,
+            offset: 0.,
+            ..BrickPattern::new(first, second, transform)
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(BrickPattern::new(first, second, transform))
+    }
+}
+
+impl Pattern for BrickPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        let row = (point.z / self.depth).floor();
+        let shift = if (row as i64).rem_euclid(2) == 1 {
+            self.offset
+        } else {
+            0.
+        };
+        let column = point.x / self.width + shift;
+
+        // Position in the brick, with the joints centered on its edges
+        let x = (column - column.floor()) * self.width;
+        let z = point.z - row * self.depth;
+        let half = self.mortar / 2.;
+        if x < half || x > self.width - half || z < half || z > self.depth - half {
+            self.second.filtered_color_at(point, footprint)
+        } else {
+            self.first.filtered_color_at(point, footprint)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckersPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub transform: Matrix,
+}
+
+impl CheckersPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(CheckersPattern::new(first, second, transform))
+    }
+}
+
+impl Pattern for CheckersPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        if ((point.x.floor().abs() + point.y.floor().abs() + point.z.floor().abs()) as u32)
+            .is_multiple_of(2)
+        {
+            self.first.filtered_color_at(point, footprint)
+        } else {
+            self.second.filtered_color_at(point, footprint)
+        }
+    }
+}
+
+/// Soft clouds: the first paint is the sky and the second one the clouds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudsPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub noise: Noise,
+    pub octaves: u32,
+    /// The share of the surface covered by clouds, from 0 to 1
+    pub coverage: f64,
+    pub transform: Matrix,
+}
+
+impl CloudsPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            noise: Noise::perlin(seed),
+            octaves: 5,
+            coverage: 0.5,
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(CloudsPattern::new(first, second, seed, transform))
+    }
+}
+
+impl Pattern for CloudsPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        // From a clear sky with no coverage to overcast with full coverage
+        let density = (self.noise.fbm(point, self.octaves) + 1.) / 2. + 2. * self.coverage - 1.;
+        mix(
+            &self.first,
+            &self.second,
+            density.clamp(0., 1.),
+            point,
+            footprint,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradientPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub transform: Matrix,
+}
+
+impl GradientPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(GradientPattern::new(first, second, transform))
+    }
+}
+
+impl Pattern for GradientPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        let first = self.first.filtered_color_at(point, footprint);
+        let second = self.second.filtered_color_at(point, footprint);
+
+        let fraction = point.x - point.x.floor();
+        first + (second - first) * fraction
+    }
+}
+
+/// Speckled granite: high frequency turbulence between the two paints.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GranitePattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub noise: Noise,
+    pub octaves: u32,
+    pub transform: Matrix,
+}
+
+impl GranitePattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            noise: Noise::perlin(seed),
+            octaves: 4,
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(GranitePattern::new(first, second, seed, transform))
+    }
+}
+
+impl Pattern for GranitePattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        // Four times the frequency of the other patterns, for small grains
+        let grains = Tuple::point(point.x * 4., point.y * 4., point.z * 4.);
+        let t = 2.5 * self.noise.turbulence(&grains, self.octaves);
+        mix(&self.first, &self.second, t.min(1.), point, footprint)
+    }
+}
+
+/// A tiling of hexagons in the xz plane, constant in y, with a radius of 1.
+/// Three paints are needed so that neighboring hexagons never share one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HexagonPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub third: Paint,
+    pub transform: Matrix,
+}
+
+impl HexagonPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        third: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            third: third.into(),
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        third: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(HexagonPattern::new(first, second, third, transform))
+    }
+}
+
+impl Pattern for HexagonPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        // Cube coordinates of the point, rounded to the center of its hexagon
+        let q = 3f64.sqrt() / 3. * point.x - point.z / 3.;
+        let r = 2. / 3. * point.z;
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+
+        // Stepping to any neighbor changes q - r by 1 or 2
+        match ((rq - rr) as i64).rem_euclid(3) {
+            0 => self.first.filtered_color_at(point, footprint),
+            1 => self.second.filtered_color_at(point, footprint),
+            _ => self.third.filtered_color_at(point, footprint),
+        }
+    }
+}
+
+/// Marble: veins along x, bent by turbulence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarblePattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub noise: Noise,
+    pub octaves: u32,
+    /// How much the veins are bent
+    pub turbulence: f64,
+    pub transform: Matrix,
+}
+
+impl MarblePattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            noise: Noise::perlin(seed),
+            octaves: 4,
+            turbulence: 5.,
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(MarblePattern::new(first, second, seed, transform))
+    }
+}
+
+impl Pattern for MarblePattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        let phase = point.x * PI + self.turbulence * self.noise.turbulence(point, self.octaves);
+        mix(
+            &self.first,
+            &self.second,
+            (phase.sin() + 1.) / 2.,
+            point,
+            footprint,
+        )
+    }
+}
+
+/// Jitters the lookup point of a paint with noise, to break up straight edges.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerturbPattern {
+    pub paint: Paint,
+    /// The largest offset of the lookup point along each axis
+    pub scale: f64,
+    pub noise: Noise,
+    pub transform: Matrix,
+}
+
+impl PerturbPattern {
+    pub fn new(paint: impl Into<Paint>, scale: f64, seed: u64, transform: Option<Matrix>) -> Self {
+        Self {
+            paint: paint.into(),
+            scale,
+            noise: Noise::perlin(seed),
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        paint: impl Into<Paint>,
+        scale: f64,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(PerturbPattern::new(paint, scale, seed, transform))
+    }
+}
+
+impl Pattern for PerturbPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        // Offset lookups give three uncorrelated noise values
+        let offset = |dx: f64, dy: f64, dz: f64| {
+            self.noise
+                .noise(&Tuple::point(point.x + dx, point.y + dy, point.z + dz))
+                * self.scale
+        };
+        let perturbed = Tuple::point(
+            point.x + offset(0., 0., 0.),
+            point.y + offset(31.7, 17.3, 5.9),
+            point.z + offset(-11.1, 43.9, 23.3),
+        );
+        self.paint.filtered_color_at(&perturbed, footprint)
+    }
+}
+
+/// Dots of the first paint on a background of the second one.
+/// The dots are balls centered on every integer point, so they show as disks on the
+/// planes through integer coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolkaDotPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub radius: f64,
+    pub transform: Matrix,
+}
+
+impl PolkaDotPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            radius: 0.25,
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(PolkaDotPattern::new(first, second, transform))
+    }
+}
+
+impl Pattern for PolkaDotPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        let center = Tuple::point(point.x.round(), point.y.round(), point.z.round());
+        if (*point - center).magnitude() < self.radius {
+            self.first.filtered_color_at(point, footprint)
+        } else {
+            self.second.filtered_color_at(point, footprint)
+        }
+    }
+}
+
+/// Like `GradientPattern`, but repeating with the distance to the y axis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RadialGradientPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub transform: Matrix,
+}
+
+impl RadialGradientPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(RadialGradientPattern::new(first, second, transform))
+    }
+}
+
+impl Pattern for RadialGradientPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        let distance = (point.x.powi(2) + point.z.powi(2)).sqrt();
+        mix(
+            &self.first,
+            &self.second,
+            distance - distance.floor(),
+            point,
+            footprint,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RingPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub transform: Matrix,
+}
+
+impl RingPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(RingPattern::new(first, second, transform))
+    }
+}
+
+impl Pattern for RingPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        if ((point.x.powi(2) + point.z.powi(2)).sqrt().floor().abs() as u32).is_multiple_of(2) {
+            self.first.filtered_color_at(point, footprint)
+        } else {
+            self.second.filtered_color_at(point, footprint)
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct CheckersPattern {
-    pub first: Color,
-    pub second: Color,
+#[derive(Debug, Clone, PartialEq)]
+pub struct StripePattern {
+    pub first: Paint,
+    pub second: Paint,
     pub transform: Matrix,
 }
 
-impl CheckersPattern {
-    pub fn new(first: Color, second: Color, transform: Option<Matrix>) -> Self {
+impl StripePattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> Self {
         Self {
-            first,
-            second,
+            first: first.into(),
+            second: second.into(),
             transform: transform.unwrap_or_default(),
         }
     }
 
-    pub fn pattern_at(&self, point: &Tuple) -> Color {
-        if (point.x.floor().abs() + point.y.floor().abs() + point.z.floor().abs()) as u32 % 2 == 0 {
-            self.first
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(StripePattern::new(first, second, transform))
+    }
+}
+
+impl Pattern for StripePattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        if (point.x.floor().abs() as u32).is_multiple_of(2) {
+            self.first.filtered_color_at(point, footprint)
         } else {
-            self.second
+            self.second.filtered_color_at(point, footprint)
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct GradientPattern {
-    pub first: Color,
-    pub second: Color,
+/// Wood grain: rings around the y axis, like `RingPattern`, distorted by noise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WoodPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub noise: Noise,
+    pub octaves: u32,
+    /// How much the rings are distorted
+    pub turbulence: f64,
     pub transform: Matrix,
 }
 
-impl GradientPattern {
-    pub fn new(first: Color, second: Color, transform: Option<Matrix>) -> Self {
+impl WoodPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> Self {
         Self {
-            first,
-            second,
+            first: first.into(),
+            second: second.into(),
+            noise: Noise::perlin(seed),
+            octaves: 2,
+            turbulence: 0.1,
             transform: transform.unwrap_or_default(),
         }
     }
 
-    pub fn pattern_at(&self, point: &Tuple) -> Color {
-        let distance_r = self.second.r - self.first.r;
-        let distance_g = self.second.g - self.first.g;
-        let distance_b = self.second.b - self.first.b;
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(WoodPattern::new(first, second, seed, transform))
+    }
+}
+
+impl Pattern for WoodPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 
-        let fraction = point.x - point.x.floor();
-        Color {
-            r: self.first.r + (distance_r * fraction),
-            g: self.first.g + (distance_g * fraction),
-            b: self.first.b + (distance_b * fraction),
-        }
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        let radius = (point.x.powi(2) + point.z.powi(2)).sqrt()
+            + self.turbulence * self.noise.fbm(point, self.octaves);
+        // Light early wood fading into a darker late wood in each ring
+        let t = radius - radius.floor();
+        mix(&self.first, &self.second, t * t, point, footprint)
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct RingPattern {
-    pub first: Color,
-    pub second: Color,
-    pub transform: Matrix,
+/// What a `WorleyPattern` shows of its cells.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum WorleyFeature {
+    /// The distance to the nearest feature point: round cells growing to the second paint
+    #[default]
+    Distance,
+    /// The difference between the two nearest distances: the second paint along the borders
+    Edges,
+    /// Flat Voronoi cells, each one with either paint
+    Cells,
 }
 
-impl RingPattern {
-    pub fn new(first: Color, second: Color, transform: Option<Matrix>) -> Self {
-        Self {
-            first,
-            second,
-            transform: transform.unwrap_or_default(),
-        }
-    }
+impl std::str::FromStr for WorleyFeature {
+    type Err = String;
 
-    pub fn pattern_at(&self, point: &Tuple) -> Color {
-        if (point.x.powi(2) + point.z.powi(2)).sqrt().floor().abs() as u32 % 2 == 0 {
-            self.first
-        } else {
-            self.second
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "distance" => Ok(WorleyFeature::Distance),
+            "edges" => Ok(WorleyFeature::Edges),
+            "cells" | "voronoi" => Ok(WorleyFeature::Cells),
+            _ => Err(format!("Unknown Worley feature '{s}'")),
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct StripePattern {
-    pub first: Color,
-    pub second: Color,
+/// Worley cellular noise: every unit cube holds a feature point at a random position,
+/// and space is split into the cells of the nearest feature points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorleyPattern {
+    pub first: Paint,
+    pub second: Paint,
+    pub seed: u64,
+    pub feature: WorleyFeature,
     pub transform: Matrix,
 }
 
-impl StripePattern {
-    pub fn new(first: Color, second: Color, transform: Option<Matrix>) -> Self {
+impl WorleyPattern {
+    pub fn new(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> Self {
         Self {
-            first,
-            second,
+            first: first.into(),
+            second: second.into(),
+            seed,
+            feature: WorleyFeature::default(),
             transform: transform.unwrap_or_default(),
         }
     }
 
-    pub fn pattern_at(&self, point: &Tuple) -> Color {
-        if point.x.floor().abs() as u32 % 2 == 0 {
-            self.first
-        } else {
-            self.second
+    pub fn new_boxed(
+        first: impl Into<Paint>,
+        second: impl Into<Paint>,
+        seed: u64,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(WorleyPattern::new(first, second, seed, transform))
+    }
+
+    /// The feature point of the cube at the given integer coordinates, and a random
+    /// number in [0, 1) to pick the paint of its cell.
+    fn feature_point(&self, cube: &Tuple) -> (Tuple, f64) {
+        let mut rng = Rng::for_point(self.seed, cube);
+        let offset = Tuple::vector(rng.next_f64(), rng.next_f64(), rng.next_f64());
+        (*cube + offset, rng.next_f64())
+    }
+}
+
+impl Pattern for WorleyPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        self.filtered_pattern_at(point, &Footprint::default())
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        // The nearest feature points are in the cube of the point or in its neighbors
+        let (mut nearest, mut second_nearest, mut pick) = (f64::INFINITY, f64::INFINITY, 0.);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let cube = Tuple::point(
+                        point.x.floor() + dx as f64,
+                        point.y.floor() + dy as f64,
+                        point.z.floor() + dz as f64,
+                    );
+                    let (feature, value) = self.feature_point(&cube);
+                    let distance = (feature - *point).magnitude();
+                    if distance < nearest {
+                        second_nearest = nearest;
+                        nearest = distance;
+                        pick = value;
+                    } else if distance < second_nearest {
+                        second_nearest = distance;
+                    }
+                }
+            }
+        }
+
+        match self.feature {
+            WorleyFeature::Distance => {
+                mix(&self.first, &self.second, nearest.min(1.), point, footprint)
+            }
+            WorleyFeature::Edges => mix(
+                &self.first,
+                &self.second,
+                1. - (second_nearest - nearest).min(1.),
+                point,
+                footprint,
+            ),
+            WorleyFeature::Cells if pick < 0.5 => self.first.filtered_color_at(point, footprint),
+            WorleyFeature::Cells => self.second.filtered_color_at(point, footprint),
         }
     }
 }
@@ -167,7 +1224,29 @@ impl TestPattern {
         }
     }
 
-    pub fn pattern_at(&self, point: &Tuple) -> Color {
+    pub fn new_boxed(transform: Option<Matrix>) -> BoxPattern {
+        Box::new(TestPattern::new(transform))
+    }
+}
+
+impl Pattern for TestPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(*self)
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
         Color::new(point.x, point.y, point.z)
     }
 }
@@ -175,9 +1254,9 @@ impl TestPattern {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::color::{BLACK, WHITE};
+    use crate::color::{BLACK, BLUE, GREEN, RED, WHITE};
+    use crate::material::Material;
     use crate::matrix::MATRIX_IDENTITY;
-    use crate::pattern::PatternType;
     use crate::sphere::Sphere;
     use crate::transform;
 
@@ -199,7 +1278,7 @@ mod tests {
         let s = Sphere::new_boxed(Some(transform::scaling(2., 2., 2.)), None);
         assert_eq!(
             Color::new(1., 1.5, 2.),
-            PatternType::Test(p).pattern_at_shape(&s, &Tuple::point(2., 3., 4.))
+            p.pattern_at_shape(&s, &Tuple::point(2., 3., 4.))
         );
     }
 
@@ -209,7 +1288,7 @@ mod tests {
         let s = Sphere::new_boxed(None, None);
         assert_eq!(
             Color::new(1., 1.5, 2.),
-            PatternType::Test(p).pattern_at_shape(&s, &Tuple::point(2., 3., 4.))
+            p.pattern_at_shape(&s, &Tuple::point(2., 3., 4.))
         );
     }
 
@@ -219,10 +1298,450 @@ mod tests {
         let s = Sphere::new_boxed(Some(transform::scaling(2., 2., 2.)), None);
         assert_eq!(
             Color::new(0.75, 0.5, 0.25),
-            PatternType::Test(p).pattern_at_shape(&s, &Tuple::point(2.5, 3., 3.5))
+            p.pattern_at_shape(&s, &Tuple::point(2.5, 3., 3.5))
+        );
+    }
+
+    /// A pattern defined outside of this module, like a downstream crate would.
+    #[derive(Debug, Clone, PartialEq)]
+    struct HeightPattern {
+        transform: Matrix,
+    }
+
+    impl Pattern for HeightPattern {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn box_eq(&self, other: &dyn Any) -> bool {
+            other.downcast_ref::<Self>() == Some(self)
+        }
+
+        fn box_clone(&self) -> BoxPattern {
+            Box::new(self.clone())
+        }
+
+        fn transformation(&self) -> Matrix {
+            self.transform
+        }
+
+        fn pattern_at(&self, point: &Tuple) -> Color {
+            if point.y > 0. { WHITE } else { BLACK }
+        }
+    }
+
+    #[test]
+    fn a_custom_pattern_on_a_material() {
+        let p = HeightPattern {
+            transform: transform::translation(0., 1., 0.),
+        };
+        let m = Material {
+            pattern: Some(Box::new(p)),
+            ..Default::default()
+        };
+        let s = Sphere::new_boxed(Some(transform::scaling(2., 2., 2.)), Some(m.clone()));
+
+        assert_eq!(BLACK, m.color_at(&s, &Tuple::point(0., 1.5, 0.)));
+        assert_eq!(WHITE, m.color_at(&s, &Tuple::point(0., 2.5, 0.)));
+    }
+
+    #[test]
+    fn comparing_boxed_patterns() {
+        let a = StripePattern::new_boxed(WHITE, BLACK, None);
+
+        assert!(a.eq(&a.clone()));
+        assert!(!a.eq(&StripePattern::new_boxed(BLACK, WHITE, None)));
+        assert!(!a.eq(&CheckersPattern::new_boxed(WHITE, BLACK, None)));
+    }
+
+    #[test]
+    fn stripes_of_stripes() {
+        // Thin horizontal stripes inside every other vertical stripe
+        let inner = StripePattern::new_boxed(
+            RED,
+            BLUE,
+            Some(transform::transforms(&[
+                transform::rotation_z(std::f64::consts::FRAC_PI_2),
+                transform::scaling(0.5, 0.5, 0.5),
+            ])),
+        );
+        let p = StripePattern::new(inner, WHITE, None);
+
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0.5, 0.2, 0.)));
+        assert_eq!(BLUE, p.pattern_at(&Tuple::point(0.5, 0.7, 0.)));
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0.5, 1.2, 0.)));
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(1.5, 0.7, 0.)));
+    }
+
+    #[test]
+    fn a_nested_pattern_uses_both_transformations() {
+        let inner = TestPattern::new_boxed(Some(transform::scaling(2., 2., 2.)));
+        let p = CheckersPattern::new(inner, BLACK, Some(transform::translation(1., 1., 1.)));
+        let s = Sphere::default_boxed();
+
+        assert_eq!(
+            Color::new(0.5, 0.5, 0.25),
+            p.pattern_at_shape(&s, &Tuple::point(2., 2., 1.5))
+        );
+    }
+
+    #[test]
+    fn a_gradient_between_patterns() {
+        let p = GradientPattern::new(
+            StripePattern::new_boxed(WHITE, BLACK, Some(transform::scaling(0.1, 1., 1.))),
+            RED,
+            None,
+        );
+
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0., 0., 0.)));
+        assert_eq!(
+            Color::new(0.5, 0., 0.),
+            p.pattern_at(&Tuple::point(0.5, 0., 0.))
+        );
+        assert_eq!(
+            Color::new(1., 0.75, 0.75),
+            p.pattern_at(&Tuple::point(0.25, 0., 0.))
+        );
+    }
+
+    #[test]
+    fn blending_two_patterns() {
+        let horizontal = StripePattern::new_boxed(
+            WHITE,
+            BLACK,
+            Some(transform::rotation_y(std::f64::consts::FRAC_PI_2)),
+        );
+        let vertical = StripePattern::new_boxed(WHITE, BLACK, None);
+        let p = BlendPattern::average(horizontal, vertical);
+
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.5, 0., -0.5)));
+        assert_eq!(
+            Color::new(0.5, 0.5, 0.5),
+            p.pattern_at(&Tuple::point(1.5, 0., -0.5))
+        );
+        assert_eq!(BLACK, p.pattern_at(&Tuple::point(1.5, 0., 0.5)));
+    }
+
+    #[test]
+    fn the_weight_of_a_blend() {
+        let p = BlendPattern::new(WHITE, RED, 0.25, None);
+        assert_eq!(
+            Color::new(1., 0.75, 0.75),
+            p.pattern_at(&Tuple::point(0., 0., 0.))
+        );
+
+        assert_eq!(
+            WHITE,
+            BlendPattern::new(WHITE, RED, 0., None).pattern_at(&Tuple::point(0., 0., 0.))
+        );
+        assert_eq!(
+            RED,
+            BlendPattern::new(WHITE, RED, 1., None).pattern_at(&Tuple::point(0., 0., 0.))
+        );
+    }
+
+    #[test]
+    fn perturbing_a_pattern() {
+        let p = PerturbPattern::new(TestPattern::new_boxed(None), 0.2, 7, None);
+        let point = Tuple::point(0.3, 0.6, 0.1);
+
+        let color = p.pattern_at(&point);
+        assert_ne!(Color::new(0.3, 0.6, 0.1), color);
+        // The test pattern shows the lookup point, which moves by at most the scale
+        assert!((color.r - 0.3).abs() <= 0.2);
+        assert!((color.g - 0.6).abs() <= 0.2);
+        assert!((color.b - 0.1).abs() <= 0.2);
+    }
+
+    #[test]
+    fn perturbing_is_deterministic_for_a_seed() {
+        let stripes = StripePattern::new_boxed(WHITE, BLACK, None);
+        let a = PerturbPattern::new_boxed(stripes.clone(), 0.5, 3, None);
+        let b = PerturbPattern::new_boxed(stripes.clone(), 0.5, 3, None);
+        let point = Tuple::point(0.95, 0.4, 0.3);
+
+        assert!(a.eq(&b));
+        assert!(!a.eq(&PerturbPattern::new_boxed(stripes, 0.5, 4, None)));
+        assert_eq!(a.pattern_at(&point), b.pattern_at(&point));
+    }
+
+    fn grid() -> Vec<Tuple> {
+        (0..1000)
+            .map(|i| {
+                Tuple::point(
+                    (i % 10) as f64 * 0.37,
+                    (i / 10 % 10) as f64 * 0.29,
+                    (i / 100) as f64 * 0.41,
+                )
+            })
+            .collect()
+    }
+
+    fn is_grey_between_black_and_white(color: Color) -> bool {
+        (0. ..=1.).contains(&color.r) && color.r == color.g && color.g == color.b
+    }
+
+    #[test]
+    fn noise_patterns_stay_between_their_paints() {
+        let patterns = [
+            MarblePattern::new_boxed(WHITE, BLACK, 1, None),
+            WoodPattern::new_boxed(WHITE, BLACK, 1, None),
+            CloudsPattern::new_boxed(WHITE, BLACK, 1, None),
+            GranitePattern::new_boxed(WHITE, BLACK, 1, None),
+        ];
+
+        for p in patterns {
+            assert!(
+                grid()
+                    .iter()
+                    .all(|point| is_grey_between_black_and_white(p.pattern_at(point)))
+            );
+        }
+    }
+
+    #[test]
+    fn noise_patterns_are_deterministic_for_a_seed() {
+        let patterns = [
+            (
+                MarblePattern::new_boxed(WHITE, BLACK, 1, None),
+                MarblePattern::new_boxed(WHITE, BLACK, 2, None),
+            ),
+            (
+                WoodPattern::new_boxed(WHITE, BLACK, 1, None),
+                WoodPattern::new_boxed(WHITE, BLACK, 2, None),
+            ),
+            (
+                CloudsPattern::new_boxed(WHITE, BLACK, 1, None),
+                CloudsPattern::new_boxed(WHITE, BLACK, 2, None),
+            ),
+            (
+                GranitePattern::new_boxed(WHITE, BLACK, 1, None),
+                GranitePattern::new_boxed(WHITE, BLACK, 2, None),
+            ),
+        ];
+
+        for (p, other_seed) in patterns {
+            let again = p.clone();
+            assert!(
+                grid()
+                    .iter()
+                    .all(|point| p.pattern_at(point) == again.pattern_at(point))
+            );
+            assert!(
+                grid()
+                    .iter()
+                    .any(|point| p.pattern_at(point) != other_seed.pattern_at(point))
+            );
+        }
+    }
+
+    #[test]
+    fn wood_without_turbulence_is_rings() {
+        let p = WoodPattern {
+            turbulence: 0.,
+            ..WoodPattern::new(WHITE, BLACK, 0, None)
+        };
+
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0., 0., 0.)));
+        assert_eq!(
+            Color::new(0.75, 0.75, 0.75),
+            p.pattern_at(&Tuple::point(0.5, 0., 0.))
+        );
+        assert_eq!(
+            Color::new(0.75, 0.75, 0.75),
+            p.pattern_at(&Tuple::point(0., 3., 1.5))
+        );
+    }
+
+    #[test]
+    fn marble_without_turbulence_is_a_sine_wave() {
+        let p = MarblePattern {
+            turbulence: 0.,
+            ..MarblePattern::new(WHITE, BLACK, 0, None)
+        };
+
+        assert_eq!(
+            Color::new(0.5, 0.5, 0.5),
+            p.pattern_at(&Tuple::point(0., 0., 0.))
+        );
+        assert_eq!(BLACK, p.pattern_at(&Tuple::point(0.5, 1., 0.)));
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(1.5, 0., 2.)));
+    }
+
+    #[test]
+    fn the_coverage_of_clouds() {
+        let sky = Color::new(0.3, 0.5, 1.);
+        let clear = CloudsPattern {
+            coverage: 0.,
+            ..CloudsPattern::new(sky, WHITE, 0, None)
+        };
+        let overcast = CloudsPattern {
+            coverage: 1.,
+            ..CloudsPattern::new(sky, WHITE, 0, None)
+        };
+
+        assert!(grid().iter().all(|point| clear.pattern_at(point) == sky));
+        assert!(
+            grid()
+                .iter()
+                .all(|point| overcast.pattern_at(point) == WHITE)
+        );
+    }
+
+    #[test]
+    fn noise_patterns_with_simplex_noise() {
+        let perlin = MarblePattern::new(WHITE, BLACK, 1, None);
+        let simplex = MarblePattern {
+            noise: Noise::simplex(1),
+            ..perlin.clone()
+        };
+
+        assert!(
+            grid()
+                .iter()
+                .all(|point| is_grey_between_black_and_white(simplex.pattern_at(point)))
+        );
+        assert!(
+            grid()
+                .iter()
+                .any(|point| perlin.pattern_at(point) != simplex.pattern_at(point))
+        );
+    }
+
+    #[test]
+    fn a_radial_gradient_repeats_with_the_distance_to_the_axis() {
+        let p = RadialGradientPattern::new(WHITE, BLACK, None);
+
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0., 0., 0.)));
+        assert_eq!(
+            Color::new(0.5, 0.5, 0.5),
+            p.pattern_at(&Tuple::point(0.5, 0., 0.))
+        );
+        assert_eq!(
+            Color::new(0.5, 0.5, 0.5),
+            p.pattern_at(&Tuple::point(0., 2., 1.5))
+        );
+        assert_eq!(
+            Color::new(0.25, 0.25, 0.25),
+            p.pattern_at(&Tuple::point(0.75, 0., 0.))
+        );
+    }
+
+    #[test]
+    fn polka_dots_are_centered_on_integer_points() {
+        let p = PolkaDotPattern::new(RED, WHITE, None);
+
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0., 0., 0.)));
+        assert_eq!(RED, p.pattern_at(&Tuple::point(1.2, 0., -2.1)));
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.3, 0., 0.)));
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.5, 0., 0.5)));
+    }
+
+    #[test]
+    fn neighboring_hexagons_have_different_paints() {
+        let p = HexagonPattern::new(RED, GREEN, BLUE, None);
+        // The centers of the six neighbors of the hexagon at the origin
+        let neighbors: Vec<Color> = (0..6)
+            .map(|i| {
+                let angle = i as f64 * PI / 3.;
+                let distance = 3f64.sqrt();
+                p.pattern_at(&Tuple::point(
+                    distance * angle.cos(),
+                    0.,
+                    distance * angle.sin(),
+                ))
+            })
+            .collect();
+
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0., 0., 0.)));
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0.8, 5., 0.)));
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0., 0., -0.95)));
+        for i in 0..6 {
+            assert_ne!(RED, neighbors[i]);
+            assert_ne!(neighbors[i], neighbors[(i + 1) % 6]);
+        }
+    }
+
+    #[test]
+    fn bricks_are_offset_every_other_row() {
+        let p = BrickPattern::new(RED, WHITE, None);
+
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0.5, 0., 0.25)));
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0.5, 7., 0.25)));
+        // Vertical joints
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.01, 0., 0.25)));
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.99, 0., 0.25)));
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0.01, 0., 0.75)));
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.51, 0., 0.75)));
+        // Horizontal joints
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.5, 0., 0.49)));
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.5, 0., -0.01)));
+    }
+
+    #[test]
+    fn tiles_are_aligned_squares() {
+        let p = BrickPattern::tiles(RED, WHITE, None);
+
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0.5, 0., 0.5)));
+        assert_eq!(RED, p.pattern_at(&Tuple::point(0.5, 0., 1.5)));
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.01, 0., 1.5)));
+        assert_eq!(WHITE, p.pattern_at(&Tuple::point(0.5, 0., 0.99)));
+    }
+
+    #[test]
+    fn worley_distance_is_null_at_a_feature_point() {
+        let p = WorleyPattern::new(WHITE, BLACK, 3, None);
+        let (feature, _) = p.feature_point(&Tuple::point(2., -1., 0.));
+
+        assert_eq!(WHITE, p.pattern_at(&feature));
+        assert!(
+            grid()
+                .iter()
+                .all(|point| is_grey_between_black_and_white(p.pattern_at(point)))
         );
     }
 
+    #[test]
+    fn worley_cells_have_either_paint() {
+        let p = WorleyPattern {
+            feature: WorleyFeature::Cells,
+            ..WorleyPattern::new(RED, BLUE, 3, None)
+        };
+        let colors: Vec<Color> = grid().iter().map(|point| p.pattern_at(point)).collect();
+
+        assert!(colors.iter().all(|&color| color == RED || color == BLUE));
+        assert!(colors.contains(&RED));
+        assert!(colors.contains(&BLUE));
+    }
+
+    #[test]
+    fn worley_patterns_are_deterministic_for_a_seed() {
+        let p = Tuple::point(1.3, 2.7, -0.4);
+
+        for feature in [
+            WorleyFeature::Distance,
+            WorleyFeature::Edges,
+            WorleyFeature::Cells,
+        ] {
+            let worley = |seed| WorleyPattern {
+                feature,
+                ..WorleyPattern::new(WHITE, BLACK, seed, None)
+            };
+            assert_eq!(worley(5).pattern_at(&p), worley(5).pattern_at(&p));
+        }
+        assert!(grid().iter().any(|point| {
+            WorleyPattern::new(WHITE, BLACK, 5, None).pattern_at(point)
+                != WorleyPattern::new(WHITE, BLACK, 6, None).pattern_at(point)
+        }));
+    }
+
+    #[test]
+    fn parsing_worley_features() {
+        assert_eq!(Ok(WorleyFeature::Cells), "voronoi".parse());
+        assert_eq!(Ok(WorleyFeature::Edges), "edges".parse());
+        assert!("cracks".parse::<WorleyFeature>().is_err());
+    }
+
     #[test]
     fn create_a_stripe_pattern() {
         let p = StripePattern::new(WHITE, BLACK, None);
@@ -270,30 +1789,21 @@ mod tests {
     fn stripes_with_an_object_transformation() {
         let p = StripePattern::new(WHITE, BLACK, None);
         let s = Sphere::new_boxed(Some(transform::scaling(2., 2., 2.)), None);
-        assert_eq!(
-            WHITE,
-            PatternType::Stripe(p).pattern_at_shape(&s, &Tuple::point(1.5, 0., 0.))
-        );
+        assert_eq!(WHITE, p.pattern_at_shape(&s, &Tuple::point(1.5, 0., 0.)));
     }
 
     #[test]
     fn stripes_with_a_pattern_transformation() {
         let p = StripePattern::new(WHITE, BLACK, Some(transform::scaling(2., 2., 2.)));
         let s = Sphere::new_boxed(None, None);
-        assert_eq!(
-            WHITE,
-            PatternType::Stripe(p).pattern_at_shape(&s, &Tuple::point(1.5, 0., 0.))
-        );
+        assert_eq!(WHITE, p.pattern_at_shape(&s, &Tuple::point(1.5, 0., 0.)));
     }
 
     #[test]
     fn stripes_with_a_both_an_object_and_a_pattern_transformation() {
         let p = StripePattern::new(WHITE, BLACK, Some(transform::translation(0.5, 0., 0.)));
         let s = Sphere::new_boxed(Some(transform::scaling(2., 2., 2.)), None);
-        assert_eq!(
-            WHITE,
-            PatternType::Stripe(p).pattern_at_shape(&s, &Tuple::point(1.5, 0., 0.))
-        );
+        assert_eq!(WHITE, p.pattern_at_shape(&s, &Tuple::point(1.5, 0., 0.)));
     }
 
     #[test]
@@ -72,6 +72,11 @@ impl Shape for Plane {
         Tuple::vector(0., 1., 0.)
     }
 
+    fn local_tangents_at(&self, _local_point: &Tuple) -> (Tuple, Tuple) {
+        // Along x and z, like the planar mapping
+        (Tuple::vector(1., 0., 0.), Tuple::vector(0., 0., 1.))
+    }
+
     fn local_intersect(&self, &r: &Ray) -> Intersections {
         let mut xs = vec![];
         if r.direction.y.abs() >= EPSILON {
@@ -85,7 +90,10 @@ impl Shape for Plane {
 
 #[cfg(test)]
 mod tests {
+    use std::f64::consts::PI;
+
     use super::*;
+    use crate::transform;
 
     #[test]
     fn the_normal_of_a_place_is_a_constant_everywhere() {
@@ -97,6 +105,32 @@ mod tests {
         assert_eq!(normal, p.local_normal_at(&Tuple::point(-5., 0., 150.)));
     }
 
+    #[test]
+    fn the_tangents_of_a_plane_follow_the_planar_mapping() {
+        let p = Plane::default();
+
+        assert_eq!(
+            (Tuple::vector(1., 0., 0.), Tuple::vector(0., 0., 1.)),
+            p.tangents_at(&Tuple::point(3., 0., -7.))
+        );
+    }
+
+    #[test]
+    fn the_tangents_of_a_transformed_plane() {
+        let p = Plane::new(
+            Some(transform::transforms(&[
+                transform::scaling(2., 2., 2.),
+                transform::rotation_z(PI / 2.),
+            ])),
+            None,
+        );
+
+        assert_eq!(
+            (Tuple::vector(0., 1., 0.), Tuple::vector(0., 0., 1.)),
+            p.tangents_at(&Tuple::point(0., 3., 2.))
+        );
+    }
+
     #[test]
     fn intersect_with_a_ray_parallel_to_the_place() {
         let p = Plane::default();
@@ -0,0 +1,402 @@
+use super::canvas::Canvas;
+use super::color::{Color, quantize};
+use super::deflate;
+
+use std::io::{self, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PngColorType {
+    Rgb,
+    /// RGB with the alpha of the canvas
+    Rgba,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PngBitDepth {
+    Eight,
+    Sixteen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PngOptions {
+    pub color_type: PngColorType,
+    pub bit_depth: PngBitDepth,
+    /// Keyword and text pairs stored in tEXt chunks (ie "Software", "Title")
+    pub text: Vec<(String, String)>,
+}
+
+impl PngOptions {
+    pub fn new(color_type: PngColorType, bit_depth: PngBitDepth) -> PngOptions {
+        PngOptions {
+            color_type,
+            bit_depth,
+            text: vec![],
+        }
+    }
+
+    pub fn with_text(mut self, keyword: &str, text: &str) -> PngOptions {
+        self.text.push((keyword.to_string(), text.to_string()));
+        self
+    }
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions::new(PngColorType::Rgb, PngBitDepth::Eight)
+    }
+}
+
+/// Writes the canvas as a PNG image.
+///
+/// Colors are clamped to [0, 1] like the PPM writer does.
+pub fn write_png<W: Write>(canvas: &Canvas, options: &PngOptions, out: &mut W) -> io::Result<()> {
+    let (channels, color_type) = match options.color_type {
+        PngColorType::Rgb => (3, 2),
+        PngColorType::Rgba => (4, 6),
+    };
+    let (bytes_per_sample, bit_depth) = match options.bit_depth {
+        PngBitDepth::Eight => (1, 8),
+        PngBitDepth::Sixteen => (2, 16),
+    };
+
+    // The PNG specification limits both sizes to [1, 2^31 - 1]
+    let valid_size = |size: usize| (1..=i32::MAX as usize).contains(&size);
+    if !valid_size(canvas.width) || !valid_size(canvas.height) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Cannot write a {}x{} canvas as a PNG image",
+                canvas.width, canvas.height
+            ),
+        ));
+    }
+
+    out.write_all(&SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((canvas.width as u32).to_be_bytes());
+    header.extend((canvas.height as u32).to_be_bytes());
+    // Bit depth, color type, compression, filter and interlace methods
+    header.extend([bit_depth, color_type, 0, 0, 0]);
+    write_chunk(out, b"IHDR", &header)?;
+
+    for (keyword, text) in &options.text {
+        if keyword.is_empty() || keyword.len() > 79 || keyword.contains('\0') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid PNG text keyword '{keyword}'"),
+            ));
+        }
+        // tEXt chunks are Latin-1, replace what cannot be represented
+        let latin1 = |s: &str| -> Vec<u8> {
+            s.chars()
+                .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' })
+                .collect()
+        };
+        let data = [latin1(keyword), vec![0], latin1(text)].concat();
+        write_chunk(out, b"tEXt", &data)?;
+    }
+
+    let bpp = channels * bytes_per_sample;
+    let raw = scanlines(canvas, channels, bytes_per_sample);
+    let filtered = filter(&raw, canvas.width * bpp, bpp);
+    write_chunk(out, b"IDAT", &deflate::zlib_compress(&filtered))?;
+
+    write_chunk(out, b"IEND", &[])
+}
+
+fn scanlines(canvas: &Canvas, channels: usize, bytes_per_sample: usize) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(canvas.width * canvas.height * channels * bytes_per_sample);
+    for y in 0..canvas.height {
+        for x in 0..canvas.width {
+            let alpha = canvas.alpha_at(x, y);
+            // PNG colors are not premultiplied by the alpha
+            let Color { r, g, b } = if channels == 4 && alpha > 0. && alpha < 1. {
+                canvas.pixel_at(x, y) * (1. / alpha)
+            } else {
+                canvas.pixel_at(x, y)
+            };
+            let samples = [r, g, b, alpha];
+            for &value in &samples[..channels] {
+                if bytes_per_sample == 1 {
+                    raw.push(quantize(value, 255) as u8);
+                } else {
+                    raw.extend((quantize(value, 65535) as u16).to_be_bytes());
+                }
+            }
+        }
+    }
+    raw
+}
+
+/// Prefixes each row with the filter type that makes it the most compressible,
+/// using the minimum sum of absolute differences heuristic.
+fn filter(raw: &[u8], stride: usize, bpp: usize) -> Vec<u8> {
+    let mut filtered = Vec::with_capacity(raw.len() + raw.len() / stride.max(1));
+    let zeros = vec![0; stride];
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+
+    for (i, row) in raw.chunks(stride.max(1)).enumerate() {
+        let above = if i == 0 {
+            &zeros[..]
+        } else {
+            &raw[(i - 1) * stride..i * stride]
+        };
+
+        let mut best_type = 0;
+        let mut best_score = u64::MAX;
+        for filter_type in 0..5u8 {
+            for x in 0..row.len() {
+                let a = if x >= bpp { row[x - bpp] } else { 0 };
+                let b = above[x];
+                let c = if x >= bpp { above[x - bpp] } else { 0 };
+                let predicted = match filter_type {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                candidate[x] = row[x].wrapping_sub(predicted);
+            }
+
+            let score = candidate[..row.len()]
+                .iter()
+                .map(|&v| (v as i8).unsigned_abs() as u64)
+                .sum();
+            if score < best_score {
+                best_score = score;
+                best_type = filter_type;
+                best[..row.len()].copy_from_slice(&candidate[..row.len()]);
+            }
+        }
+
+        filtered.push(best_type);
+        filtered.extend_from_slice(&best[..row.len()]);
+    }
+
+    filtered
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+
+    let crc = crc32(&[&kind[..], data].concat());
+    out.write_all(&crc.to_be_bytes())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLUE, RED, WHITE};
+
+    struct Chunk {
+        kind: String,
+        data: Vec<u8>,
+    }
+
+    fn chunks(png: &[u8]) -> Vec<Chunk> {
+        assert_eq!(SIGNATURE, png[..8]);
+
+        let mut chunks = vec![];
+        let mut pos = 8;
+        while pos < png.len() {
+            let length = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let kind = &png[pos + 4..pos + 8];
+            let data = &png[pos + 8..pos + 8 + length];
+            let crc =
+                u32::from_be_bytes(png[pos + 8 + length..pos + 12 + length].try_into().unwrap());
+            assert_eq!(crc32(&[kind, data].concat()), crc);
+
+            chunks.push(Chunk {
+                kind: String::from_utf8(kind.to_vec()).unwrap(),
+                data: data.to_vec(),
+            });
+            pos += 12 + length;
+        }
+        chunks
+    }
+
+    /// Decompresses and unfilters the image data.
+    fn pixels(png: &[u8], stride: usize, bpp: usize) -> Vec<Vec<u8>> {
+        let idat = chunks(png).into_iter().find(|c| c.kind == "IDAT").unwrap();
+        let data = deflate::zlib_decompress(&idat.data);
+
+        let mut rows: Vec<Vec<u8>> = vec![];
+        for line in data.chunks(stride + 1) {
+            let zeros = vec![0; stride];
+            let above = rows.last().unwrap_or(&zeros).clone();
+            let mut row = vec![0u8; stride];
+            for x in 0..stride {
+                let a = if x >= bpp { row[x - bpp] } else { 0 };
+                let c = if x >= bpp { above[x - bpp] } else { 0 };
+                let predicted = match line[0] {
+                    0 => 0,
+                    1 => a,
+                    2 => above[x],
+                    3 => ((a as u16 + above[x] as u16) / 2) as u8,
+                    _ => paeth(a, above[x], c),
+                };
+                row[x] = line[x + 1].wrapping_add(predicted);
+            }
+            rows.push(row);
+        }
+        rows
+    }
+
+    fn canvas() -> Canvas {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, RED);
+        c.write_pixel(1, 0, Color::new(0., 0.5, 1.5));
+        c.write_pixel(2, 1, WHITE);
+        c
+    }
+
+    #[test]
+    fn the_crc32_checksum() {
+        assert_eq!(0xAE42_6082, crc32(b"IEND"));
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn a_png_starts_with_the_header_and_ends_with_iend() {
+        let mut png = Vec::new();
+        write_png(&canvas(), &PngOptions::default(), &mut png).unwrap();
+
+        let chunks = chunks(&png);
+        assert_eq!("IHDR", chunks[0].kind);
+        assert_eq!(vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0], chunks[0].data);
+        assert_eq!("IEND", chunks.last().unwrap().kind);
+        assert!(chunks.last().unwrap().data.is_empty());
+    }
+
+    #[test]
+    fn writing_rgb_pixels() {
+        let mut png = Vec::new();
+        write_png(&canvas(), &PngOptions::default(), &mut png).unwrap();
+
+        let rows = pixels(&png, 9, 3);
+        assert_eq!(vec![255, 0, 0, 0, 127, 255, 0, 0, 0], rows[0]);
+        assert_eq!(vec![0, 0, 0, 0, 0, 0, 255, 255, 255], rows[1]);
+    }
+
+    #[test]
+    fn writing_rgba_pixels() {
+        let options = PngOptions::new(PngColorType::Rgba, PngBitDepth::Eight);
+        let mut png = Vec::new();
+        write_png(&canvas(), &options, &mut png).unwrap();
+
+        assert_eq!(6, chunks(&png)[0].data[9]);
+        let rows = pixels(&png, 12, 4);
+        assert_eq!(
+            vec![255, 0, 0, 255, 0, 127, 255, 255, 0, 0, 0, 255],
+            rows[0]
+        );
+    }
+
+    #[test]
+    fn writing_transparent_pixels() {
+        let mut c = Canvas::new_transparent(2, 1);
+        // Half covered by red, premultiplied
+        c.write_pixel(1, 0, Color::new(0.5, 0., 0.));
+        c.write_alpha(1, 0, 0.5);
+
+        let options = PngOptions::new(PngColorType::Rgba, PngBitDepth::Eight);
+        let mut png = Vec::new();
+        write_png(&c, &options, &mut png).unwrap();
+
+        assert_eq!(vec![0, 0, 0, 0, 255, 0, 0, 127], pixels(&png, 8, 4)[0]);
+    }
+
+    #[test]
+    fn writing_16_bit_pixels() {
+        let options = PngOptions::new(PngColorType::Rgb, PngBitDepth::Sixteen);
+        let mut png = Vec::new();
+        write_png(&canvas(), &options, &mut png).unwrap();
+
+        assert_eq!(16, chunks(&png)[0].data[8]);
+        let rows = pixels(&png, 18, 6);
+        assert_eq!(vec![0xFF, 0xFF, 0, 0, 0, 0], rows[0][..6].to_vec());
+        assert_eq!(vec![0, 0, 0x7F, 0xFF, 0xFF, 0xFF], rows[0][6..12].to_vec());
+    }
+
+    #[test]
+    fn writing_text_metadata() {
+        let options = PngOptions::default()
+            .with_text("Software", "The Ray Tracer Challenge")
+            .with_text("Title", "Chapter 11");
+        let mut png = Vec::new();
+        write_png(&canvas(), &options, &mut png).unwrap();
+
+        let text: Vec<Vec<u8>> = chunks(&png)
+            .into_iter()
+            .filter(|c| c.kind == "tEXt")
+            .map(|c| c.data)
+            .collect();
+        assert_eq!(2, text.len());
+        assert_eq!(b"Software\0The Ray Tracer Challenge".to_vec(), text[0]);
+        assert_eq!(b"Title\0Chapter 11".to_vec(), text[1]);
+    }
+
+    #[test]
+    fn text_keywords_must_be_valid() {
+        let options = PngOptions::default().with_text("", "empty");
+        let mut png = Vec::new();
+        assert!(write_png(&canvas(), &options, &mut png).is_err());
+    }
+
+    #[test]
+    fn empty_canvases_cannot_be_written() {
+        for c in [Canvas::new(0, 3), Canvas::new(3, 0)] {
+            let mut png = Vec::new();
+            let err = write_png(&c, &PngOptions::default(), &mut png).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+            assert!(png.is_empty());
+        }
+    }
+
+    #[test]
+    fn large_uniform_images_are_compressed() {
+        let c = Canvas::new_filled(200, 100, BLUE);
+        let mut png = Vec::new();
+        write_png(&c, &PngOptions::default(), &mut png).unwrap();
+
+        assert!(png.len() < 2000);
+        let rows = pixels(&png, 600, 3);
+        assert_eq!(100, rows.len());
+        assert_eq!(vec![0, 0, 255], rows[99][597..].to_vec());
+    }
+}
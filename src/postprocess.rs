@@ -0,0 +1,542 @@
+use super::canvas::{Canvas, Interpolation};
+use super::color::{BLACK, Color};
+use super::sampling::Rng;
+
+// Post-processing filters applied to a rendered canvas, before the display pipeline.
+// Filters work on the linear, unclamped colors, so bloom can find the over-bright pixels.
+// Pixels outside of the canvas take the color of the closest edge.
+
+/// A convolution matrix with odd dimensions, centered on the pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    pub width: usize,
+    pub height: usize,
+    /// Row by row
+    pub weights: Vec<f64>,
+}
+
+impl Kernel {
+    pub fn new(width: usize, height: usize, weights: Vec<f64>) -> Kernel {
+        assert!(
+            width % 2 == 1 && height % 2 == 1,
+            "The dimensions of a kernel must be odd"
+        );
+        assert_eq!(width * height, weights.len(), "Wrong number of weights");
+        Kernel {
+            width,
+            height,
+            weights,
+        }
+    }
+
+    /// Averages the pixels of a square of `2 * radius + 1` pixels.
+    pub fn box_blur(radius: usize) -> Kernel {
+        let size = 2 * radius + 1;
+        Kernel::new(size, size, vec![1. / (size * size) as f64; size * size])
+    }
+
+    pub fn sharpen() -> Kernel {
+        Kernel::new(3, 3, vec![0., -1., 0., -1., 5., -1., 0., -1., 0.])
+    }
+
+    /// A Laplacian kernel, black except on the edges.
+    pub fn edge_detect() -> Kernel {
+        Kernel::new(3, 3, vec![-1., -1., -1., -1., 8., -1., -1., -1., -1.])
+    }
+
+    pub fn emboss() -> Kernel {
+        Kernel::new(3, 3, vec![-2., -1., 0., -1., 1., 1., 0., 1., 2.])
+    }
+
+    /// The normalized weights of a 1D Gaussian, covering 3 standard deviations on each side.
+    pub fn gaussian_weights(sigma: f64) -> Vec<f64> {
+        let radius = (3. * sigma).ceil().max(0.) as isize;
+        let weights: Vec<f64> = (-radius..=radius)
+            .map(|i| (-((i * i) as f64) / (2. * sigma * sigma)).exp())
+            .collect();
+        let sum: f64 = weights.iter().sum();
+        weights.iter().map(|w| w / sum).collect()
+    }
+
+    /// Convolves the colors of the canvas. Kernels without negative weights, like blurs,
+    /// also spread the alpha, kept in [0, 1]. The others, like edge detection, are not
+    /// meant for coverage and keep the alpha of the canvas.
+    pub fn convolve(&self, canvas: &Canvas) -> Canvas {
+        let blur = self.weights.iter().all(|&weight| weight >= 0.);
+        let mut result = Canvas::new(canvas.width, canvas.height);
+        let (rx, ry) = ((self.width / 2) as isize, (self.height / 2) as isize);
+        let clamp = |v: isize, max: usize| v.clamp(0, max as isize - 1) as usize;
+
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                let (mut color, mut alpha) = (BLACK, 0.);
+                for ky in 0..self.height {
+                    let sy = clamp(y as isize + ky as isize - ry, canvas.height);
+                    for kx in 0..self.width {
+                        let weight = self.weights[ky * self.width + kx];
+                        if weight == 0. {
+                            continue;
+                        }
+                        let sx = clamp(x as isize + kx as isize - rx, canvas.width);
+                        color = color + canvas.pixel_at(sx, sy) * weight;
+                        alpha += canvas.alpha_at(sx, sy) * weight;
+                    }
+                }
+                result.write_pixel(x, y, color);
+                if blur {
+                    result.write_alpha(x, y, alpha.clamp(0., 1.));
+                } else {
+                    result.write_alpha(x, y, canvas.alpha_at(x, y));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// A post-processing step.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Convolve(Kernel),
+    /// Blur with a standard deviation in pixels
+    GaussianBlur {
+        sigma: f64,
+    },
+    /// Unsharp masking: adds the difference with a slightly blurred image
+    Sharpen {
+        amount: f64,
+    },
+    /// Glare around the colors brighter than the threshold
+    Bloom {
+        threshold: f64,
+        intensity: f64,
+        sigma: f64,
+    },
+    /// Darkens the corners, by the strength at the very corner
+    Vignette {
+        strength: f64,
+    },
+    /// Scales the red channel up and the blue channel down around the center,
+    /// by a fraction of the distance to the center
+    ChromaticAberration {
+        amount: f64,
+    },
+    /// Adds random noise of at most the amount, the same for a given seed
+    FilmGrain {
+        amount: f64,
+        seed: u64,
+    },
+}
+
+impl Filter {
+    pub fn apply(&self, canvas: &Canvas) -> Canvas {
+        match self {
+            Filter::Convolve(kernel) => kernel.convolve(canvas),
+            Filter::GaussianBlur { sigma } => gaussian_blur(canvas, *sigma),
+            Filter::Sharpen { amount } => {
+                let blurred = gaussian_blur(canvas, 1.);
+                map_pixels(canvas, |x, y, c| c + (c - blurred.pixel_at(x, y)) * *amount)
+            }
+            Filter::Bloom {
+                threshold,
+                intensity,
+                sigma,
+            } => {
+                let bright = map_pixels(canvas, |_, _, c| {
+                    let excess = |v: f64| (v - threshold).max(0.);
+                    Color::new(excess(c.r), excess(c.g), excess(c.b))
+                });
+                let glare = gaussian_blur(&bright, *sigma);
+                map_pixels(canvas, |x, y, c| c + glare.pixel_at(x, y) * *intensity)
+            }
+            Filter::Vignette { strength } => {
+                let (cx, cy) = (canvas.width as f64 / 2., canvas.height as f64 / 2.);
+                let corner = (cx * cx + cy * cy).sqrt();
+                map_pixels(canvas, |x, y, c| {
+                    let (dx, dy) = (x as f64 + 0.5 - cx, y as f64 + 0.5 - cy);
+                    let d = (dx * dx + dy * dy) / (corner * corner);
+                    c * (1. - strength * d).max(0.)
+                })
+            }
+            Filter::ChromaticAberration { amount } => {
+                let (cx, cy) = (canvas.width as f64 / 2., canvas.height as f64 / 2.);
+                map_pixels(canvas, |x, y, c| {
+                    let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+                    let shifted = |scale: f64| {
+                        canvas.sample(
+                            cx + (px - cx) * scale,
+                            cy + (py - cy) * scale,
+                            Interpolation::Bilinear,
+                        )
+                    };
+                    Color::new(shifted(1. - amount).r, c.g, shifted(1. + amount).b)
+                })
+            }
+            Filter::FilmGrain { amount, seed } => map_pixels(canvas, |x, y, c| {
+                let noise = (Rng::for_pixel(*seed, x, y).next_f64() * 2. - 1.) * amount;
+                // Keep the colors premultiplied by the alpha
+                c + Color::new(noise, noise, noise) * canvas.alpha_at(x, y)
+            }),
+        }
+    }
+}
+
+/// The largest blur radius or standard deviation accepted from a filter description.
+const MAX_FILTER_SIZE: f64 = 100.;
+
+impl std::str::FromStr for Filter {
+    type Err = String;
+
+    /// Parses a filter name followed by its optional parameters, separated by colons,
+    /// e.g. "blur:2" or "bloom:1:0.5:4".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default();
+        let params = parts
+            .map(|p| match p.parse::<f64>() {
+                Ok(value) if value.is_finite() => Ok(value),
+                _ => Err(format!("Invalid parameter '{p}' for filter '{name}'")),
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+        let param = |i: usize, default: f64| params.get(i).copied().unwrap_or(default);
+        // Radii and standard deviations in pixels, bounded to keep the kernels small
+        let size = |i: usize, default: f64| {
+            let value = param(i, default);
+            if value > 0. && value <= MAX_FILTER_SIZE {
+                Ok(value)
+            } else {
+                Err(format!(
+                    "The size of filter '{name}' must be in (0, {MAX_FILTER_SIZE}], got {value}"
+                ))
+            }
+        };
+
+        let (filter, max_params) = match name {
+            "blur" => (
+                Filter::GaussianBlur {
+                    sigma: size(0, 1.)?,
+                },
+                1,
+            ),
+            "box" => (
+                Filter::Convolve(Kernel::box_blur(size(0, 1.)?.ceil() as usize)),
+                1,
+            ),
+            "sharpen" => (
+                Filter::Sharpen {
+                    amount: param(0, 1.),
+                },
+                1,
+            ),
+            "edges" => (Filter::Convolve(Kernel::edge_detect()), 0),
+            "emboss" => (Filter::Convolve(Kernel::emboss()), 0),
+            "bloom" => (
+                Filter::Bloom {
+                    threshold: param(0, 1.),
+                    intensity: param(1, 0.5),
+                    sigma: size(2, 4.)?,
+                },
+                3,
+            ),
+            "vignette" => (
+                Filter::Vignette {
+                    strength: param(0, 0.5),
+                },
+                1,
+            ),
+            "aberration" => (
+                Filter::ChromaticAberration {
+                    amount: param(0, 0.005),
+                },
+                1,
+            ),
+            "grain" => (
+                Filter::FilmGrain {
+                    amount: param(0, 0.05),
+                    seed: param(1, 0.) as u64,
+                },
+                2,
+            ),
+            _ => return Err(format!("Unknown filter '{name}'")),
+        };
+
+        if params.len() > max_params {
+            return Err(format!("Too many parameters for filter '{name}'"));
+        }
+        Ok(filter)
+    }
+}
+
+/// A chain of filters, applied in order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PostProcess {
+    pub filters: Vec<Filter>,
+}
+
+impl PostProcess {
+    pub fn new(filters: Vec<Filter>) -> PostProcess {
+        PostProcess { filters }
+    }
+
+    /// Adds a filter at the end of the chain.
+    pub fn then(mut self, filter: Filter) -> PostProcess {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    pub fn apply(&self, canvas: &Canvas) -> Canvas {
+        self.filters
+            .iter()
+            .fold(canvas.clone(), |canvas, filter| filter.apply(&canvas))
+    }
+}
+
+impl std::str::FromStr for PostProcess {
+    type Err = String;
+
+    /// Parses filters separated by commas, e.g. "bloom,vignette:0.3,grain:0.02".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|f| f.trim().parse::<Filter>())
+            .collect::<Result<Vec<Filter>, String>>()
+            .map(PostProcess::new)
+    }
+}
+
+fn gaussian_blur(canvas: &Canvas, sigma: f64) -> Canvas {
+    if sigma <= 0. {
+        return canvas.clone();
+    }
+    // The Gaussian is separable: blur the rows, then the columns
+    let weights = Kernel::gaussian_weights(sigma);
+    let horizontal = Kernel::new(weights.len(), 1, weights.clone());
+    let vertical = Kernel::new(1, weights.len(), weights);
+    vertical.convolve(&horizontal.convolve(canvas))
+}
+
+/// Changes the color of every pixel, keeping the alpha.
+fn map_pixels<F>(canvas: &Canvas, f: F) -> Canvas
+where
+    F: Fn(usize, usize, Color) -> Color,
+{
+    let mut result = canvas.clone();
+    for y in 0..canvas.height {
+        for x in 0..canvas.width {
+            result.write_pixel(x, y, f(x, y, canvas.pixel_at(x, y)));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{RED, WHITE};
+    use crate::utils::approx_eq;
+
+    fn single_white_pixel(size: usize) -> Canvas {
+        let mut c = Canvas::new(size, size);
+        c.write_pixel(size / 2, size / 2, WHITE);
+        c
+    }
+
+    #[test]
+    fn convolving_with_a_box_kernel() {
+        let c = single_white_pixel(5);
+
+        let blurred = Kernel::box_blur(1).convolve(&c);
+        assert_eq!(WHITE * (1. / 9.), blurred.pixel_at(1, 1));
+        assert_eq!(WHITE * (1. / 9.), blurred.pixel_at(3, 2));
+        assert_eq!(BLACK, blurred.pixel_at(0, 2));
+        assert!(approx_eq(1., blurred.alpha_at(0, 0)));
+    }
+
+    #[test]
+    fn the_edges_are_extended_during_a_convolution() {
+        let c = Canvas::new_filled(3, 3, RED);
+
+        let blurred = Kernel::box_blur(2).convolve(&c);
+        assert_eq!(RED, blurred.pixel_at(0, 0));
+        assert_eq!(RED, blurred.pixel_at(2, 1));
+    }
+
+    #[test]
+    fn color_kernels_keep_the_alpha() {
+        let opaque = single_white_pixel(5);
+        assert!(Kernel::edge_detect().convolve(&opaque).is_opaque());
+
+        let mut c = Canvas::new_transparent(3, 1);
+        c.write_pixel(1, 0, RED);
+        c.write_alpha(1, 0, 1.);
+        for kernel in [Kernel::sharpen(), Kernel::emboss()] {
+            let filtered = kernel.convolve(&c);
+            assert_eq!(0., filtered.alpha_at(0, 0));
+            assert_eq!(1., filtered.alpha_at(1, 0));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The dimensions of a kernel must be odd")]
+    fn kernels_must_have_a_center() {
+        Kernel::new(2, 1, vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn gaussian_weights_are_normalized() {
+        let weights = Kernel::gaussian_weights(1.5);
+
+        assert_eq!(11, weights.len());
+        assert!(approx_eq(1., weights.iter().sum()));
+        assert!(weights[5] > weights[4]);
+        assert!(approx_eq(weights[3], weights[7]));
+    }
+
+    #[test]
+    fn a_gaussian_blur_keeps_the_total_energy() {
+        let c = single_white_pixel(21);
+
+        let blurred = Filter::GaussianBlur { sigma: 2. }.apply(&c);
+        let total: f64 = blurred.pixels().iter().map(|c| c.r).sum();
+        assert!(approx_eq(1., total));
+        assert!(blurred.pixel_at(10, 10).r < 0.1);
+        assert!(blurred.pixel_at(10, 10).r > blurred.pixel_at(12, 10).r);
+    }
+
+    #[test]
+    fn sharpening_increases_the_contrast() {
+        let mut c = Canvas::new_filled(8, 1, Color::new(0.5, 0.5, 0.5));
+        for x in 4..8 {
+            c.write_pixel(x, 0, Color::new(0.6, 0.6, 0.6));
+        }
+
+        let sharpened = Filter::Sharpen { amount: 1. }.apply(&c);
+        assert!(sharpened.pixel_at(3, 0).r < 0.5);
+        assert!(sharpened.pixel_at(4, 0).r > 0.6);
+        assert_eq!(c.pixel_at(0, 0), sharpened.pixel_at(0, 0));
+    }
+
+    #[test]
+    fn bloom_spreads_only_the_over_bright_colors() {
+        let mut c = Canvas::new_filled(9, 9, Color::new(0.9, 0.9, 0.9));
+        c.write_pixel(4, 4, Color::new(5., 1., 1.));
+
+        let bloom = Filter::Bloom {
+            threshold: 1.,
+            intensity: 1.,
+            sigma: 1.,
+        };
+        let result = bloom.apply(&c);
+        assert_eq!(Color::new(0.9, 0.9, 0.9), result.pixel_at(0, 0));
+        let glow = result.pixel_at(5, 4);
+        assert!(glow.r > 0.9 && approx_eq(0.9, glow.g));
+    }
+
+    #[test]
+    fn a_vignette_darkens_the_corners() {
+        let c = Canvas::new_filled(10, 10, WHITE);
+
+        let result = Filter::Vignette { strength: 0.5 }.apply(&c);
+        let center = result.pixel_at(5, 5).r;
+        let corner = result.pixel_at(0, 0).r;
+        assert!(center > 0.99);
+        assert!(corner < 0.6 && corner > 0.5);
+    }
+
+    #[test]
+    fn chromatic_aberration_shifts_red_and_blue() {
+        let mut c = Canvas::new(21, 1);
+        c.write_pixel(20, 0, WHITE);
+
+        let result = Filter::ChromaticAberration { amount: 0.1 }.apply(&c);
+        // Red is sampled closer to the center and blue further from it
+        assert!(result.pixel_at(19, 0).b > 0.);
+        assert_eq!(0., result.pixel_at(19, 0).r);
+        assert_eq!(0., result.pixel_at(19, 0).g);
+        assert_eq!(0., result.pixel_at(20, 0).r);
+        assert_eq!(BLACK, result.pixel_at(10, 0));
+    }
+
+    #[test]
+    fn film_grain_depends_only_on_the_seed() {
+        let c = Canvas::new_filled(4, 4, Color::new(0.5, 0.5, 0.5));
+        let grain = Filter::FilmGrain {
+            amount: 0.1,
+            seed: 3,
+        };
+
+        let a = grain.apply(&c);
+        let b = grain.apply(&c);
+        assert!(a.pixels().iter().zip(b.pixels()).all(|(a, b)| a.r == b.r));
+        assert!(a.pixels().iter().all(|p| (p.r - 0.5).abs() <= 0.1));
+        assert!(a.pixels().iter().any(|p| p.r != 0.5));
+
+        let other = Filter::FilmGrain {
+            amount: 0.1,
+            seed: 4,
+        }
+        .apply(&c);
+        assert_ne!(a.pixel_at(1, 1).r, other.pixel_at(1, 1).r);
+    }
+
+    #[test]
+    fn chaining_filters() {
+        let chain = PostProcess::default()
+            .then(Filter::Vignette { strength: 1. })
+            .then(Filter::Convolve(Kernel::new(1, 1, vec![2.])));
+
+        let result = chain.apply(&Canvas::new_filled(2, 2, WHITE));
+        assert_eq!(2, chain.filters.len());
+        // Each pixel is at a quarter of the squared distance to the corners
+        assert_eq!(Color::new(1.5, 1.5, 1.5), result.pixel_at(0, 0));
+    }
+
+    #[test]
+    fn parsing_a_chain_of_filters() {
+        let chain: PostProcess = "bloom:2:0.25, vignette,grain:0.1:7".parse().unwrap();
+
+        assert_eq!(
+            vec![
+                Filter::Bloom {
+                    threshold: 2.,
+                    intensity: 0.25,
+                    sigma: 4.
+                },
+                Filter::Vignette { strength: 0.5 },
+                Filter::FilmGrain {
+                    amount: 0.1,
+                    seed: 7
+                },
+            ],
+            chain.filters
+        );
+        assert_eq!(Ok(Filter::Sharpen { amount: 1. }), "sharpen".parse());
+        assert!("blur:x".parse::<Filter>().is_err());
+        assert!("edges:1".parse::<Filter>().is_err());
+        assert!("glow".parse::<PostProcess>().is_err());
+    }
+
+    #[test]
+    fn parsing_invalid_filter_sizes() {
+        for invalid in [
+            "blur:1e9",
+            "blur:0",
+            "blur:-1",
+            "blur:NaN",
+            "blur:inf",
+            "box:1e9",
+            "box:0",
+            "box:-2",
+            "bloom:1:0.5:1e9",
+            "bloom:1:0.5:0",
+            "vignette:NaN",
+        ] {
+            assert!(invalid.parse::<Filter>().is_err(), "{invalid}");
+        }
+        assert_eq!(Ok(Filter::GaussianBlur { sigma: 100. }), "blur:100".parse());
+        assert_eq!(Ok(Filter::Convolve(Kernel::box_blur(2))), "box:2".parse());
+    }
+}
@@ -1,59 +1,302 @@
 use super::canvas::*;
 
+use super::color::Color;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
 const MAX_COLOR_VALUE: i32 = 255;
 const MAX_PIXELS_PER_LINE: usize = 5;
 
-fn clamp(value: i32, min: i32, max: i32) -> i32 {
-    if value < min {
-        return min;
-    }
+/// The two variants of the PPM format.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PpmFormat {
+    /// P3: human readable, but large and slow to write
+    Ascii,
+    /// P6: one byte per color component
+    Binary,
+}
+
+impl std::str::FromStr for PpmFormat {
+    type Err = String;
 
-    if value > max {
-        return max;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "p3" | "ascii" => Ok(PpmFormat::Ascii),
+            "p6" | "binary" => Ok(PpmFormat::Binary),
+            _ => Err(format!("Unknown PPM format: {s}")),
+        }
     }
+}
 
-    value
+// FIXME: This works in ImageMagick but crashes EOG for some values
+pub fn canvas_to_ppm(canvas: Canvas) -> String {
+    let mut ppm = Vec::new();
+    write_ppm(&canvas, PpmFormat::Ascii, &mut ppm).expect("Writing to memory cannot fail");
+    String::from_utf8(ppm).expect("ASCII PPM is valid UTF-8")
 }
 
-fn as_rgb255(color: f64) -> i32 {
-    let color255 = (color * MAX_COLOR_VALUE as f64) as i32;
-    clamp(color255, 0, 255)
+/// Writes the canvas as a PPM image, one row of pixels at a time.
+///
+/// The writer is not buffered here: wrap files and stdout in a `BufWriter`.
+pub fn write_ppm<W: Write>(canvas: &Canvas, format: PpmFormat, out: &mut W) -> io::Result<()> {
+    match format {
+        PpmFormat::Ascii => write_ascii(canvas, out),
+        PpmFormat::Binary => write_binary(canvas, out),
+    }
 }
 
-// FIXME: This works in ImageMagick but crashes EOG for some values
-pub fn canvas_to_ppm(canvas: Canvas) -> String {
-    let mut s = format!(
+fn write_ascii<W: Write>(canvas: &Canvas, out: &mut W) -> io::Result<()> {
+    write!(
+        out,
         "P3\n{} {}\n{}\n",
         canvas.width, canvas.height, MAX_COLOR_VALUE
-    );
+    )?;
 
+    let mut line = String::new();
     for y in 0..canvas.height {
+        line.clear();
         for x in 0..canvas.width {
             let pixel = canvas.pixel_at(x, y);
-
-            // FIXME: Add a method in color struct to get RGB_255 value of a pixel/color component
-            // Could also use destructuring ie let (red, green, blue) = pixel.as_rgb255();
-            let red = as_rgb255(pixel.r);
-            let green = as_rgb255(pixel.g);
-            let blue = as_rgb255(pixel.b);
+            let (red, green, blue) = pixel.as_rgb255();
 
             let rgb_tuple = format!("{red} {green} {blue}");
-            s.push_str(&rgb_tuple);
+            line.push_str(&rgb_tuple);
             if (x > 0 && x % MAX_PIXELS_PER_LINE == 0) || x == (canvas.width - 1) {
-                s.push('\n');
+                line.push('\n');
             } else {
-                s.push(' ');
+                line.push(' ');
             }
         }
+        out.write_all(line.as_bytes())?;
     }
 
-    s
+    Ok(())
+}
+
+fn write_binary<W: Write>(canvas: &Canvas, out: &mut W) -> io::Result<()> {
+    write!(
+        out,
+        "P6\n{} {}\n{}\n",
+        canvas.width, canvas.height, MAX_COLOR_VALUE
+    )?;
+
+    let mut row = Vec::with_capacity(canvas.width * 3);
+    for y in 0..canvas.height {
+        row.clear();
+        for x in 0..canvas.width {
+            let (red, green, blue) = canvas.pixel_at(x, y).as_rgb255();
+            row.extend([red, green, blue]);
+        }
+        out.write_all(&row)?;
+    }
+
+    Ok(())
+}
+
+/// Errors found while reading a PPM image.
+#[derive(Debug)]
+pub enum PpmError {
+    Io(io::Error),
+    /// The file does not start with P3 or P6
+    UnknownFormat(String),
+    /// A width, height or maximum value is missing or is not a number
+    InvalidHeader(String),
+    /// The maximum color value must be between 1 and 65535
+    InvalidMaxValue(u32),
+    /// A color value is not a number or is above the maximum value
+    InvalidPixel {
+        x: usize,
+        y: usize,
+        value: String,
+    },
+    /// The file ends before all the pixels were read
+    MissingPixels {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PpmError::Io(err) => write!(f, "{err}"),
+            PpmError::UnknownFormat(magic) => {
+                write!(f, "Unknown PPM format '{magic}', expected P3 or P6")
+            }
+            PpmError::InvalidHeader(reason) => write!(f, "Invalid PPM header: {reason}"),
+            PpmError::InvalidMaxValue(max) => {
+                write!(f, "Invalid maximum color value {max}, expected 1 to 65535")
+            }
+            PpmError::InvalidPixel { x, y, value } => {
+                write!(f, "Invalid color value '{value}' for pixel ({x}, {y})")
+            }
+            PpmError::MissingPixels { expected, found } => write!(
+                f,
+                "Truncated PPM data: expected {expected} color values, found {found}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PpmError {}
+
+impl From<io::Error> for PpmError {
+    fn from(err: io::Error) -> Self {
+        PpmError::Io(err)
+    }
+}
+
+/// Reads an ASCII (P3) or binary (P6) PPM image.
+///
+/// Color values are divided by the maximum value of the file, so they are between 0 and 1.
+pub fn read_ppm<R: Read>(input: &mut R) -> Result<Canvas, PpmError> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    ppm_to_canvas(&data)
+}
+
+pub fn ppm_to_canvas(data: &[u8]) -> Result<Canvas, PpmError> {
+    let mut tokens = Tokens { data, pos: 0 };
+
+    let magic = tokens.next().unwrap_or_default();
+    let binary = match magic.as_str() {
+        "P3" => false,
+        "P6" => true,
+        _ => return Err(PpmError::UnknownFormat(magic)),
+    };
+
+    let width = tokens.header_value("width")? as usize;
+    let height = tokens.header_value("height")? as usize;
+    let max = tokens.header_value("maximum color value")?;
+    if max == 0 || max > 65535 {
+        return Err(PpmError::InvalidMaxValue(max));
+    }
+
+    let expected = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or_else(|| PpmError::InvalidHeader(format!("{width}x{height} is too large")))?;
+
+    // The header is not trusted with the allocations: they are limited by the data
+    // actually there, and the canvas is only created once all the values were read
+    let remaining = data.len().saturating_sub(tokens.pos);
+    let mut values = Vec::new();
+
+    if binary {
+        // A single whitespace separates the header from the pixels
+        let start = tokens.pos + 1;
+        let bytes_per_value = if max < 256 { 1 } else { 2 };
+        let raster = data.get(start..).unwrap_or_default();
+        values.reserve(expected.min(raster.len() / bytes_per_value));
+        for (i, chunk) in raster
+            .chunks_exact(bytes_per_value)
+            .take(expected)
+            .enumerate()
+        {
+            let value = chunk.iter().fold(0u32, |v, b| (v << 8) | *b as u32);
+            if value > max {
+                let (x, y) = ((i / 3) % width, i / 3 / width);
+                return Err(PpmError::InvalidPixel {
+                    x,
+                    y,
+                    value: value.to_string(),
+                });
+            }
+            values.push(value);
+        }
+    } else {
+        // Each value takes at least a digit and a separator
+        values.reserve(expected.min(remaining / 2 + 1));
+        while values.len() < expected {
+            let Some(token) = tokens.next() else { break };
+            match token.parse::<u32>() {
+                Ok(value) if value <= max => values.push(value),
+                _ => {
+                    let i = values.len() / 3;
+                    return Err(PpmError::InvalidPixel {
+                        x: i % width,
+                        y: i / width,
+                        value: token,
+                    });
+                }
+            }
+        }
+    }
+
+    if values.len() < expected {
+        return Err(PpmError::MissingPixels {
+            expected,
+            found: values.len(),
+        });
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    let max = max as f64;
+    for (i, rgb) in values.chunks_exact(3).enumerate() {
+        let color = Color::new(
+            rgb[0] as f64 / max,
+            rgb[1] as f64 / max,
+            rgb[2] as f64 / max,
+        );
+        canvas.write_pixel(i % width, i / width, color);
+    }
+
+    Ok(canvas)
+}
+
+/// Whitespace separated words, skipping comments (# until the end of the line).
+struct Tokens<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Tokens<'_> {
+    fn next(&mut self) -> Option<String> {
+        // Skip whitespace and comments
+        while let Some(&b) = self.data.get(self.pos) {
+            if b == b'#' {
+                while self.data.get(self.pos).is_some_and(|&b| b != b'\n') {
+                    self.pos += 1;
+                }
+            } else if b.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace() && *b != b'#')
+        {
+            self.pos += 1;
+        }
+
+        if start == self.pos {
+            None
+        } else {
+            Some(String::from_utf8_lossy(&self.data[start..self.pos]).into_owned())
+        }
+    }
+
+    fn header_value(&mut self, name: &str) -> Result<u32, PpmError> {
+        match self.next() {
+            None => Err(PpmError::InvalidHeader(format!("missing {name}"))),
+            Some(token) => token
+                .parse::<u32>()
+                .map_err(|_| PpmError::InvalidHeader(format!("invalid {name} '{token}'"))),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::color::*;
+    use crate::utils::approx_eq;
 
     #[test]
     fn constructing_the_ppm_header() {
@@ -128,4 +371,191 @@ mod tests {
 
         assert_eq!(Some('\n'), ppm.chars().last());
     }
+
+    #[test]
+    fn writing_an_ascii_ppm_to_a_writer() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(1, 0, Color::new(1., 0.5, 0.));
+
+        let mut out = Vec::new();
+        write_ppm(&c, PpmFormat::Ascii, &mut out).unwrap();
+        assert_eq!(b"P3\n2 1\n255\n0 0 0 255 127 0\n".to_vec(), out);
+    }
+
+    #[test]
+    fn the_ascii_writer_matches_canvas_to_ppm() {
+        let c = Canvas::new_filled(10, 2, Color::new(1., 0.8, 0.6));
+
+        let mut out = Vec::new();
+        write_ppm(&c, PpmFormat::Ascii, &mut out).unwrap();
+        assert_eq!(canvas_to_ppm(c), String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn writing_a_binary_ppm() {
+        let mut c = Canvas::new(2, 2);
+        c.write_pixel(0, 0, Color::new(1.5, 0., 0.));
+        c.write_pixel(1, 0, Color::new(0., 0.505, 0.));
+        c.write_pixel(1, 1, Color::new(-0.5, 0., 1.));
+
+        let mut out = Vec::new();
+        write_ppm(&c, PpmFormat::Binary, &mut out).unwrap();
+
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(header.to_vec(), out[..header.len()].to_vec());
+        assert_eq!(
+            vec![255, 0, 0, 0, 128, 0, 0, 0, 0, 0, 0, 255],
+            out[header.len()..].to_vec()
+        );
+    }
+
+    #[test]
+    fn parsing_ppm_formats() {
+        assert_eq!(Ok(PpmFormat::Ascii), "p3".parse());
+        assert_eq!(Ok(PpmFormat::Binary), "p6".parse());
+        assert!("p5".parse::<PpmFormat>().is_err());
+    }
+
+    #[test]
+    fn reading_a_file_with_the_wrong_magic_number() {
+        let ppm = b"P32\n1 1\n255\n0 0 0\n";
+        let err = ppm_to_canvas(ppm).unwrap_err();
+        assert!(matches!(err, PpmError::UnknownFormat(m) if m == "P32"));
+    }
+
+    #[test]
+    fn reading_a_ppm_returns_a_canvas_of_the_right_size() {
+        let ppm = b"P3\n10 2\n255\n".to_vec();
+        let ppm = [ppm, "0 0 0\n".repeat(20).into_bytes()].concat();
+
+        let c = ppm_to_canvas(&ppm).unwrap();
+        assert_eq!(10, c.width);
+        assert_eq!(2, c.height);
+    }
+
+    #[test]
+    fn reading_pixel_data_from_a_ppm_file() {
+        let ppm = b"P3
+4 3
+255
+255 127 0  0 127 255  127 255 0  255 255 255
+0 0 0  255 0 0  0 255 0  0 0 255
+255 255 0  0 255 255  255 0 255  127 127 127
+";
+        let c = ppm_to_canvas(ppm).unwrap();
+        assert_eq!(Color::new(1., 0.49804, 0.), c.pixel_at(0, 0));
+        assert_eq!(Color::new(0., 0.49804, 1.), c.pixel_at(1, 0));
+        assert_eq!(Color::new(1., 0., 0.), c.pixel_at(1, 1));
+        assert_eq!(Color::new(0.49804, 0.49804, 0.49804), c.pixel_at(3, 2));
+    }
+
+    #[test]
+    fn ppm_parsing_ignores_comment_lines() {
+        let ppm = b"P3
+# this is a comment
+2 1
+# this, too
+255
+# another comment
+255 255 255
+# oh, no, comments in the pixel data!
+255 0 255
+";
+        let c = ppm_to_canvas(ppm).unwrap();
+        assert_eq!(WHITE, c.pixel_at(0, 0));
+        assert_eq!(Color::new(1., 0., 1.), c.pixel_at(1, 0));
+    }
+
+    #[test]
+    fn ppm_parsing_allows_an_rgb_triple_to_span_lines() {
+        let ppm = b"P3\n1 1\n255\n51\n153\n\n204\n";
+        let c = ppm_to_canvas(ppm).unwrap();
+        assert_eq!(Color::new(0.2, 0.6, 0.8), c.pixel_at(0, 0));
+    }
+
+    #[test]
+    fn ppm_parsing_respects_the_scale_setting() {
+        let ppm = b"P3\n2 2\n100\n100 100 100  50 50 50\n75 50 25  0 0 0\n";
+        let c = ppm_to_canvas(ppm).unwrap();
+        assert_eq!(Color::new(0.75, 0.5, 0.25), c.pixel_at(0, 1));
+    }
+
+    #[test]
+    fn reading_a_binary_ppm() {
+        let mut ppm = b"P6\n# comment\n2 1\n255\n".to_vec();
+        ppm.extend_from_slice(&[255, 0, 51, 0, 255, 10]);
+
+        let c = ppm_to_canvas(&ppm).unwrap();
+        assert_eq!(Color::new(1., 0., 0.2), c.pixel_at(0, 0));
+        assert!(approx_eq(10. / 255., c.pixel_at(1, 0).b));
+    }
+
+    #[test]
+    fn reading_a_16_bit_binary_ppm() {
+        let mut ppm = b"P6 1 1 65535\n".to_vec();
+        ppm.extend_from_slice(&[0xFF, 0xFF, 0x80, 0x00, 0x00, 0x00]);
+
+        let c = ppm_to_canvas(&ppm).unwrap();
+        assert_eq!(Color::new(1., 32768. / 65535., 0.), c.pixel_at(0, 0));
+    }
+
+    #[test]
+    fn a_written_binary_ppm_can_be_read_back() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, WHITE);
+        c.write_pixel(2, 1, BLUE);
+
+        let mut out = Vec::new();
+        write_ppm(&c, PpmFormat::Binary, &mut out).unwrap();
+        let read = read_ppm(&mut out.as_slice()).unwrap();
+
+        assert_eq!(WHITE, read.pixel_at(0, 0));
+        assert_eq!(BLUE, read.pixel_at(2, 1));
+        assert_eq!(BLACK, read.pixel_at(1, 1));
+    }
+
+    #[test]
+    fn reading_malformed_ppm_files() {
+        let err = ppm_to_canvas(b"P3\n2 x\n255\n").unwrap_err();
+        assert!(matches!(err, PpmError::InvalidHeader(_)));
+
+        let err = ppm_to_canvas(b"P3\n2 1\n0\n").unwrap_err();
+        assert!(matches!(err, PpmError::InvalidMaxValue(0)));
+
+        let err = ppm_to_canvas(b"P3\n2 1\n255\n0 0 0 0 300 0\n").unwrap_err();
+        assert!(matches!(err, PpmError::InvalidPixel { x: 1, y: 0, .. }));
+
+        let err = ppm_to_canvas(b"P3\n2 1\n255\n0 0 0 0\n").unwrap_err();
+        assert!(matches!(
+            err,
+            PpmError::MissingPixels {
+                expected: 6,
+                found: 4
+            }
+        ));
+
+        let err = ppm_to_canvas(b"P6\n2 1\n255\n\x00\x00").unwrap_err();
+        assert_eq!(
+            "Truncated PPM data: expected 6 color values, found 2",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn reading_a_header_larger_than_the_data() {
+        let err = ppm_to_canvas(b"P3\n100000 100000\n255\n1 2 3\n").unwrap_err();
+        assert!(matches!(
+            err,
+            PpmError::MissingPixels {
+                expected: 30_000_000_000,
+                found: 3
+            }
+        ));
+
+        let err = ppm_to_canvas(b"P6\n100000 100000\n255\n\x01\x02\x03").unwrap_err();
+        assert!(matches!(err, PpmError::MissingPixels { found: 3, .. }));
+
+        let err = ppm_to_canvas(b"P3\n4294967295 4294967295\n255\n").unwrap_err();
+        assert!(matches!(err, PpmError::InvalidHeader(_)));
+    }
 }
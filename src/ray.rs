@@ -1,10 +1,33 @@
 use super::matrix::Matrix;
 use super::tuple::Tuple;
+use super::utils::EPSILON;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Ray {
     pub origin: Tuple,
     pub direction: Tuple,
+    /// The rays through the next pixels, to estimate the area seen by the pixel
+    pub differentials: Option<RayDifferentials>,
+}
+
+/// The rays offset by one pixel to the right (x) and one pixel down (y).
+#[derive(Debug, Copy, Clone)]
+pub struct RayDifferentials {
+    pub x_origin: Tuple,
+    pub x_direction: Tuple,
+    pub y_origin: Tuple,
+    pub y_direction: Tuple,
+}
+
+/// The area of a surface seen by a pixel, as the offsets from the hit point to the
+/// points hit by the rays through the next pixels. Null when unknown.
+#[derive(Debug, Copy, Clone)]
+pub struct Footprint {
+    pub dpdx: Tuple,
+    pub dpdy: Tuple,
+    /// The rays through the next pixels miss the tangent plane, because they graze it
+    /// or hit it behind their origin: the pixel sees an unbounded area.
+    pub unbounded: bool,
 }
 
 impl Ray {
@@ -12,15 +35,89 @@ impl Ray {
         Ray {
             origin: *origin,
             direction: *direction,
+            differentials: None,
         }
     }
 
+    pub fn with_differentials(mut self, differentials: RayDifferentials) -> Ray {
+        self.differentials = Some(differentials);
+        self
+    }
+
     pub fn position(&self, t: f64) -> Tuple {
         self.origin + (self.direction * t)
     }
 
     pub fn transform(&self, m: Matrix) -> Ray {
-        Ray::new(&(m * self.origin), &(m * self.direction))
+        Ray {
+            origin: m * self.origin,
+            direction: m * self.direction,
+            differentials: self.differentials.map(|d| RayDifferentials {
+                x_origin: m * d.x_origin,
+                x_direction: m * d.x_direction,
+                y_origin: m * d.y_origin,
+                y_direction: m * d.y_direction,
+            }),
+        }
+    }
+
+    /// The footprint of the ray on the plane tangent to a surface at the hit point.
+    pub fn footprint(&self, point: &Tuple, normal: &Tuple) -> Footprint {
+        let Some(d) = self.differentials else {
+            return Footprint::default();
+        };
+        let offset = |origin: Tuple, direction: Tuple| {
+            let denominator = Tuple::dot_product(normal, &direction);
+            if denominator.abs() < EPSILON {
+                return None;
+            }
+            let t = Tuple::dot_product(normal, &(*point - origin)) / denominator;
+            (t >= 0.).then(|| origin + direction * t - *point)
+        };
+        match (
+            offset(d.x_origin, d.x_direction),
+            offset(d.y_origin, d.y_direction),
+        ) {
+            (Some(dpdx), Some(dpdy)) => Footprint {
+                dpdx,
+                dpdy,
+                unbounded: false,
+            },
+            _ => Footprint::unbounded(),
+        }
+    }
+}
+
+impl Footprint {
+    /// A footprint covering the whole surface, with null offsets.
+    pub fn unbounded() -> Footprint {
+        Footprint {
+            unbounded: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        !self.unbounded && self.dpdx.magnitude() == 0. && self.dpdy.magnitude() == 0.
+    }
+
+    /// The footprint seen through a transformation, like the inverse of a shape transformation.
+    pub fn transform(&self, m: Matrix) -> Footprint {
+        Footprint {
+            dpdx: m * self.dpdx,
+            dpdy: m * self.dpdy,
+            unbounded: self.unbounded,
+        }
+    }
+}
+
+impl Default for Footprint {
+    fn default() -> Self {
+        Footprint {
+            dpdx: Tuple::vector(0., 0., 0.),
+            dpdy: Tuple::vector(0., 0., 0.),
+            unbounded: false,
+        }
     }
 }
 
@@ -74,4 +171,96 @@ mod tests {
         assert_eq!(Tuple::point(2., 6., 12.), ray2.origin);
         assert_eq!(Tuple::vector(0., 3., 0.), ray2.direction);
     }
+
+    fn with_parallel_differentials(ray: Ray, spacing: f64) -> Ray {
+        ray.with_differentials(RayDifferentials {
+            x_origin: ray.origin + Tuple::vector(spacing, 0., 0.),
+            x_direction: ray.direction,
+            y_origin: ray.origin + Tuple::vector(0., -spacing, 0.),
+            y_direction: ray.direction,
+        })
+    }
+
+    #[test]
+    fn a_ray_has_no_differentials_by_default() {
+        let r = Ray::new(&Tuple::point(0., 0., 0.), &Tuple::vector(0., 0., 1.));
+
+        assert!(r.differentials.is_none());
+        assert!(
+            r.footprint(&Tuple::point(0., 0., 5.), &Tuple::vector(0., 0., -1.))
+                .is_null()
+        );
+    }
+
+    #[test]
+    fn the_footprint_of_a_ray_facing_a_surface() {
+        let r = with_parallel_differentials(
+            Ray::new(&Tuple::point(0., 0., -5.), &Tuple::vector(0., 0., 1.)),
+            0.1,
+        );
+
+        let f = r.footprint(&Tuple::point(0., 0., 0.), &Tuple::vector(0., 0., -1.));
+        assert_eq!(Tuple::vector(0.1, 0., 0.), f.dpdx);
+        assert_eq!(Tuple::vector(0., -0.1, 0.), f.dpdy);
+    }
+
+    #[test]
+    fn the_footprint_stretches_at_grazing_angles() {
+        // Looking down at a floor, two units forward for one unit down
+        let r = with_parallel_differentials(
+            Ray::new(
+                &Tuple::point(0., 1., -2.),
+                &Tuple::vector(0., -1., 2.).normalize(),
+            ),
+            0.1,
+        );
+
+        let f = r.footprint(&Tuple::point(0., 0., 0.), &Tuple::vector(0., 1., 0.));
+        assert_eq!(Tuple::vector(0.1, 0., 0.), f.dpdx);
+        assert_eq!(Tuple::vector(0., 0., -0.2), f.dpdy);
+    }
+
+    #[test]
+    fn the_footprint_is_unbounded_when_the_differentials_graze_the_surface() {
+        let r = with_parallel_differentials(
+            Ray::new(&Tuple::point(0., 1., -2.), &Tuple::vector(0., 0., 1.)),
+            0.1,
+        );
+
+        let f = r.footprint(&Tuple::point(0., 0., 0.), &Tuple::vector(0., 1., 0.));
+        assert!(f.unbounded);
+        assert!(!f.is_null());
+    }
+
+    #[test]
+    fn the_footprint_is_unbounded_when_the_differentials_hit_behind_their_origin() {
+        // The ray barely reaches the floor while the next row looks above the horizon
+        let r = Ray::new(
+            &Tuple::point(0., 1., 0.),
+            &Tuple::vector(0., -0.01, 1.).normalize(),
+        );
+        let r = r.with_differentials(RayDifferentials {
+            x_origin: r.origin,
+            x_direction: r.direction,
+            y_origin: r.origin,
+            y_direction: Tuple::vector(0., 0.01, 1.).normalize(),
+        });
+
+        let f = r.footprint(&Tuple::point(0., 0., 100.), &Tuple::vector(0., 1., 0.));
+        assert!(f.unbounded);
+    }
+
+    #[test]
+    fn transforming_the_differentials_of_a_ray() {
+        let r = with_parallel_differentials(
+            Ray::new(&Tuple::point(1., 2., 3.), &Tuple::vector(0., 1., 0.)),
+            0.5,
+        );
+
+        let r2 = r.transform(transform::scaling(2., 3., 4.));
+        let d = r2.differentials.unwrap();
+        assert_eq!(Tuple::point(3., 6., 12.), d.x_origin);
+        assert_eq!(Tuple::point(2., 4.5, 12.), d.y_origin);
+        assert_eq!(Tuple::vector(0., 3., 0.), d.y_direction);
+    }
 }
@@ -0,0 +1,146 @@
+use super::tuple::Tuple;
+
+use std::f64::consts::PI;
+
+/// A small, seedable pseudo-random number generator (xorshift64*).
+///
+/// The same seed always produces the same sequence, so renders are reproducible.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // The state must never be zero with xorshift
+        let state = splitmix64(seed);
+        Rng {
+            state: if state == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                state
+            },
+        }
+    }
+
+    /// A generator for one pixel, independent of the order in which pixels are rendered.
+    pub fn for_pixel(seed: u64, x: usize, y: usize) -> Rng {
+        Rng::new(seed ^ splitmix64(((y as u64) << 32) | x as u64))
+    }
+
+    /// A generator for one point in space, so that the same surface point is
+    /// always sampled the same way.
+    pub fn for_point(seed: u64, point: &Tuple) -> Rng {
+        let hash = splitmix64(point.x.to_bits())
+            ^ splitmix64(point.y.to_bits()).rotate_left(21)
+            ^ splitmix64(point.z.to_bits()).rotate_left(42);
+        Rng::new(seed ^ hash)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A uniformly distributed number in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Two unit vectors perpendicular to the normal and to each other.
+pub fn orthonormal_basis(&normal: &Tuple) -> (Tuple, Tuple) {
+    let helper = if normal.x.abs() > 0.9 {
+        Tuple::vector(0., 1., 0.)
+    } else {
+        Tuple::vector(1., 0., 0.)
+    };
+    let tangent = Tuple::cross_product(&helper, &normal).normalize();
+    let bitangent = Tuple::cross_product(&normal, &tangent);
+    (tangent, bitangent)
+}
+
+/// A direction in the hemisphere around the normal, where directions close
+/// to the normal are more likely (the probability is cos(theta) / PI).
+pub fn cosine_weighted_hemisphere(normal: &Tuple, rng: &mut Rng) -> Tuple {
+    let r = rng.next_f64().sqrt();
+    let phi = 2. * PI * rng.next_f64();
+    let (x, y) = (r * phi.cos(), r * phi.sin());
+    let z = (1. - r * r).max(0.).sqrt();
+
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    (tangent * x + bitangent * y + *normal * z).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::approx_eq;
+
+    #[test]
+    fn the_same_seed_gives_the_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn random_numbers_are_between_zero_and_one() {
+        let mut rng = Rng::new(0);
+        for _ in 0..1000 {
+            let n = rng.next_f64();
+            assert!((0. ..1.).contains(&n));
+        }
+    }
+
+    #[test]
+    fn pixel_generators_are_independent_of_each_other() {
+        let a = Rng::for_pixel(7, 1, 0).next_u64();
+        let b = Rng::for_pixel(7, 0, 1).next_u64();
+        assert_ne!(a, b);
+        assert_eq!(a, Rng::for_pixel(7, 1, 0).next_u64());
+    }
+
+    #[test]
+    fn point_generators_depend_only_on_the_point() {
+        let p = Tuple::point(1., 2., 3.);
+        let a = Rng::for_point(0, &p).next_u64();
+        assert_eq!(a, Rng::for_point(0, &p).next_u64());
+        assert_ne!(a, Rng::for_point(0, &Tuple::point(1., 3., 2.)).next_u64());
+    }
+
+    #[test]
+    fn an_orthonormal_basis_is_perpendicular_to_the_normal() {
+        let n = Tuple::vector(1., 2., 3.).normalize();
+        let (t, b) = orthonormal_basis(&n);
+
+        assert!(Tuple::dot_product(&t, &n).abs() < 1e-9);
+        assert!(Tuple::dot_product(&b, &n).abs() < 1e-9);
+        assert!(Tuple::dot_product(&t, &b).abs() < 1e-9);
+        assert!(approx_eq(1., b.magnitude()));
+    }
+
+    #[test]
+    fn hemisphere_samples_are_on_the_side_of_the_normal() {
+        let n = Tuple::vector(0., 1., 0.);
+        let mut rng = Rng::new(3);
+        for _ in 0..100 {
+            let v = cosine_weighted_hemisphere(&n, &mut rng);
+            assert!(v.is_vector());
+            assert!(Tuple::dot_product(&v, &n) >= 0.);
+            assert!(approx_eq(1., v.magnitude()));
+        }
+    }
+}
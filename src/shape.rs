@@ -2,6 +2,7 @@ use super::intersection::Intersections;
 use super::material::Material;
 use super::matrix::Matrix;
 use super::ray::Ray;
+use super::sampling::orthonormal_basis;
 use super::tuple::Tuple;
 
 use std::any::Any;
@@ -19,6 +20,25 @@ pub trait Shape: fmt::Debug {
         Tuple::vector(world_normal.x, world_normal.y, world_normal.z).normalize()
     }
 
+    /// The tangent and the bitangent at a world point: unit vectors perpendicular to the
+    /// normal and to each other, along which the u and v texture coordinates increase.
+    fn tangents_at(&self, &world_point: &Tuple) -> (Tuple, Tuple) {
+        let local_point = self.transformation().inverse() * world_point;
+        let (tangent, bitangent) = self.local_tangents_at(&local_point);
+        let normal = self.normal_at(&world_point);
+
+        // Tangents lie along the surface, so they transform like its points. The
+        // transformation can skew them, Gram-Schmidt makes the frame orthonormal again.
+        let tangent = self.transformation() * tangent;
+        let tangent = (tangent - normal * Tuple::dot_product(&tangent, &normal)).normalize();
+        let bitangent = self.transformation() * bitangent;
+        let bitangent = (bitangent
+            - normal * Tuple::dot_product(&bitangent, &normal)
+            - tangent * Tuple::dot_product(&bitangent, &tangent))
+        .normalize();
+        (tangent, bitangent)
+    }
+
     fn intersect(&self, r: Ray) -> Intersections {
         let r = r.transform(self.transformation().inverse());
         self.local_intersect(&r)
@@ -31,6 +51,12 @@ pub trait Shape: fmt::Debug {
     // Non-public API
     fn local_intersect(&self, r: &Ray) -> Intersections;
     fn local_normal_at(&self, local_point: &Tuple) -> Tuple;
+
+    /// The tangent and the bitangent in object space. By default, any two directions
+    /// perpendicular to the normal: shapes with a natural UV mapping follow it instead.
+    fn local_tangents_at(&self, local_point: &Tuple) -> (Tuple, Tuple) {
+        orthonormal_basis(&self.local_normal_at(local_point).normalize())
+    }
 }
 
 pub type BoxShape = Box<dyn Shape>;
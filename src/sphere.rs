@@ -4,6 +4,7 @@ use super::matrix::Matrix;
 use super::ray::Ray;
 use super::shape::{BoxShape, Shape};
 use super::tuple::{POINT_ORIGIN, Tuple};
+use super::utils::EPSILON;
 
 use std::any::Any;
 
@@ -73,6 +74,17 @@ impl Shape for Sphere {
         local_point - self.origin
     }
 
+    fn local_tangents_at(&self, &local_point: &Tuple) -> (Tuple, Tuple) {
+        // Along the parallels and the meridians, in the directions of the spherical mapping
+        let normal = (local_point - self.origin).normalize();
+        let tangent = if normal.x.abs() < EPSILON && normal.z.abs() < EPSILON {
+            Tuple::vector(1., 0., 0.) // Any direction at the poles
+        } else {
+            Tuple::vector(-normal.z, 0., normal.x).normalize()
+        };
+        (tangent, Tuple::cross_product(&tangent, &normal))
+    }
+
     fn local_intersect(&self, r: &Ray) -> Intersections {
         let sphere_to_ray = r.origin - self.origin;
         let a = Tuple::dot_product(&r.direction, &r.direction);
@@ -102,7 +114,49 @@ mod tests {
     use super::*;
     use crate::color::WHITE;
     use crate::matrix::MATRIX_IDENTITY;
+    use crate::texture::UvMapping;
     use crate::transform;
+    use crate::utils::approx_eq;
+
+    #[test]
+    fn the_tangents_of_a_sphere() {
+        let s = Sphere::default();
+
+        assert_eq!(
+            (Tuple::vector(1., 0., 0.), Tuple::vector(0., 1., 0.)),
+            s.local_tangents_at(&Tuple::point(0., 0., -1.))
+        );
+        assert_eq!(
+            (Tuple::vector(0., 0., 1.), Tuple::vector(0., 1., 0.)),
+            s.local_tangents_at(&Tuple::point(1., 0., 0.))
+        );
+    }
+
+    #[test]
+    fn the_tangents_of_a_sphere_follow_the_spherical_mapping() {
+        let s = Sphere::default();
+        let delta = 1e-4;
+
+        for point in [
+            Tuple::point(0.3, 0.5, -0.81240384),
+            Tuple::point(-0.6, -0.48, 0.64),
+            Tuple::point(0., 1., 0.),
+        ] {
+            let normal = s.normal_at(&point);
+            let (tangent, bitangent) = s.tangents_at(&point);
+            assert!(approx_eq(0., Tuple::dot_product(&tangent, &normal)));
+            assert!(approx_eq(0., Tuple::dot_product(&bitangent, &normal)));
+            assert!(approx_eq(0., Tuple::dot_product(&tangent, &bitangent)));
+
+            if point.y < 1. {
+                let (u, v) = UvMapping::Spherical.map(&point);
+                let (u_along, _) = UvMapping::Spherical.map(&(point + tangent * delta));
+                let (_, v_along) = UvMapping::Spherical.map(&(point + bitangent * delta));
+                assert!(u_along > u);
+                assert!(v_along > v);
+            }
+        }
+    }
 
     #[test]
     fn a_ray_intersects_a_sphere_at_two_points() {
@@ -248,6 +302,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn the_normal_on_a_translated_sphere() {
         let t = transform::translation(0., 1., 0.);
         let s = Sphere::new_boxed(Some(t), None);
@@ -263,7 +318,7 @@ mod tests {
         let r = transform::rotation_z(std::f64::consts::PI / 5.);
         let t = transform::transforms(&[r, s]);
         let s = Sphere::new_boxed(Some(t), None);
-        let p = Tuple::point(0., 2f64.sqrt() / 2., -1. * 2f64.sqrt() / 2.);
+        let p = Tuple::point(0., 2f64.sqrt() / 2., -2f64.sqrt() / 2.);
 
         let n = Tuple::vector(0., 0.97014, -0.24254);
         assert_eq!(n, s.normal_at(&p));
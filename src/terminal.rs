@@ -0,0 +1,137 @@
+use super::canvas::Canvas;
+use super::color::BLACK;
+
+use std::env;
+use std::io::{self, Write};
+
+// Preview of a canvas in a terminal supporting 24-bit colors.
+// Each character shows two pixels with the upper half block: the top pixel is the
+// foreground color and the bottom pixel the background color.
+
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+const DEFAULT_COLUMNS: usize = 80;
+
+/// The width of the terminal in characters, from the COLUMNS environment variable.
+pub fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.trim().parse::<usize>().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(DEFAULT_COLUMNS)
+}
+
+/// Writes the canvas with ANSI escape codes, shrunk to at most `max_width` characters.
+/// Smaller canvases are never enlarged.
+pub fn write_ansi<W: Write>(canvas: &Canvas, max_width: usize, out: &mut W) -> io::Result<()> {
+    let preview = if canvas.width > max_width && max_width > 0 {
+        let height = (canvas.height * max_width).div_ceil(canvas.width);
+        downscale(canvas, max_width, height.max(1))
+    } else {
+        canvas.clone()
+    };
+
+    let mut line = String::new();
+    for y in (0..preview.height).step_by(2) {
+        line.clear();
+        for x in 0..preview.width {
+            let (r, g, b) = preview.pixel_at(x, y).as_rgb255();
+            line.push_str(&format!("\x1b[38;2;{r};{g};{b}m"));
+            if y + 1 < preview.height {
+                let (r, g, b) = preview.pixel_at(x, y + 1).as_rgb255();
+                line.push_str(&format!("\x1b[48;2;{r};{g};{b}m"));
+            } else {
+                // No pixel below the last row of an odd height canvas
+                line.push_str("\x1b[49m");
+            }
+            line.push(UPPER_HALF_BLOCK);
+        }
+        line.push_str("\x1b[0m\n");
+        out.write_all(line.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Shrinks the canvas by averaging the pixels covered by each new pixel.
+fn downscale(canvas: &Canvas, width: usize, height: usize) -> Canvas {
+    let mut result = Canvas::new(width, height);
+    for y in 0..height {
+        let (y0, y1) = span(y, height, canvas.height);
+        for x in 0..width {
+            let (x0, x1) = span(x, width, canvas.width);
+            let mut sum = BLACK;
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    sum = sum + canvas.pixel_at(sx, sy);
+                }
+            }
+            result.write_pixel(x, y, sum * (1. / ((x1 - x0) * (y1 - y0)) as f64));
+        }
+    }
+    result
+}
+
+/// The source pixels covered by a pixel of the smaller image, at least one.
+fn span(i: usize, size: usize, source_size: usize) -> (usize, usize) {
+    let start = i * source_size / size;
+    let end = ((i + 1) * source_size / size).max(start + 1);
+    (start, end.min(source_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BLUE, Color, RED, WHITE};
+
+    fn ansi(canvas: &Canvas, max_width: usize) -> String {
+        let mut out = Vec::new();
+        write_ansi(canvas, max_width, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn each_character_shows_two_pixels() {
+        let mut c = Canvas::new(2, 2);
+        c.write_pixel(0, 0, RED);
+        c.write_pixel(0, 1, BLUE);
+        c.write_pixel(1, 0, WHITE);
+
+        assert_eq!(
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m\u{2580}\
+             \x1b[38;2;255;255;255m\x1b[48;2;0;0;0m\u{2580}\x1b[0m\n",
+            ansi(&c, 80)
+        );
+    }
+
+    #[test]
+    fn the_last_row_of_an_odd_height_has_no_background() {
+        let c = Canvas::new_filled(1, 3, Color::new(0.5, 1.5, -1.));
+
+        let output = ansi(&c, 80);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(2, lines.len());
+        assert_eq!("\x1b[38;2;127;255;0m\x1b[49m\u{2580}\x1b[0m", lines[1]);
+    }
+
+    #[test]
+    fn wide_canvases_are_shrunk_to_the_terminal() {
+        let c = Canvas::new_filled(100, 40, WHITE);
+
+        let output = ansi(&c, 50);
+        let lines: Vec<&str> = output.lines().collect();
+        // 50x20 pixels, two rows per line
+        assert_eq!(10, lines.len());
+        assert_eq!(50, lines[0].matches(UPPER_HALF_BLOCK).count());
+    }
+
+    #[test]
+    fn shrinking_averages_the_pixels() {
+        let mut c = Canvas::new(4, 2);
+        c.write_pixel(0, 0, WHITE);
+        c.write_pixel(3, 1, RED);
+
+        let small = downscale(&c, 2, 1);
+        assert_eq!(WHITE * 0.25, small.pixel_at(0, 0));
+        assert_eq!(RED * 0.25, small.pixel_at(1, 0));
+    }
+}
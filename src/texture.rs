@@ -0,0 +1,1348 @@
+use super::canvas::{Addressing, Canvas, Interpolation};
+use super::color::Color;
+use super::matrix::Matrix;
+use super::pattern::{BoxPattern, Pattern};
+use super::ray::Footprint;
+use super::shape::BoxShape;
+use super::tuple::Tuple;
+use super::utils::EPSILON;
+
+use std::any::Any;
+use std::f64::consts::PI;
+use std::fmt;
+
+// Texture mapping: 2D patterns wrapped around shapes.
+// A mapping turns a point in pattern space into (u, v) coordinates in [0, 1], and a
+// UV pattern gives the color at these coordinates.
+// The cube mappings first project the point on the [-1, 1] cube from its center, so
+// they also work on other shapes, like a sphere around the scene for a skybox.
+
+/// How a point is projected on the 2D texture.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum UvMapping {
+    /// Longitude and latitude on the unit sphere
+    #[default]
+    Spherical,
+    /// The xz plane, repeated every unit
+    Planar,
+    /// Around the y axis, repeated every unit of height
+    Cylindrical,
+    /// Each face of the [-1, 1] cube, with the same texture on the six faces
+    Cube,
+}
+
+impl UvMapping {
+    pub fn map(&self, point: &Tuple) -> (f64, f64) {
+        match self {
+            UvMapping::Spherical => spherical_map(point),
+            UvMapping::Planar => planar_map(point),
+            UvMapping::Cylindrical => cylindrical_map(point),
+            UvMapping::Cube => {
+                let point = project_on_cube(point);
+                CubeFace::from_point(&point).map(&point)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for UvMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spherical" | "sphere" => Ok(UvMapping::Spherical),
+            "planar" | "plane" => Ok(UvMapping::Planar),
+            "cylindrical" | "cylinder" => Ok(UvMapping::Cylindrical),
+            "cube" => Ok(UvMapping::Cube),
+            _ => Err(format!("Unknown UV mapping '{s}'")),
+        }
+    }
+}
+
+fn spherical_map(point: &Tuple) -> (f64, f64) {
+    // The azimuthal angle, in (-π, π], and the polar angle, in [0, π]
+    let theta = point.x.atan2(point.z);
+    let radius = Tuple::vector(point.x, point.y, point.z).magnitude();
+    let phi = (point.y / radius).acos();
+
+    // Counterclockwise when seen from above
+    let u = 1. - (theta / (2. * PI) + 0.5);
+    let v = 1. - phi / PI;
+    (u, v)
+}
+
+fn planar_map(point: &Tuple) -> (f64, f64) {
+    (point.x.rem_euclid(1.), point.z.rem_euclid(1.))
+}
+
+fn cylindrical_map(point: &Tuple) -> (f64, f64) {
+    let theta = point.x.atan2(point.z);
+    let u = 1. - (theta / (2. * PI) + 0.5);
+    (u, point.y.rem_euclid(1.))
+}
+
+/// The point on the surface of the [-1, 1] cube in the direction of the point.
+fn project_on_cube(point: &Tuple) -> Tuple {
+    let coord = point.x.abs().max(point.y.abs()).max(point.z.abs());
+    if coord == 0. {
+        return *point;
+    }
+    Tuple::point(point.x / coord, point.y / coord, point.z / coord)
+}
+
+/// The faces of the [-1, 1] cube.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CubeFace {
+    Left,
+    Right,
+    Front,
+    Back,
+    Up,
+    Down,
+}
+
+impl CubeFace {
+    /// The faces in the order of the textures of a `CubeMapPattern`.
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::Left,
+        CubeFace::Right,
+        CubeFace::Front,
+        CubeFace::Back,
+        CubeFace::Up,
+        CubeFace::Down,
+    ];
+
+    /// The face along the largest coordinate of the point.
+    pub fn from_point(point: &Tuple) -> CubeFace {
+        let coord = point.x.abs().max(point.y.abs()).max(point.z.abs());
+        if coord == point.x {
+            CubeFace::Right
+        } else if coord == -point.x {
+            CubeFace::Left
+        } else if coord == point.y {
+            CubeFace::Up
+        } else if coord == -point.y {
+            CubeFace::Down
+        } else if coord == point.z {
+            CubeFace::Front
+        } else {
+            CubeFace::Back
+        }
+    }
+
+    /// The (u, v) coordinates of a point on this face, seen from outside of the cube.
+    pub fn map(&self, point: &Tuple) -> (f64, f64) {
+        let wrap = |v: f64| v.rem_euclid(2.) / 2.;
+        match self {
+            CubeFace::Front => (wrap(point.x + 1.), wrap(point.y + 1.)),
+            CubeFace::Back => (wrap(1. - point.x), wrap(point.y + 1.)),
+            CubeFace::Left => (wrap(point.z + 1.), wrap(point.y + 1.)),
+            CubeFace::Right => (wrap(1. - point.z), wrap(point.y + 1.)),
+            CubeFace::Up => (wrap(point.x + 1.), wrap(1. - point.z)),
+            CubeFace::Down => (wrap(point.x + 1.), wrap(point.z + 1.)),
+        }
+    }
+}
+
+/// A color that varies over a 2D texture, with `u` and `v` in [0, 1].
+///
+/// Like `Pattern`, this trait can be implemented outside of this crate.
+pub trait UvPattern: fmt::Debug {
+    fn uv_pattern_at(&self, u: f64, v: f64) -> Color;
+
+    /// The color averaged over a square of about `width` by `width` around (u, v).
+    /// By default, the color at (u, v).
+    fn filtered_uv_pattern_at(&self, u: f64, v: f64, _width: f64) -> Color {
+        self.uv_pattern_at(u, v)
+    }
+
+    fn box_clone(&self) -> BoxUvPattern;
+    fn box_eq(&self, other: &dyn Any) -> bool;
+    fn as_any(&self) -> &dyn Any;
+}
+
+pub type BoxUvPattern = Box<dyn UvPattern>;
+
+impl Clone for BoxUvPattern {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+impl PartialEq for BoxUvPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.box_eq(other.as_any())
+    }
+}
+
+/// Checkers with `width` squares along u and `height` squares along v.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UvCheckers {
+    pub width: f64,
+    pub height: f64,
+    pub first: Color,
+    pub second: Color,
+}
+
+impl UvCheckers {
+    pub fn new(width: f64, height: f64, first: Color, second: Color) -> Self {
+        Self {
+            width,
+            height,
+            first,
+            second,
+        }
+    }
+
+    pub fn new_boxed(width: f64, height: f64, first: Color, second: Color) -> BoxUvPattern {
+        Box::new(UvCheckers::new(width, height, first, second))
+    }
+}
+
+impl UvPattern for UvCheckers {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxUvPattern {
+        Box::new(*self)
+    }
+
+    fn uv_pattern_at(&self, u: f64, v: f64) -> Color {
+        let u2 = (u * self.width).floor();
+        let v2 = (v * self.height).floor();
+        if (u2 + v2).rem_euclid(2.) == 0. {
+            self.first
+        } else {
+            self.second
+        }
+    }
+}
+
+/// A main color with a different color in each corner, to check the orientation of a mapping.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UvAlignCheck {
+    pub main: Color,
+    pub upper_left: Color,
+    pub upper_right: Color,
+    pub bottom_left: Color,
+    pub bottom_right: Color,
+}
+
+impl UvAlignCheck {
+    pub fn new(
+        main: Color,
+        upper_left: Color,
+        upper_right: Color,
+        bottom_left: Color,
+        bottom_right: Color,
+    ) -> Self {
+        Self {
+            main,
+            upper_left,
+            upper_right,
+            bottom_left,
+            bottom_right,
+        }
+    }
+
+    pub fn new_boxed(
+        main: Color,
+        upper_left: Color,
+        upper_right: Color,
+        bottom_left: Color,
+        bottom_right: Color,
+    ) -> BoxUvPattern {
+        Box::new(UvAlignCheck::new(
+            main,
+            upper_left,
+            upper_right,
+            bottom_left,
+            bottom_right,
+        ))
+    }
+}
+
+impl UvPattern for UvAlignCheck {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxUvPattern {
+        Box::new(*self)
+    }
+
+    fn uv_pattern_at(&self, u: f64, v: f64) -> Color {
+        // Remember: v = 0 is at the bottom, v = 1 at the top
+        if v > 0.8 {
+            if u < 0.2 {
+                return self.upper_left;
+            }
+            if u > 0.8 {
+                return self.upper_right;
+            }
+        } else if v < 0.2 {
+            if u < 0.2 {
+                return self.bottom_left;
+            }
+            if u > 0.8 {
+                return self.bottom_right;
+            }
+        }
+        self.main
+    }
+}
+
+/// Copies of an image, each one half the size of the previous one, down to a single pixel.
+/// Distant textures are sampled in the smaller copies, where the pixels are already averaged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mipmap {
+    levels: Vec<Canvas>,
+}
+
+impl Mipmap {
+    pub fn new(image: &Canvas) -> Mipmap {
+        let mut levels = vec![image.clone()];
+        while let Some(last) = levels.last()
+            && (last.width > 1 || last.height > 1)
+        {
+            let (width, height) = ((last.width / 2).max(1), (last.height / 2).max(1));
+            // Halving with bilinear interpolation averages each 2x2 block
+            levels.push(last.resize(width, height, Interpolation::Bilinear));
+        }
+        Mipmap { levels }
+    }
+
+    /// The full size image first.
+    pub fn levels(&self) -> &[Canvas] {
+        &self.levels
+    }
+
+    /// The color averaged over about `width` of the texture, interpolated between the
+    /// two levels with the closest pixel sizes.
+    pub fn sample(
+        &self,
+        u: f64,
+        v: f64,
+        width: f64,
+        interpolation: Interpolation,
+        addressing: Addressing,
+    ) -> Color {
+        let base = &self.levels[0];
+        let pixels = width * base.width.max(base.height) as f64;
+        let level = pixels.max(1.).log2().min((self.levels.len() - 1) as f64);
+
+        let lower = level.floor() as usize;
+        let t = level - lower as f64;
+        let color = sample_image(&self.levels[lower], u, v, interpolation, addressing);
+        if t == 0. {
+            return color;
+        }
+        let upper = sample_image(&self.levels[lower + 1], u, v, interpolation, addressing);
+        color * (1. - t) + upper * t
+    }
+}
+
+fn sample_image(
+    image: &Canvas,
+    u: f64,
+    v: f64,
+    interpolation: Interpolation,
+    addressing: Addressing,
+) -> Color {
+    // The rows of a canvas go from top to bottom
+    let x = u * image.width as f64;
+    let y = (1. - v) * image.height as f64;
+    image.sample_addressed(x, y, interpolation, addressing)
+}
+
+/// An image wrapped around a shape, with (0, 0) at the bottom left corner of the image.
+///
+/// With a mipmap, filtered lookups blend the two closest mipmap levels. Along with
+/// bilinear interpolation inside each level, that is trilinear filtering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageTexture {
+    pub image: Canvas,
+    pub interpolation: Interpolation,
+    pub addressing: Addressing,
+    pub mipmap: Option<Mipmap>,
+}
+
+impl ImageTexture {
+    pub fn new(image: Canvas, interpolation: Interpolation, addressing: Addressing) -> Self {
+        Self {
+            image,
+            interpolation,
+            addressing,
+            mipmap: None,
+        }
+    }
+
+    /// Builds the mipmap of the image, to filter distant textures.
+    pub fn with_mipmap(mut self) -> Self {
+        self.mipmap = Some(Mipmap::new(&self.image));
+        self
+    }
+
+    pub fn new_boxed(
+        image: Canvas,
+        interpolation: Interpolation,
+        addressing: Addressing,
+    ) -> BoxUvPattern {
+        Box::new(ImageTexture::new(image, interpolation, addressing))
+    }
+}
+
+impl UvPattern for ImageTexture {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxUvPattern {
+        Box::new(self.clone())
+    }
+
+    fn uv_pattern_at(&self, u: f64, v: f64) -> Color {
+        sample_image(&self.image, u, v, self.interpolation, self.addressing)
+    }
+
+    fn filtered_uv_pattern_at(&self, u: f64, v: f64, width: f64) -> Color {
+        match &self.mipmap {
+            Some(mipmap) => mipmap.sample(u, v, width, self.interpolation, self.addressing),
+            None => self.uv_pattern_at(u, v),
+        }
+    }
+}
+
+/// A 2D pattern wrapped around a shape with a UV mapping.
+#[derive(Debug, Clone)]
+pub struct TextureMapPattern {
+    pub uv_pattern: BoxUvPattern,
+    pub mapping: UvMapping,
+    pub transform: Matrix,
+}
+
+// Not derived: the derive cannot compare a boxed trait object field
+impl PartialEq for TextureMapPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.uv_pattern.eq(&other.uv_pattern)
+            && self.mapping == other.mapping
+            && self.transform == other.transform
+    }
+}
+
+impl TextureMapPattern {
+    pub fn new(uv_pattern: BoxUvPattern, mapping: UvMapping, transform: Option<Matrix>) -> Self {
+        Self {
+            uv_pattern,
+            mapping,
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(
+        uv_pattern: BoxUvPattern,
+        mapping: UvMapping,
+        transform: Option<Matrix>,
+    ) -> BoxPattern {
+        Box::new(TextureMapPattern::new(uv_pattern, mapping, transform))
+    }
+}
+
+impl Pattern for TextureMapPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        let (u, v) = self.mapping.map(point);
+        self.uv_pattern.uv_pattern_at(u, v)
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        let (u, v) = self.mapping.map(point);
+        let width = footprint_width(|p| self.mapping.map(p), point, footprint);
+        self.uv_pattern.filtered_uv_pattern_at(u, v, width)
+    }
+}
+
+/// The width of a footprint in a texture: the distance from the point to where the next
+/// pixels hit, across the seams, once mapped to (u, v). Infinite when unbounded.
+fn footprint_width(
+    map: impl Fn(&Tuple) -> (f64, f64),
+    point: &Tuple,
+    footprint: &Footprint,
+) -> f64 {
+    if footprint.unbounded {
+        return f64::INFINITY;
+    }
+    let (u, v) = map(point);
+    let delta = |a: f64, b: f64| {
+        let d = (a - b).abs();
+        d.min(1. - d)
+    };
+    let distance = |offset: &Tuple| {
+        let (u2, v2) = map(&(*point + *offset));
+        delta(u, u2).hypot(delta(v, v2))
+    };
+    distance(&footprint.dpdx).max(distance(&footprint.dpdy))
+}
+
+/// A different 2D pattern on each face of the [-1, 1] cube.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeMapPattern {
+    /// The patterns in the order of `CubeFace::ALL`: left, right, front, back, up, down
+    pub faces: [BoxUvPattern; 6],
+    pub transform: Matrix,
+}
+
+impl CubeMapPattern {
+    pub fn new(faces: [BoxUvPattern; 6], transform: Option<Matrix>) -> Self {
+        Self {
+            faces,
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    pub fn new_boxed(faces: [BoxUvPattern; 6], transform: Option<Matrix>) -> BoxPattern {
+        Box::new(CubeMapPattern::new(faces, transform))
+    }
+
+    /// A skybox from six images in the order of `CubeFace::ALL`, each as seen from the
+    /// center of the cube. Put it on a large shape around the scene, seen from inside.
+    pub fn skybox(
+        images: [Canvas; 6],
+        interpolation: Interpolation,
+        transform: Option<Matrix>,
+    ) -> Self {
+        let faces = images.map(|mut image| {
+            // The faces are mapped as seen from outside of the cube
+            image.flip_horizontal();
+            ImageTexture::new_boxed(image, interpolation, Addressing::Clamp)
+        });
+        CubeMapPattern::new(faces, transform)
+    }
+
+    pub fn face(&self, face: CubeFace) -> &BoxUvPattern {
+        &self.faces[face as usize]
+    }
+}
+
+impl Pattern for CubeMapPattern {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn box_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<Self>() == Some(self)
+    }
+
+    fn box_clone(&self) -> BoxPattern {
+        Box::new(self.clone())
+    }
+
+    fn transformation(&self) -> Matrix {
+        self.transform
+    }
+
+    fn pattern_at(&self, point: &Tuple) -> Color {
+        let point = project_on_cube(point);
+        let face = CubeFace::from_point(&point);
+        let (u, v) = face.map(&point);
+        self.face(face).uv_pattern_at(u, v)
+    }
+
+    fn filtered_pattern_at(&self, point: &Tuple, footprint: &Footprint) -> Color {
+        let projected = project_on_cube(point);
+        let face = CubeFace::from_point(&projected);
+        let (u, v) = face.map(&projected);
+        // The next pixels are measured on the same face, even past its edges
+        let width = footprint_width(|p| face.map(&project_on_cube(p)), point, footprint);
+        self.face(face).filtered_uv_pattern_at(u, v, width)
+    }
+}
+
+/// Normals read from a texture, usually an RGB image, in the tangent frame of the shape:
+/// red along the tangent, green along the bitangent and blue along the normal, with each
+/// channel mapping [0, 1] to [-1, 1].
+///
+/// The tangent frame follows the natural mapping of the shape, spherical for a sphere
+/// and planar for a plane, so the texture should use the same one.
+#[derive(Debug, Clone)]
+pub struct NormalMap {
+    pub uv_pattern: BoxUvPattern,
+    pub mapping: UvMapping,
+    /// How much the normals of the texture tilt the surface: 0 keeps it flat
+    pub strength: f64,
+    pub transform: Matrix,
+}
+
+impl PartialEq for NormalMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.uv_pattern.eq(&other.uv_pattern)
+            && self.mapping == other.mapping
+            && self.strength == other.strength
+            && self.transform == other.transform
+    }
+}
+
+impl NormalMap {
+    pub fn new(uv_pattern: BoxUvPattern, mapping: UvMapping, transform: Option<Matrix>) -> Self {
+        Self {
+            uv_pattern,
+            mapping,
+            strength: 1.,
+            transform: transform.unwrap_or_default(),
+        }
+    }
+
+    /// A normal map from an image, interpolated and repeated over the surface.
+    pub fn from_image(image: Canvas, mapping: UvMapping, transform: Option<Matrix>) -> Self {
+        let texture = ImageTexture::new_boxed(image, Interpolation::Bilinear, Addressing::Wrap);
+        NormalMap::new(texture, mapping, transform)
+    }
+
+    /// The normal at a world point on the given shape, whose geometric normal is given.
+    pub fn normal_at(&self, object: &BoxShape, point: &Tuple, normal: &Tuple) -> Tuple {
+        let object_point = object.transformation().inverse() * *point;
+        let (u, v) = self.mapping.map(&(self.transform.inverse() * object_point));
+        let color = self.uv_pattern.uv_pattern_at(u, v);
+
+        let (tangent, bitangent) = object.tangents_at(point);
+        let x = (2. * color.r - 1.) * self.strength;
+        let y = (2. * color.g - 1.) * self.strength;
+        let z = 2. * color.b - 1.;
+        let mapped = tangent * x + bitangent * y + *normal * z;
+        // A mid-grey texel encodes no direction at all
+        if mapped.magnitude() < EPSILON {
+            return *normal;
+        }
+        mapped.normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::color::{BLACK, BLUE, GREEN, RED, WHITE};
+    use crate::comps::Comps;
+    use crate::intersection::Intersection;
+    use crate::light::PointLight;
+    use crate::material::Material;
+    use crate::pattern::CheckersPattern;
+    use crate::plane::Plane;
+    use crate::ray::Ray;
+    use crate::sphere::Sphere;
+    use crate::transform;
+    use crate::transformation::view_transform;
+    use crate::utils::{EPSILON, approx_eq};
+    use crate::world::World;
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    const YELLOW: Color = Color {
+        r: 1.,
+        g: 1.,
+        b: 0.,
+    };
+    const BROWN: Color = Color {
+        r: 1.,
+        g: 0.5,
+        b: 0.,
+    };
+    const CYAN: Color = Color {
+        r: 0.,
+        g: 1.,
+        b: 1.,
+    };
+    const PURPLE: Color = Color {
+        r: 1.,
+        g: 0.,
+        b: 1.,
+    };
+
+    fn assert_uv(expected: (f64, f64), actual: (f64, f64)) {
+        assert!(
+            approx_eq(expected.0, actual.0) && approx_eq(expected.1, actual.1),
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    #[test]
+    fn checker_pattern_in_2d() {
+        let checkers = UvCheckers::new(2., 2., BLACK, WHITE);
+
+        assert_eq!(BLACK, checkers.uv_pattern_at(0., 0.));
+        assert_eq!(WHITE, checkers.uv_pattern_at(0.5, 0.));
+        assert_eq!(WHITE, checkers.uv_pattern_at(0., 0.5));
+        assert_eq!(BLACK, checkers.uv_pattern_at(0.5, 0.5));
+        assert_eq!(BLACK, checkers.uv_pattern_at(1., 1.));
+    }
+
+    #[test]
+    fn using_a_spherical_mapping_on_a_3d_point() {
+        let cases = [
+            (Tuple::point(0., 0., -1.), (0., 0.5)),
+            (Tuple::point(1., 0., 0.), (0.25, 0.5)),
+            (Tuple::point(0., 0., 1.), (0.5, 0.5)),
+            (Tuple::point(-1., 0., 0.), (0.75, 0.5)),
+            (Tuple::point(0., 1., 0.), (0.5, 1.)),
+            (Tuple::point(0., -1., 0.), (0.5, 0.)),
+            (
+                Tuple::point(2f64.sqrt() / 2., 2f64.sqrt() / 2., 0.),
+                (0.25, 0.75),
+            ),
+        ];
+
+        for (point, uv) in cases {
+            assert_uv(uv, UvMapping::Spherical.map(&point));
+        }
+    }
+
+    #[test]
+    fn using_a_texture_map_pattern_with_a_spherical_map() {
+        let checkers = UvCheckers::new_boxed(16., 8., BLACK, WHITE);
+        let pattern = TextureMapPattern::new(checkers, UvMapping::Spherical, None);
+        let cases = [
+            (Tuple::point(0.4315, 0.467, 0.7719), WHITE),
+            (Tuple::point(-0.9654, 0.2552, -0.0534), BLACK),
+            (Tuple::point(0.1039, 0.709, 0.6975), WHITE),
+            (Tuple::point(-0.4986, -0.7856, -0.3663), BLACK),
+            (Tuple::point(-0.0317, -0.9395, 0.3411), BLACK),
+            (Tuple::point(0.4809, -0.7721, 0.4154), BLACK),
+            (Tuple::point(0.0285, -0.9612, -0.2745), BLACK),
+            (Tuple::point(-0.5734, -0.2162, -0.7903), WHITE),
+            (Tuple::point(0.7688, -0.147, 0.6223), BLACK),
+            (Tuple::point(-0.7652, 0.2175, 0.6060), BLACK),
+        ];
+
+        for (point, color) in cases {
+            assert_eq!(color, pattern.pattern_at(&point));
+        }
+    }
+
+    #[test]
+    fn using_a_planar_mapping_on_a_3d_point() {
+        let cases = [
+            (Tuple::point(0.25, 0., 0.5), (0.25, 0.5)),
+            (Tuple::point(0.25, 0., -0.25), (0.25, 0.75)),
+            (Tuple::point(0.25, 0.5, -0.25), (0.25, 0.75)),
+            (Tuple::point(1.25, 0., 0.5), (0.25, 0.5)),
+            (Tuple::point(0.25, 0., -1.75), (0.25, 0.25)),
+            (Tuple::point(1., 0., -1.), (0., 0.)),
+            (Tuple::point(0., 0., 0.), (0., 0.)),
+        ];
+
+        for (point, uv) in cases {
+            assert_uv(uv, UvMapping::Planar.map(&point));
+        }
+    }
+
+    #[test]
+    fn using_a_cylindrical_mapping_on_a_3d_point() {
+        let cases = [
+            (Tuple::point(0., 0., -1.), (0., 0.)),
+            (Tuple::point(0., 0.5, -1.), (0., 0.5)),
+            (Tuple::point(0., 1., -1.), (0., 0.)),
+            (
+                Tuple::point(FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2),
+                (0.125, 0.5),
+            ),
+            (Tuple::point(1., 0.5, 0.), (0.25, 0.5)),
+            (
+                Tuple::point(FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2),
+                (0.375, 0.5),
+            ),
+            (Tuple::point(0., -0.25, 1.), (0.5, 0.75)),
+            (
+                Tuple::point(-FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2),
+                (0.625, 0.5),
+            ),
+            (Tuple::point(-1., 1.25, 0.), (0.75, 0.25)),
+            (
+                Tuple::point(-FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2),
+                (0.875, 0.5),
+            ),
+        ];
+
+        for (point, uv) in cases {
+            assert_uv(uv, UvMapping::Cylindrical.map(&point));
+        }
+    }
+
+    #[test]
+    fn layout_of_the_align_check_pattern() {
+        let main = WHITE;
+        let pattern = UvAlignCheck::new(main, RED, YELLOW, GREEN, CYAN);
+        let cases = [
+            (0.5, 0.5, main),
+            (0.1, 0.9, RED),
+            (0.9, 0.9, YELLOW),
+            (0.1, 0.1, GREEN),
+            (0.9, 0.1, CYAN),
+        ];
+
+        for (u, v, color) in cases {
+            assert_eq!(color, pattern.uv_pattern_at(u, v));
+        }
+    }
+
+    #[test]
+    fn identifying_the_face_of_a_cube_from_a_point() {
+        let cases = [
+            (Tuple::point(-1., 0.5, -0.25), CubeFace::Left),
+            (Tuple::point(1.1, -0.75, 0.8), CubeFace::Right),
+            (Tuple::point(0.1, 0.6, 0.9), CubeFace::Front),
+            (Tuple::point(-0.7, 0., -2.), CubeFace::Back),
+            (Tuple::point(0.5, 1., 0.9), CubeFace::Up),
+            (Tuple::point(-0.2, -1.3, 1.1), CubeFace::Down),
+        ];
+
+        for (point, face) in cases {
+            assert_eq!(face, CubeFace::from_point(&point));
+        }
+    }
+
+    #[test]
+    fn uv_mapping_the_faces_of_a_cube() {
+        let cases = [
+            (CubeFace::Front, Tuple::point(-0.5, 0.5, 1.), (0.25, 0.75)),
+            (CubeFace::Front, Tuple::point(0.5, -0.5, 1.), (0.75, 0.25)),
+            (CubeFace::Back, Tuple::point(0.5, 0.5, -1.), (0.25, 0.75)),
+            (CubeFace::Back, Tuple::point(-0.5, -0.5, -1.), (0.75, 0.25)),
+            (CubeFace::Left, Tuple::point(-1., 0.5, -0.5), (0.25, 0.75)),
+            (CubeFace::Left, Tuple::point(-1., -0.5, 0.5), (0.75, 0.25)),
+            (CubeFace::Right, Tuple::point(1., 0.5, 0.5), (0.25, 0.75)),
+            (CubeFace::Right, Tuple::point(1., -0.5, -0.5), (0.75, 0.25)),
+            (CubeFace::Up, Tuple::point(-0.5, 1., -0.5), (0.25, 0.75)),
+            (CubeFace::Up, Tuple::point(0.5, 1., 0.5), (0.75, 0.25)),
+            (CubeFace::Down, Tuple::point(-0.5, -1., 0.5), (0.25, 0.75)),
+            (CubeFace::Down, Tuple::point(0.5, -1., -0.5), (0.75, 0.25)),
+        ];
+
+        for (face, point, uv) in cases {
+            assert_uv(uv, face.map(&point));
+        }
+    }
+
+    #[test]
+    fn finding_the_colors_on_a_mapped_cube() {
+        let left = UvAlignCheck::new_boxed(YELLOW, CYAN, RED, BLUE, BROWN);
+        let front = UvAlignCheck::new_boxed(CYAN, RED, YELLOW, BROWN, GREEN);
+        let right = UvAlignCheck::new_boxed(RED, YELLOW, PURPLE, GREEN, WHITE);
+        let back = UvAlignCheck::new_boxed(GREEN, PURPLE, CYAN, WHITE, BLUE);
+        let up = UvAlignCheck::new_boxed(BROWN, CYAN, PURPLE, RED, YELLOW);
+        let down = UvAlignCheck::new_boxed(PURPLE, BROWN, GREEN, BLUE, WHITE);
+        let pattern = CubeMapPattern::new([left, right, front, back, up, down], None);
+        let cases = [
+            // Left
+            (Tuple::point(-1., 0., 0.), YELLOW),
+            (Tuple::point(-1., 0.9, -0.9), CYAN),
+            (Tuple::point(-1., 0.9, 0.9), RED),
+            (Tuple::point(-1., -0.9, -0.9), BLUE),
+            (Tuple::point(-1., -0.9, 0.9), BROWN),
+            // Front
+            (Tuple::point(0., 0., 1.), CYAN),
+            (Tuple::point(-0.9, 0.9, 1.), RED),
+            (Tuple::point(0.9, 0.9, 1.), YELLOW),
+            (Tuple::point(-0.9, -0.9, 1.), BROWN),
+            (Tuple::point(0.9, -0.9, 1.), GREEN),
+            // Right
+            (Tuple::point(1., 0., 0.), RED),
+            (Tuple::point(1., 0.9, 0.9), YELLOW),
+            (Tuple::point(1., 0.9, -0.9), PURPLE),
+            (Tuple::point(1., -0.9, 0.9), GREEN),
+            (Tuple::point(1., -0.9, -0.9), WHITE),
+            // Back
+            (Tuple::point(0., 0., -1.), GREEN),
+            (Tuple::point(0.9, 0.9, -1.), PURPLE),
+            (Tuple::point(-0.9, 0.9, -1.), CYAN),
+            (Tuple::point(0.9, -0.9, -1.), WHITE),
+            (Tuple::point(-0.9, -0.9, -1.), BLUE),
+            // Up
+            (Tuple::point(0., 1., 0.), BROWN),
+            (Tuple::point(-0.9, 1., -0.9), CYAN),
+            (Tuple::point(0.9, 1., -0.9), PURPLE),
+            (Tuple::point(-0.9, 1., 0.9), RED),
+            (Tuple::point(0.9, 1., 0.9), YELLOW),
+            // Down
+            (Tuple::point(0., -1., 0.), PURPLE),
+            (Tuple::point(-0.9, -1., 0.9), BROWN),
+            (Tuple::point(0.9, -1., 0.9), GREEN),
+            (Tuple::point(-0.9, -1., -0.9), BLUE),
+            (Tuple::point(0.9, -1., -0.9), WHITE),
+        ];
+
+        for (point, color) in cases {
+            assert_eq!(color, pattern.pattern_at(&point), "at {point:?}");
+        }
+    }
+
+    #[test]
+    fn selecting_the_mapping_of_a_material_pattern() {
+        let checkers = UvCheckers::new_boxed(4., 2., BLACK, WHITE);
+        let m = Material {
+            pattern: Some(TextureMapPattern::new_boxed(
+                checkers,
+                UvMapping::Planar,
+                None,
+            )),
+            ..Default::default()
+        };
+        let s = Sphere::new_boxed(Some(transform::translation(0., 2., 0.)), Some(m.clone()));
+
+        // The object transformation applies before the mapping
+        assert_eq!(BLACK, m.color_at(&s, &Tuple::point(0.1, 3., 0.1)));
+        assert_eq!(WHITE, m.color_at(&s, &Tuple::point(0.3, 3., 0.1)));
+        assert_eq!(WHITE, m.color_at(&s, &Tuple::point(0.1, 3., 0.6)));
+    }
+
+    #[test]
+    fn uv_checkers_are_even_on_a_sphere_unlike_solid_checkers() {
+        let s = Sphere::default_boxed();
+        let uv = TextureMapPattern::new(
+            UvCheckers::new_boxed(16., 8., BLACK, WHITE),
+            UvMapping::Spherical,
+            None,
+        );
+        let solid = CheckersPattern::new(BLACK, WHITE, Some(transform::scaling(0.25, 0.25, 0.25)));
+
+        // Walking around the equator crosses 16 squares, wherever the sphere is sampled
+        let crossings = |p: &dyn Pattern| {
+            let colors: Vec<Color> = (0..720)
+                .map(|i| {
+                    let angle = (i as f64 + 0.5) * PI / 360.;
+                    p.pattern_at_shape(&s, &Tuple::point(angle.sin(), 0.01, angle.cos()))
+                })
+                .collect();
+            colors.windows(2).filter(|pair| pair[0] != pair[1]).count()
+        };
+        assert_eq!(15, crossings(&uv));
+        assert_ne!(15, crossings(&solid));
+    }
+
+    /// A 2x2 image: red and green on top, blue and white at the bottom.
+    fn four_colors() -> Canvas {
+        let mut image = Canvas::new(2, 2);
+        image.write_pixel(0, 0, RED);
+        image.write_pixel(1, 0, GREEN);
+        image.write_pixel(0, 1, BLUE);
+        image.write_pixel(1, 1, WHITE);
+        image
+    }
+
+    #[test]
+    fn sampling_an_image_texture() {
+        let texture = ImageTexture::new(four_colors(), Interpolation::Nearest, Addressing::Wrap);
+
+        assert_eq!(RED, texture.uv_pattern_at(0.25, 0.75));
+        assert_eq!(GREEN, texture.uv_pattern_at(0.75, 0.75));
+        assert_eq!(BLUE, texture.uv_pattern_at(0.25, 0.25));
+        assert_eq!(WHITE, texture.uv_pattern_at(0.75, 0.25));
+    }
+
+    #[test]
+    fn bilinear_filtering_of_an_image_texture() {
+        let texture = ImageTexture::new(four_colors(), Interpolation::Bilinear, Addressing::Clamp);
+
+        assert_eq!(Color::new(0.5, 0.5, 0.5), texture.uv_pattern_at(0.5, 0.5));
+        assert_eq!(Color::new(0.5, 0.5, 0.), texture.uv_pattern_at(0.5, 1.));
+        assert_eq!(RED, texture.uv_pattern_at(0., 1.));
+    }
+
+    #[test]
+    fn addressing_an_image_texture_outside_of_its_edges() {
+        let at = |addressing, u| {
+            ImageTexture::new(four_colors(), Interpolation::Nearest, addressing)
+                .uv_pattern_at(u, 0.75)
+        };
+
+        assert_eq!(GREEN, at(Addressing::Wrap, -0.25));
+        assert_eq!(RED, at(Addressing::Clamp, -0.25));
+        assert_eq!(RED, at(Addressing::Mirror, -0.25));
+        assert_eq!(GREEN, at(Addressing::Wrap, 1.75));
+        assert_eq!(GREEN, at(Addressing::Clamp, 1.75));
+        assert_eq!(RED, at(Addressing::Mirror, 1.75));
+    }
+
+    #[test]
+    fn an_image_texture_with_a_spherical_mapping() {
+        let texture =
+            ImageTexture::new_boxed(four_colors(), Interpolation::Nearest, Addressing::Clamp);
+        let m = Material {
+            pattern: Some(TextureMapPattern::new_boxed(
+                texture,
+                UvMapping::Spherical,
+                None,
+            )),
+            ..Default::default()
+        };
+        let s = Sphere::new_boxed(None, Some(m.clone()));
+
+        // The top of the image is at the north pole, its left edge behind the sphere
+        assert_eq!(GREEN, m.color_at(&s, &Tuple::point(0., 1., 0.)));
+        assert_eq!(WHITE, m.color_at(&s, &Tuple::point(0., -1., 0.)));
+        assert_eq!(RED, m.color_at(&s, &Tuple::point(0.7, 0.7, -0.1)));
+        assert_eq!(WHITE, m.color_at(&s, &Tuple::point(-0.7, -0.7, 0.1)));
+    }
+
+    #[test]
+    fn a_skybox_on_a_large_sphere() {
+        // Red on the left and blue on the right, seen from the center
+        let mut front = Canvas::new_filled(2, 1, BLUE);
+        front.write_pixel(0, 0, RED);
+        let solid = |color| Canvas::new_filled(2, 1, color);
+        let images = [
+            solid(BROWN),
+            solid(CYAN),
+            front,
+            solid(PURPLE),
+            solid(WHITE),
+            solid(YELLOW),
+        ];
+        let skybox = CubeMapPattern::skybox(images, Interpolation::Nearest, None);
+        let s = Sphere::new_boxed(Some(transform::scaling(50., 50., 50.)), None);
+
+        // Looking toward +z with y up, -x is on the right
+        assert_eq!(
+            RED,
+            skybox.pattern_at_shape(&s, &Tuple::point(10., 0., 50.))
+        );
+        assert_eq!(
+            BLUE,
+            skybox.pattern_at_shape(&s, &Tuple::point(-10., 0., 50.))
+        );
+        assert_eq!(
+            BROWN,
+            skybox.pattern_at_shape(&s, &Tuple::point(-50., 1., 0.))
+        );
+        assert_eq!(
+            CYAN,
+            skybox.pattern_at_shape(&s, &Tuple::point(50., 0., -3.))
+        );
+        assert_eq!(
+            PURPLE,
+            skybox.pattern_at_shape(&s, &Tuple::point(2., 0., -50.))
+        );
+        assert_eq!(
+            WHITE,
+            skybox.pattern_at_shape(&s, &Tuple::point(0., 50., 0.))
+        );
+        assert_eq!(
+            YELLOW,
+            skybox.pattern_at_shape(&s, &Tuple::point(0., -50., 0.))
+        );
+    }
+
+    #[test]
+    fn a_cube_mapping_on_a_sphere() {
+        let pattern = TextureMapPattern::new(
+            UvAlignCheck::new_boxed(WHITE, RED, YELLOW, GREEN, CYAN),
+            UvMapping::Cube,
+            None,
+        );
+        let diagonal = 1. / 3f64.sqrt();
+
+        // The corners of the front face are on the diagonals of the cube
+        assert_eq!(WHITE, pattern.pattern_at(&Tuple::point(0., 0., 1.)));
+        assert_eq!(
+            YELLOW,
+            pattern.pattern_at(&Tuple::point(diagonal * 0.95, diagonal * 0.95, diagonal))
+        );
+    }
+
+    #[test]
+    fn building_a_mipmap() {
+        let mut image = Canvas::new(4, 2);
+        image.fill_rect(&image.region(), WHITE);
+        image.write_pixel(0, 0, BLACK);
+        image.write_pixel(1, 0, BLACK);
+
+        let mipmap = Mipmap::new(&image);
+        let sizes: Vec<(usize, usize)> = mipmap
+            .levels()
+            .iter()
+            .map(|level| (level.width, level.height))
+            .collect();
+        assert_eq!(vec![(4, 2), (2, 1), (1, 1)], sizes);
+        assert_eq!(Color::new(0.5, 0.5, 0.5), mipmap.levels()[1].pixel_at(0, 0));
+        assert_eq!(WHITE, mipmap.levels()[1].pixel_at(1, 0));
+        assert_eq!(
+            Color::new(0.75, 0.75, 0.75),
+            mipmap.levels()[2].pixel_at(0, 0)
+        );
+    }
+
+    #[test]
+    fn the_mipmap_level_follows_the_footprint() {
+        let mipmap = Mipmap::new(&four_colors());
+        let sample =
+            |width| mipmap.sample(0.25, 0.75, width, Interpolation::Nearest, Addressing::Wrap);
+        let average = Color::new(0.5, 0.5, 0.5);
+
+        // Smaller than a pixel, then the whole texture
+        assert_eq!(RED, sample(0.1));
+        assert_eq!(average, sample(1.));
+        assert_eq!(average, sample(100.));
+        // Between the two levels
+        let t = 1.5f64.log2();
+        assert_eq!(RED * (1. - t) + average * t, sample(0.75));
+    }
+
+    #[test]
+    fn filtered_lookups_need_a_mipmap() {
+        let texture = ImageTexture::new(four_colors(), Interpolation::Nearest, Addressing::Wrap);
+
+        assert_eq!(RED, texture.filtered_uv_pattern_at(0.25, 0.75, 1.));
+        assert_eq!(
+            Color::new(0.5, 0.5, 0.5),
+            texture.with_mipmap().filtered_uv_pattern_at(0.25, 0.75, 1.)
+        );
+    }
+
+    #[test]
+    fn an_unbounded_footprint_samples_the_coarsest_level() {
+        let texture = ImageTexture::new(four_colors(), Interpolation::Nearest, Addressing::Wrap);
+        let pattern =
+            TextureMapPattern::new(Box::new(texture.with_mipmap()), UvMapping::Planar, None);
+        let point = Tuple::point(0.25, 0., 0.25);
+
+        assert_eq!(
+            Color::new(0.5, 0.5, 0.5),
+            pattern.filtered_pattern_at(&point, &Footprint::unbounded())
+        );
+    }
+
+    #[test]
+    fn nested_textures_are_filtered() {
+        let texture = ImageTexture::new(four_colors(), Interpolation::Nearest, Addressing::Wrap);
+        let nested =
+            TextureMapPattern::new_boxed(Box::new(texture.with_mipmap()), UvMapping::Planar, None);
+        let checkers = CheckersPattern::new(nested.clone(), BLACK, None);
+        let point = Tuple::point(0.25, 0., 0.25);
+
+        assert_eq!(
+            nested.pattern_at(&point),
+            checkers.filtered_pattern_at(&point, &Footprint::default())
+        );
+        assert_eq!(
+            Color::new(0.5, 0.5, 0.5),
+            checkers.filtered_pattern_at(&point, &Footprint::unbounded())
+        );
+    }
+
+    #[test]
+    fn cube_maps_are_filtered() {
+        let texture = ImageTexture::new(four_colors(), Interpolation::Nearest, Addressing::Wrap);
+        let faces: [BoxUvPattern; 6] =
+            std::array::from_fn(|_| texture.clone().with_mipmap().box_clone());
+        let cube = CubeMapPattern::new(faces, None);
+        let point = Tuple::point(0.25, 0.25, -1.);
+
+        assert_eq!(
+            Color::new(0.5, 0.5, 0.5),
+            cube.filtered_pattern_at(&point, &Footprint::unbounded())
+        );
+    }
+
+    /// A floor of tiny checkers receding toward the horizon.
+    fn render_floor(texture: ImageTexture) -> Canvas {
+        let floor = Plane::new_boxed(
+            None,
+            Some(Material {
+                pattern: Some(TextureMapPattern::new_boxed(
+                    Box::new(texture),
+                    UvMapping::Planar,
+                    None,
+                )),
+                ambient: 1.,
+                diffuse: 0.,
+                specular: 0.,
+                ..Default::default()
+            }),
+        );
+        let light = PointLight::new(&Tuple::point(0., 10., 0.), &WHITE);
+        let w = World::new(Some(light), vec![floor]);
+        let c = Camera::new(
+            32,
+            16,
+            PI / 3.,
+            Some(view_transform(
+                &Tuple::point(0., 1., 0.),
+                &Tuple::point(0., 0.9, 10.),
+                &Tuple::vector(0., 1., 0.),
+            )),
+        );
+        c.render(&w)
+    }
+
+    #[test]
+    fn mipmaps_remove_aliasing_toward_the_horizon() {
+        let mut checkers = Canvas::new(64, 64);
+        for y in 0..64 {
+            for x in (y % 2..64).step_by(2) {
+                checkers.write_pixel(x, y, WHITE);
+            }
+        }
+        let texture = ImageTexture::new(checkers, Interpolation::Nearest, Addressing::Wrap);
+
+        // Distance from the average grey of the distant rows
+        let error = |image: &Canvas| {
+            let distant: Vec<f64> = (9..11)
+                .flat_map(|y| (0..image.width).map(move |x| (x, y)))
+                .map(|(x, y)| (image.pixel_at(x, y).r - 0.5).abs())
+                .collect();
+            distant.iter().sum::<f64>() / distant.len() as f64
+        };
+        let aliased = render_floor(texture.clone());
+        let filtered = render_floor(texture.with_mipmap());
+
+        // Each distant pixel shows a single random checker, or their average
+        let row: Vec<Color> = (0..32).map(|x| aliased.pixel_at(x, 9)).collect();
+        assert!(row.contains(&WHITE) && row.contains(&BLACK));
+        assert!(error(&aliased) > 0.4);
+        assert!(error(&filtered) < 0.05);
+    }
+
+    #[test]
+    fn parsing_uv_mappings() {
+        assert_eq!(Ok(UvMapping::Cylindrical), "cylindrical".parse());
+        assert_eq!(Ok(UvMapping::Cube), "cube".parse());
+        assert!("torus".parse::<UvMapping>().is_err());
+    }
+
+    #[test]
+    fn an_empty_image_texture_is_black() {
+        let texture =
+            ImageTexture::new(Canvas::new(0, 0), Interpolation::Bilinear, Addressing::Wrap)
+                .with_mipmap();
+
+        assert_eq!(BLACK, texture.uv_pattern_at(0.3, 0.7));
+        assert_eq!(BLACK, texture.filtered_uv_pattern_at(0.3, 0.7, 0.1));
+    }
+
+    fn flat_color_normal_map(color: Color, mapping: UvMapping) -> NormalMap {
+        NormalMap::from_image(Canvas::new_filled(1, 1, color), mapping, None)
+    }
+
+    #[test]
+    fn a_flat_normal_map_keeps_the_normals() {
+        let map = flat_color_normal_map(Color::new(0.5, 0.5, 1.), UvMapping::Spherical);
+        let s = Sphere::default_boxed();
+
+        for point in [
+            Tuple::point(0., 0., -1.),
+            Tuple::point(0.6, 0.8, 0.),
+            Tuple::point(0., -1., 0.),
+        ] {
+            let normal = s.normal_at(&point);
+            assert_eq!(normal, map.normal_at(&s, &point, &normal));
+        }
+    }
+
+    #[test]
+    fn a_null_normal_keeps_the_geometric_normal() {
+        let floor = Plane::default_boxed();
+        let up = Tuple::vector(0., 1., 0.);
+        let point = Tuple::point(2.5, 0., 0.5);
+
+        let grey = flat_color_normal_map(Color::new(0.5, 0.5, 0.5), UvMapping::Planar);
+        assert_eq!(up, grey.normal_at(&floor, &point, &up));
+        let flattened = NormalMap {
+            strength: 0.,
+            ..flat_color_normal_map(Color::new(1., 0., 0.5), UvMapping::Planar)
+        };
+        assert_eq!(up, flattened.normal_at(&floor, &point, &up));
+    }
+
+    #[test]
+    fn a_normal_map_is_read_in_the_tangent_frame() {
+        let floor = Plane::default_boxed();
+        let up = Tuple::vector(0., 1., 0.);
+        let toward_tangent = flat_color_normal_map(Color::new(1., 0.5, 1.), UvMapping::Planar);
+        assert_eq!(
+            Tuple::vector(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.),
+            toward_tangent.normal_at(&floor, &Tuple::point(2.5, 0., 0.5), &up)
+        );
+
+        let s = Sphere::default_boxed();
+        let front = Tuple::vector(0., 0., -1.);
+        let toward_bitangent = flat_color_normal_map(Color::new(0.5, 1., 1.), UvMapping::Spherical);
+        assert_eq!(
+            Tuple::vector(0., FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+            toward_bitangent.normal_at(&s, &Tuple::point(0., 0., -1.), &front)
+        );
+    }
+
+    #[test]
+    fn the_strength_of_a_normal_map() {
+        let floor = Plane::default_boxed();
+        let point = Tuple::point(0.5, 0., 0.5);
+        let up = Tuple::vector(0., 1., 0.);
+        let map = |strength| NormalMap {
+            strength,
+            ..flat_color_normal_map(Color::new(1., 0.5, 1.), UvMapping::Planar)
+        };
+
+        assert_eq!(up, map(0.).normal_at(&floor, &point, &up));
+        assert_eq!(
+            Tuple::vector(0.5, 1., 0.).normalize(),
+            map(0.5).normal_at(&floor, &point, &up)
+        );
+    }
+
+    #[test]
+    fn a_normal_map_follows_the_image() {
+        // Tilted toward +x on the left half of the image, toward -x on the right half
+        let mut image = Canvas::new(2, 1);
+        image.write_pixel(0, 0, Color::new(1., 0.5, 1.));
+        image.write_pixel(1, 0, Color::new(0., 0.5, 1.));
+        let map = NormalMap {
+            uv_pattern: ImageTexture::new_boxed(image, Interpolation::Nearest, Addressing::Wrap),
+            ..flat_color_normal_map(WHITE, UvMapping::Planar)
+        };
+        let floor = Plane::default_boxed();
+        let up = Tuple::vector(0., 1., 0.);
+
+        assert!(map.normal_at(&floor, &Tuple::point(0.25, 0., 0.5), &up).x > 0.);
+        assert!(map.normal_at(&floor, &Tuple::point(0.75, 0., 0.5), &up).x < 0.);
+    }
+
+    #[test]
+    fn shading_with_a_normal_map() {
+        let map = flat_color_normal_map(Color::new(1., 0.5, 1.), UvMapping::Planar);
+        let floor = Plane::new_boxed(None, Some(Material::default().with_normal_map(map)));
+        let r = Ray::new(&Tuple::point(0.5, 1., 0.5), &Tuple::vector(0., -1., 0.));
+        let i = Intersection::new(1., floor);
+
+        let comps = Comps::prepare_computations(&i, &r, None);
+        assert_eq!(
+            Tuple::vector(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.),
+            comps.normalv
+        );
+        assert_eq!(Tuple::point(0.5, EPSILON, 0.5), comps.over_point);
+    }
+}
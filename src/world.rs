@@ -1,5 +1,6 @@
-use super::color::{BLACK, Color, WHITE};
+use super::color::{Color, WHITE};
 use super::comps::Comps;
+use super::integrator::Whitted;
 use super::intersection::{Intersection, Intersections};
 use super::light::PointLight;
 use super::material::Material;
@@ -31,87 +32,27 @@ impl World {
         Intersections::new(intersections)
     }
 
+    /// Shades a hit with the default (Whitted) integrator.
     pub fn shade_hit(&self, comps: &Comps, remaining: u8) -> Color {
-        if self.light.is_some() && remaining > 0 {
-            let material = comps.object.material();
-            let surface = material.lighting(
-                &comps.object,
-                &self.light.unwrap(),
-                &comps.point,
-                &comps.eyev,
-                &comps.normalv,
-                self.is_shadowed(&comps.over_point),
-            );
-            let reflected = self.reflected_color(comps, remaining - 1);
-            let refracted = self.refracted_color(comps, remaining - 1);
-
-            if material.reflective > 0. && material.transparency > 0. {
-                let reflectance = schlick(comps);
-                return surface + reflected * reflectance + refracted * (1. - reflectance);
-            }
-            surface + reflected + refracted
-        } else {
-            BLACK
-        }
+        Whitted::default().shade_hit(self, comps, remaining)
     }
 
+    /// The color seen along a ray with the default (Whitted) integrator.
     pub fn color_at(&self, r: &Ray, remaining: u8) -> Color {
-        let i = self.intersect(r);
-        match i.hit() {
-            None => BLACK,
-            Some(h) => {
-                let comps = Comps::prepare_computations(h, r, Some(i.clone()));
-                self.shade_hit(&comps, remaining)
-            }
-        }
+        Whitted::default().trace(self, r, remaining)
     }
 
     pub fn reflected_color(&self, comps: &Comps, remaining: u8) -> Color {
-        if comps.object.material().reflective == 0. || remaining == 0 {
-            return BLACK;
-        }
-        let reflect_ray = Ray::new(&comps.over_point, &comps.reflectv);
-        let color = self.color_at(&reflect_ray, remaining);
-        color * comps.object.material().reflective
+        Whitted::default().reflected_color(self, comps, remaining)
     }
 
     pub fn refracted_color(&self, comps: &Comps, remaining: u8) -> Color {
-        if comps.object.material().transparency == 0. || remaining == 0 {
-            return BLACK;
-        }
-
-        // Find the ratio of first index of refraction to the second.
-        // This is inverted from the definition of Snell's Law.
-        let n_ratio = comps.n1 / comps.n2;
-
-        // cos(theta_i) is the same as the dot product of the two vectors
-        let cos_i = Tuple::dot_product(&comps.eyev, &comps.normalv);
-
-        // Find sin(theta_t)^2 via trigonometric identity
-        let sin2_t = n_ratio * n_ratio * (1. - (cos_i * cos_i));
-        if sin2_t > 1. {
-            return BLACK; // Total internal reflection
-        }
-
-        // Find cos(theta_t) via trigonometric identity
-        let cos_t = (1.0 - sin2_t).sqrt();
-
-        // Compute the direction of the refracted ray
-        let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
-
-        // Create the refracted ray
-        let refracted_ray = Ray::new(&comps.under_point, &direction);
-
-        // Find the color at the refracted ray
-        // making sure to multiply by the transparency value to account for any opacity
-        self.color_at(&refracted_ray, remaining - 1) * comps.object.material().transparency
+        Whitted::default().refracted_color(self, comps, remaining)
     }
 
     pub fn is_shadowed(&self, &point: &Tuple) -> bool {
-        if self.light.is_none() {
-            false
-        } else {
-            let v = self.light.unwrap().position - point;
+        if let Some(light) = self.light {
+            let v = light.position - point;
             let distance = v.magnitude();
             let direction = v.normalize();
 
@@ -119,6 +60,8 @@ impl World {
             let intersections = self.intersect(&r);
             let h = intersections.hit();
             h.is_some() && h.unwrap().t < distance
+        } else {
+            false
         }
     }
 }
@@ -147,7 +90,8 @@ impl Default for World {
     }
 }
 
-fn schlick(comps: &Comps) -> f64 {
+/// Approximates the fraction of the light that is reflected (Fresnel effect).
+pub fn schlick(comps: &Comps) -> f64 {
     // Find the cosine of the angle between the eye and normal vectors
     let mut cos = Tuple::dot_product(&comps.eyev, &comps.normalv);
 
@@ -175,11 +119,7 @@ mod tests {
     use std::f64::consts::SQRT_2;
 
     use super::*;
-    use crate::{
-        pattern::{PatternType, TestPattern},
-        plane::Plane,
-        utils::approx_eq,
-    };
+    use crate::{color::BLACK, pattern::TestPattern, plane::Plane, utils::approx_eq};
 
     #[test]
     fn creating_a_world() {
@@ -303,6 +243,9 @@ mod tests {
             reflective: w.shapes[0].material().reflective,
             transparency: 0.,
             refractive_index: 1.,
+            channel_maps: Vec::new(),
+            normal_map: None,
+            bump: None,
         };
         w.shapes.first_mut().unwrap().set_material(material.clone());
         w.shapes.last_mut().unwrap().set_material(material);
@@ -317,7 +260,7 @@ mod tests {
         let w = World::default();
         let p = Tuple::point(0., 10., 0.);
 
-        assert_eq!(false, w.is_shadowed(&p));
+        assert!(!w.is_shadowed(&p));
     }
 
     #[test]
@@ -325,7 +268,7 @@ mod tests {
         let w = World::default();
         let p = Tuple::point(10., -10., 10.);
 
-        assert_eq!(true, w.is_shadowed(&p));
+        assert!(w.is_shadowed(&p));
     }
 
     #[test]
@@ -333,7 +276,7 @@ mod tests {
         let w = World::default();
         let p = Tuple::point(-20., 20., -20.);
 
-        assert_eq!(false, w.is_shadowed(&p));
+        assert!(!w.is_shadowed(&p));
     }
 
     #[test]
@@ -341,14 +284,16 @@ mod tests {
         let w = World::default();
         let p = Tuple::point(-2., 2., -2.);
 
-        assert_eq!(false, w.is_shadowed(&p));
+        assert!(!w.is_shadowed(&p));
     }
 
     #[test]
     fn the_reflected_color_for_a_nonreflective_material() {
         let light = PointLight::new(&Tuple::point(-10., 10., -10.), &WHITE);
-        let mut material = Material::default();
-        material.ambient = 1.;
+        let material = Material {
+            ambient: 1.,
+            ..Default::default()
+        };
         let s = Sphere::new_boxed(Some(transform::scaling(0.5, 0.5, 0.5)), Some(material));
         let w = World::new(Some(light), vec![s.clone()]);
 
@@ -376,8 +321,10 @@ mod tests {
         let s1 = Sphere::new_boxed(None, Some(m1));
         let s2 = Sphere::new_boxed(Some(transform::scaling(0.5, 0.5, 0.5)), None);
 
-        let mut m3 = Material::default();
-        m3.reflective = 0.5;
+        let m3 = Material {
+            reflective: 0.5,
+            ..Default::default()
+        };
         let plane = Plane::new_boxed(Some(transform::translation(0., -1., 0.)), Some(m3));
 
         let w = World::new(Some(light), vec![plane, s1, s2]);
@@ -410,8 +357,10 @@ mod tests {
         let s1 = Sphere::new_boxed(None, Some(m1));
         let s2 = Sphere::new_boxed(Some(transform::scaling(0.5, 0.5, 0.5)), None);
 
-        let mut m3 = Material::default();
-        m3.reflective = 0.5;
+        let m3 = Material {
+            reflective: 0.5,
+            ..Default::default()
+        };
         let plane = Plane::new_boxed(Some(transform::translation(0., -1., 0.)), Some(m3));
 
         let w = World::new(Some(light), vec![plane, s1, s2]);
@@ -474,8 +423,10 @@ mod tests {
         let s1 = Sphere::new_boxed(None, Some(m1));
         let s2 = Sphere::new_boxed(Some(transform::scaling(0.5, 0.5, 0.5)), None);
 
-        let mut m3 = Material::default();
-        m3.reflective = 0.5;
+        let m3 = Material {
+            reflective: 0.5,
+            ..Default::default()
+        };
         let plane = Plane::new_boxed(Some(transform::translation(0., -1., 0.)), Some(m3));
 
         let w = World::new(Some(light), vec![plane, s1, s2]);
@@ -559,7 +510,7 @@ mod tests {
         let mut s1 = w.shapes[0].clone();
         let mut material1 = s1.material().clone();
         material1.ambient = 1.0;
-        material1.pattern = Some(PatternType::Test(TestPattern::new(None)));
+        material1.pattern = Some(TestPattern::new_boxed(None));
         s1.set_material(material1);
 
         let mut s2 = w.shapes[1].clone();